@@ -0,0 +1,44 @@
+#!/bin/bash
+
+# Rebuilds src-tauri/resources/node-release-keys.asc, the Node.js release keys
+# bundled into the app, from https://github.com/nodejs/release-keys.
+# Only the keys pinned in src-tauri/resources/node-release-keys.txt are
+# fetched, and each one is checked against its fingerprint before it is kept.
+
+set -euo pipefail
+
+KEYS_URL="https://github.com/nodejs/release-keys/raw/HEAD/keys"
+RESOURCES="$(cd "$(dirname "$0")/../src-tauri/resources" && pwd)"
+PINNED="$RESOURCES/node-release-keys.txt"
+BUNDLE="$RESOURCES/node-release-keys.asc"
+
+GNUPGHOME="$(mktemp -d)"
+export GNUPGHOME
+trap 'rm -rf "$GNUPGHOME"' EXIT
+
+OUT="$GNUPGHOME/bundle.asc"
+{
+    echo "Node.js release keys, pinned in node-release-keys.txt."
+    echo "Generated by scripts/update_node_release_keys.sh; do not edit by hand."
+    echo
+} > "$OUT"
+
+grep -v '^#' "$PINNED" | while read -r fingerprint name; do
+    [ -z "$fingerprint" ] && continue
+    echo "Fetching $fingerprint ($name)..."
+    key="$GNUPGHOME/$fingerprint.asc"
+    curl -fsSL "$KEYS_URL/$fingerprint.asc" -o "$key"
+
+    # The file must hold exactly the pinned key
+    found="$(gpg --batch --with-colons --import-options show-only --import "$key" 2>/dev/null \
+        | awk -F: '$1 == "pub" { getline; print $10 }')"
+    if [ "$found" != "$fingerprint" ]; then
+        echo "Key for $fingerprint has fingerprint '$found', aborting" >&2
+        exit 1
+    fi
+    cat "$key" >> "$OUT"
+    echo >> "$OUT"
+done
+
+mv "$OUT" "$BUNDLE"
+echo "Wrote $BUNDLE"
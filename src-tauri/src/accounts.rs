@@ -0,0 +1,96 @@
+// Commands for managing several signed-in WayStation accounts
+
+use crate::credentials::{CredentialStoreManager, StoredAccounts};
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::token_refresh::{needs_refresh, refresh_auth_data, REFRESH_LOCK};
+use crate::{save_way_key, sign_out, AuthData, LogoutResult};
+use log::{info, warn};
+use serde::Serialize;
+use tauri::State;
+
+#[derive(Debug, Serialize, Clone)]
+pub struct AccountSummary {
+    pub sub: String,
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub picture: Option<String>,
+    pub active: bool,
+}
+
+fn load_accounts(credentials: &CredentialStoreManager) -> CommandResult<StoredAccounts> {
+    credentials
+        .0
+        .load_accounts()
+        .map_err(|e| CommandError::new(ErrorCode::CredentialStorage, e))
+}
+
+fn account_not_found(sub: &str) -> CommandError {
+    CommandError::new(ErrorCode::NotFound, format!("No account found for {}", sub))
+}
+
+#[tauri::command]
+pub async fn list_accounts(
+    credentials: State<'_, CredentialStoreManager>,
+) -> CommandResult<Vec<AccountSummary>> {
+    let stored = load_accounts(&credentials)?;
+
+    Ok(stored
+        .accounts
+        .iter()
+        .map(|(id, auth_data)| {
+            let user_info = auth_data.user_info.as_ref();
+            AccountSummary {
+                sub: id.clone(),
+                name: user_info.and_then(|u| u.name.clone()),
+                email: user_info.and_then(|u| u.email.clone()),
+                picture: user_info.and_then(|u| u.picture.clone()),
+                active: stored.active.as_deref() == Some(id.as_str()),
+            }
+        })
+        .collect())
+}
+
+/// Makes another signed-in account active and exports its token to the MCP
+/// token file.
+#[tauri::command]
+pub async fn switch_account(
+    sub: String,
+    credentials: State<'_, CredentialStoreManager>,
+) -> CommandResult<AuthData> {
+    if !load_accounts(&credentials)?.accounts.contains_key(&sub) {
+        return Err(account_not_found(&sub));
+    }
+    let auth_data = credentials
+        .0
+        .set_active(&sub)
+        .map_err(|e| CommandError::new(ErrorCode::CredentialStorage, e))?;
+    info!("Switched to account {}", sub);
+
+    // Inactive accounts aren't refreshed in the background, so their access
+    // token may have expired in the meantime
+    if needs_refresh(&auth_data) {
+        match refresh_auth_data(credentials.0.as_ref()).await {
+            Ok(refreshed) => return Ok(refreshed),
+            Err(e) => warn!("Failed to refresh switched-to account: {}", e),
+        }
+    }
+
+    save_way_key(auth_data.access_token.clone()).await?;
+    Ok(auth_data)
+}
+
+/// Signs out of a single account, active or not.
+#[tauri::command]
+pub async fn remove_account(
+    sub: String,
+    credentials: State<'_, CredentialStoreManager>,
+) -> CommandResult<LogoutResult> {
+    let _refresh_guard = REFRESH_LOCK.lock().await;
+    let stored = load_accounts(&credentials)?;
+    let auth_data = stored
+        .accounts
+        .get(&sub)
+        .ok_or_else(|| account_not_found(&sub))?;
+
+    Ok(sign_out(credentials.0.as_ref(), auth_data).await)
+}
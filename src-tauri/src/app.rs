@@ -1,24 +1,80 @@
 // Modified from original Apache 2.0 licensed code: Removed unused commands and adjusted for WayStation MCP
 
-use crate::file_utils::{ensure_config_file, ensure_mcp_servers};
+use crate::claude_process::{shutdown_claude, ShutdownReport, DEFAULT_SHUTDOWN_TIMEOUT};
+use crate::config_backup;
+use crate::environment::{ensure_environment_sync, get_node_paths};
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::file_utils::{
+    ensure_config_file, ensure_mcp_servers, lock_file, merge_json, sha256_hex, write_atomic,
+    FileLock,
+};
+use crate::mcp_clients::{all_clients, resolve_clients, ClientChange, McpClient};
+use crate::mcp_servers::McpServerSpec;
 use dirs;
 use lazy_static::lazy_static;
 use log::{debug, error, info, warn};
-use serde_json::{json, Value};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::sync::Mutex;
-use std::thread::sleep;
 use std::time::Duration;
+use tauri::AppHandle;
+
+/// The config as read from disk, together with the hash of the file it was
+/// parsed from, so edits made to the file since it was read are merged
+/// rather than overwritten when the edited copy is saved.
+#[derive(Debug, Clone)]
+pub struct ConfigSnapshot {
+    pub value: Value,
+    sha256: String,
+}
+
+/// A read-modify-write of the config. The config lock is taken before the
+/// config is read and held until the edit is saved or dropped.
+pub struct ConfigEdit {
+    base: ConfigSnapshot,
+    _lock: FileLock,
+}
+
+impl ConfigEdit {
+    pub fn begin() -> CommandResult<Self> {
+        let config_path = get_config_path()?;
+        let lock = lock_config(&config_path)?;
+        Ok(Self {
+            base: load_config(&config_path)?,
+            _lock: lock,
+        })
+    }
+
+    /// The config as it was read.
+    pub fn value(&self) -> &Value {
+        &self.base.value
+    }
+
+    /// Writes `config`, an edited copy of `value()`. The lock is held until
+    /// the edit is dropped, so files kept in step with the config can be
+    /// written under it too.
+    pub fn save(&self, config: &Value, reason: &str) -> CommandResult<()> {
+        save_config(&self.base, config, reason)
+    }
+}
+
+pub(crate) const WAYSTATION_MCP_NAME: &str = "WayStation";
+const WAYSTATION_MCP_PACKAGE: &str = "@waystation/mcp";
+// Created in the app directory once onboarding is done
+const ONBOARDING_MARKER: &str = "onboarding_completed";
 
 lazy_static! {
-    static ref CONFIG_CACHE: Mutex<Option<Value>> = Mutex::new(None);
+    static ref CONFIG_CACHE: Mutex<Option<ConfigSnapshot>> = Mutex::new(None);
     static ref ENV_SETUP_COMPLETE: Mutex<bool> = Mutex::new(false);
 }
 
 // Function to get the config path - uses test path if set
-fn get_config_path() -> Result<PathBuf, String> {
+pub(crate) fn get_config_path() -> CommandResult<PathBuf> {
     debug!(
         "Getting config path, test_mode: {}",
         crate::environment::is_test_mode()
@@ -28,7 +84,7 @@ fn get_config_path() -> Result<PathBuf, String> {
     {
         // Use macOS-specific path
         let default_path = dirs::home_dir()
-            .ok_or("Could not find home directory".to_string())?
+            .ok_or_else(|| CommandError::new(ErrorCode::ConfigIo, "Could not find home directory"))?
             .join("Library/Application Support/Claude/claude_desktop_config.json");
         debug!("Using default config path: {}", default_path.display());
         return Ok(default_path);
@@ -38,7 +94,9 @@ fn get_config_path() -> Result<PathBuf, String> {
     {
         // Use Windows-specific path
         let default_path = dirs::config_dir()
-            .ok_or("Could not find config directory".to_string())?
+            .ok_or_else(|| {
+                CommandError::new(ErrorCode::ConfigIo, "Could not find config directory")
+            })?
             .join("Claude/claude_desktop_config.json");
         debug!("Using default config path: {}", default_path.display());
         return Ok(default_path);
@@ -48,73 +106,192 @@ fn get_config_path() -> Result<PathBuf, String> {
     {
         // Fallback for other platforms
         let default_path = dirs::config_dir()
-            .ok_or("Could not find config directory".to_string())?
+            .ok_or_else(|| {
+                CommandError::new(ErrorCode::ConfigIo, "Could not find config directory")
+            })?
             .join("claude/claude_desktop_config.json");
         debug!("Using default config path: {}", default_path.display());
         return Ok(default_path);
     }
 }
 
-pub fn get_config() -> Result<Value, String> {
+pub fn get_config() -> CommandResult<Value> {
+    read_config().map(|snapshot| snapshot.value)
+}
+
+// Reads the config along with what `save_config` needs to merge changes
+// made to it
+fn read_config() -> CommandResult<ConfigSnapshot> {
     debug!(
         "Getting config, test_mode: {}",
         crate::environment::is_test_mode()
     );
 
-    let mut cache = CONFIG_CACHE.lock().unwrap();
-    if let Some(ref config) = *cache {
-        debug!("Using cached config");
-        return Ok(config.clone());
-    }
-
     let config_path = get_config_path()?;
     debug!("Using config path: {}", config_path.display());
 
+    if config_path.exists() {
+        return load_config(&config_path);
+    }
+    // Creating the file is a write like any other
+    let _lock = lock_config(&config_path)?;
+    load_config(&config_path)
+}
+
+// Reads and caches the config, creating it if it's missing. The caller holds
+// the lock unless the file exists.
+fn load_config(config_path: &Path) -> CommandResult<ConfigSnapshot> {
     if !config_path.exists() {
         info!("Config file does not exist, creating it");
-        ensure_config_file(&config_path)?;
+        ensure_config_file(config_path).map_err(|e| CommandError::new(ErrorCode::ConfigIo, e))?;
     }
 
-    let config_str = fs::read_to_string(&config_path).map_err(|e| {
-        error!("Failed to read config file: {}", e);
-        format!("Failed to read config file: {}", e)
-    })?;
+    let config_bytes = read_config_file(config_path)?;
+    let sha256 = sha256_hex(&config_bytes);
 
-    let mut config_json: Value = serde_json::from_str(&config_str).map_err(|e| {
-        error!("Failed to parse config JSON: {}", e);
-        format!("Failed to parse config JSON: {}", e)
-    })?;
+    // The file is re-hashed on every read so edits made by hand or by other
+    // tools while we are running are picked up
+    let mut cache = CONFIG_CACHE.lock().unwrap();
+    if let Some(cached) = cache.as_ref() {
+        if cached.sha256 == sha256 {
+            debug!("Using cached config");
+            return Ok(cached.clone());
+        }
+        info!("Config file changed on disk, reloading");
+    }
 
-    ensure_mcp_servers(&mut config_json)?;
+    let mut config_json = parse_config(config_path, &config_bytes)?;
+    ensure_mcp_servers(&mut config_json)
+        .map_err(|e| CommandError::new(ErrorCode::ConfigCorrupt, e))?;
 
-    *cache = Some(config_json.clone());
+    let snapshot = ConfigSnapshot {
+        value: config_json,
+        sha256,
+    };
+    *cache = Some(snapshot.clone());
     debug!("Config loaded and cached successfully");
-    Ok(config_json)
+    Ok(snapshot)
+}
+
+/// The config as it is on disk, or `None` if there is no config file yet.
+/// Unlike `get_config` this never creates the file.
+pub fn read_existing_config() -> CommandResult<Option<Value>> {
+    let config_path = get_config_path()?;
+    if !config_path.exists() {
+        return Ok(None);
+    }
+    let config_bytes = read_config_file(&config_path)?;
+    parse_config(&config_path, &config_bytes).map(Some)
 }
 
-pub fn save_config(config: &Value) -> Result<(), String> {
+fn read_config_file(config_path: &Path) -> CommandResult<Vec<u8>> {
+    fs::read(config_path).map_err(|e| {
+        error!("Failed to read config file: {}", e);
+        CommandError::new(ErrorCode::ConfigIo, "Failed to read config file")
+            .with_details(e.to_string())
+    })
+}
+
+fn parse_config(config_path: &Path, config_bytes: &[u8]) -> CommandResult<Value> {
+    serde_json::from_slice(config_bytes).map_err(|e| {
+        error!("Failed to parse config JSON: {}", e);
+        CommandError::new(ErrorCode::ConfigCorrupt, "Failed to parse config JSON")
+            .with_details(format!("{}: {}", config_path.display(), e))
+    })
+}
+
+/// Writes `config`, an edited copy of `base`, to disk. `reason` is recorded
+/// with the backup of the config it replaces. The caller holds the lock.
+///
+/// Claude Desktop doesn't honour our lock, so if the file was changed since
+/// `base` was read, those edits are three-way merged with ours; conflicting
+/// edits to the same value make the save fail with `ConfigConflict` instead
+/// of overwriting them.
+fn save_config(base: &ConfigSnapshot, config: &Value, reason: &str) -> CommandResult<()> {
     let config_path = get_config_path()?;
     debug!("Saving config to {}", config_path.display());
 
-    let updated_config = serde_json::to_string_pretty(config).map_err(|e| {
+    let mut cache = CONFIG_CACHE.lock().unwrap();
+
+    let on_disk = if config_path.exists() {
+        Some(read_config_file(&config_path)?)
+    } else {
+        None
+    };
+    let config = match &on_disk {
+        Some(on_disk) if base.sha256 != sha256_hex(on_disk) => {
+            warn!("Config file changed on disk since it was read, merging");
+            let mut theirs = parse_config(&config_path, on_disk)?;
+            // Normalize like the cached copy so that isn't mistaken for an edit
+            ensure_mcp_servers(&mut theirs)
+                .map_err(|e| CommandError::new(ErrorCode::ConfigCorrupt, e))?;
+            merge_json(&base.value, config, &theirs).map_err(|path| {
+                error!("Conflicting config edit at {}", path);
+                CommandError::new(
+                    ErrorCode::ConfigConflict,
+                    "The config file was changed by another program",
+                )
+                .with_details(format!("Conflicting edit at {}", path))
+            })?
+        }
+        _ => config.clone(),
+    };
+
+    let updated_config = serde_json::to_string_pretty(&config).map_err(|e| {
         error!("Failed to serialize config: {}", e);
-        format!("Failed to serialize config: {}", e)
+        CommandError::new(ErrorCode::Internal, "Failed to serialize config")
+            .with_details(e.to_string())
     })?;
 
-    fs::write(&config_path, updated_config).map_err(|e| {
-        error!("Failed to write config file: {}", e);
-        format!("Failed to write config file: {}", e)
-    })?;
+    write_config_file(&config_path, updated_config.as_bytes(), reason)?;
 
     // Update cache
-    let mut cache = CONFIG_CACHE.lock().unwrap();
-    *cache = Some(config.clone());
+    *cache = Some(ConfigSnapshot {
+        value: config,
+        sha256: sha256_hex(updated_config.as_bytes()),
+    });
     info!("Config saved successfully");
 
     Ok(())
 }
 
-fn get_claude_path() -> Option<PathBuf> {
+/// Replaces the config file with raw `contents`, e.g. a restored backup. The
+/// cache is dropped so the next read parses what was written.
+pub(crate) fn replace_config(contents: &[u8], reason: &str) -> CommandResult<()> {
+    let config_path = get_config_path()?;
+    let _lock = lock_config(&config_path)?;
+    write_config_file(&config_path, contents, reason)?;
+    *CONFIG_CACHE.lock().unwrap() = None;
+    Ok(())
+}
+
+// Claude Desktop reads this file on startup; never leave it half written
+fn lock_config(config_path: &Path) -> CommandResult<FileLock> {
+    lock_file(config_path).map_err(|e| {
+        error!("Failed to lock config file: {}", e);
+        CommandError::new(
+            ErrorCode::ConfigLocked,
+            "The config file is in use by another process",
+        )
+        .with_details(e)
+    })
+}
+
+// Backs up the current file, then replaces it. The caller holds the lock.
+fn write_config_file(config_path: &Path, contents: &[u8], reason: &str) -> CommandResult<()> {
+    // Refuse to write without a backup to roll back to
+    config_backup::snapshot(config_path, reason).map_err(|e| {
+        error!("Failed to back up config file: {}", e);
+        CommandError::new(ErrorCode::Io, "Failed to back up config file").with_details(e)
+    })?;
+
+    write_atomic(config_path, contents).map_err(|e| {
+        error!("Failed to write config file: {}", e);
+        CommandError::new(ErrorCode::ConfigIo, "Failed to write config file").with_details(e)
+    })
+}
+
+pub(crate) fn get_claude_path() -> Option<PathBuf> {
     #[cfg(target_os = "macos")]
     {
         let claude_app_path = PathBuf::from("/Applications/Claude.app");
@@ -145,116 +322,417 @@ fn get_claude_path() -> Option<PathBuf> {
         }
     }
 
+    #[cfg(target_os = "linux")]
+    {
+        if let Some(install) = crate::claude_linux::find_claude() {
+            return Some(install.path().to_path_buf());
+        }
+    }
+
     None
 }
 
+/// Quits Claude Desktop gracefully (see `claude_process::shutdown_claude`) and
+/// launches it again. `timeout_ms` is how long to wait before force-killing.
 #[tauri::command]
-pub fn restart_claude_app() -> Result<String, String> {
+pub async fn restart_claude_app(timeout_ms: Option<u64>) -> CommandResult<ShutdownReport> {
+    // Waiting for Claude to exit takes seconds, so keep it off the main thread
+    tauri::async_runtime::spawn_blocking(move || restart_claude_blocking(timeout_ms))
+        .await
+        .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+}
+
+/// Blocking version of `restart_claude_app`, for code already off the main
+/// thread.
+pub fn restart_claude_blocking(timeout_ms: Option<u64>) -> CommandResult<ShutdownReport> {
     info!("Restarting Claude app...");
 
-    #[cfg(target_os = "macos")]
-    {
-        // Kill the Claude app
-        Command::new("pkill")
-            .arg("-x")
-            .arg("Claude")
-            .output()
-            .map_err(|e| format!("Failed to kill Claude app: {}", e))?;
+    // Only wait for processes of an installation we can relaunch
+    if get_claude_path().is_none() {
+        debug!("Claude installation not found");
+        return Err(CommandError::new(
+            ErrorCode::ClaudeNotInstalled,
+            "Claude installation not found",
+        ));
+    }
 
-        // Wait a moment to ensure it's fully closed
-        sleep(Duration::from_millis(500));
+    let report = shutdown_claude(
+        timeout_ms
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT),
+    )?;
 
+    #[cfg(target_os = "macos")]
+    {
         // Relaunch the app
         Command::new("open")
             .arg("-a")
             .arg("Claude")
             .output()
-            .map_err(|e| format!("Failed to relaunch Claude app: {}", e))?;
+            .map_err(|e| {
+                CommandError::new(
+                    ErrorCode::ClaudeRestartFailed,
+                    "Failed to relaunch Claude app",
+                )
+                .with_details(e.to_string())
+            })?;
     }
 
     #[cfg(target_os = "windows")]
     {
-        // Kill the Claude app using taskkill
-        Command::new("taskkill")
-            .args(&["/F", "/IM", "Claude.exe"])
-            .output()
-            .map_err(|e| format!("Failed to kill Claude app: {}", e))?;
-
-        // Wait a moment to ensure it's fully closed
-        sleep(Duration::from_millis(500));
-
         if let Some(path) = get_claude_path() {
             debug!("Claude installation found at: {}", path.display());
 
             // Relaunch the app
             Command::new(path.to_str().unwrap())
             .spawn()
-            .map_err(|e| format!("Failed to relaunch Claude app: {}", e))?;
-        } else {
-            debug!("Claude installation not found");
-            return Err("Claude installation not found".to_string());
+            .map_err(|e| {
+                CommandError::new(
+                    ErrorCode::ClaudeRestartFailed,
+                    "Failed to relaunch Claude app",
+                )
+                .with_details(e.to_string())
+            })?;
         }
     }
 
-    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    #[cfg(target_os = "linux")]
+    {
+        if let Some(install) = crate::claude_linux::find_claude() {
+            debug!(
+                "Claude installation ({}) found at: {}",
+                install.kind(),
+                install.path().display()
+            );
+
+            // Relaunch the app
+            install.launch().map_err(|e| {
+                CommandError::new(
+                    ErrorCode::ClaudeRestartFailed,
+                    "Failed to relaunch Claude app",
+                )
+                .with_details(e)
+            })?;
+        }
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
     {
-        return Err("Restarting Claude app is not supported on this platform".to_string());
+        return Err(CommandError::new(
+            ErrorCode::UnsupportedPlatform,
+            "Restarting Claude app is not supported on this platform",
+        ));
+    }
+
+    Ok(report)
+}
+
+// The MCP server's PATH: node's own directory first so npx finds the right
+// node, then the PATH we were started with, then the system directories.
+// Clients launched from the Dock or a desktop entry don't inherit the user's
+// shell PATH, so nvm and friends are never on it otherwise.
+fn server_path(node_dir: &Path) -> CommandResult<String> {
+    let inherited: Vec<PathBuf> = env::var_os("PATH")
+        .map(|path| env::split_paths(&path).collect())
+        .unwrap_or_default();
+    let mut system_dirs = Vec::new();
+    if cfg!(target_os = "windows") {
+        if let Some(system_root) = env::var_os("SystemRoot").map(PathBuf::from) {
+            system_dirs.push(system_root.join("System32"));
+            system_dirs.push(system_root);
+        }
+    } else {
+        system_dirs.extend(
+            ["/usr/local/bin", "/usr/bin", "/bin", "/usr/sbin", "/sbin"]
+                .iter()
+                .map(PathBuf::from),
+        );
+    }
+    let mut dirs = vec![node_dir.to_path_buf()];
+    for dir in inherited.into_iter().chain(system_dirs) {
+        if !dir.as_os_str().is_empty() && !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
     }
+    let path = env::join_paths(dirs).map_err(|e| {
+        CommandError::new(ErrorCode::EnvironmentSetupFailed, "Invalid Node.js path")
+            .with_details(e.to_string())
+    })?;
+    Ok(path.to_string_lossy().to_string())
+}
 
-    Ok("Claude app restarted successfully".to_string())
+// The WayStation MCP server entry, launched with the Node.js found by
+// environment setup rather than whatever `npx` the client's PATH has
+fn waystation_server_spec() -> CommandResult<McpServerSpec> {
+    let (node, npx) =
+        get_node_paths().map_err(|e| CommandError::new(ErrorCode::EnvironmentSetupFailed, e))?;
+    let node_dir = Path::new(&node)
+        .parent()
+        .map(Path::to_path_buf)
+        .ok_or_else(|| {
+            CommandError::new(
+                ErrorCode::EnvironmentSetupFailed,
+                format!("Unexpected Node.js path {}", node),
+            )
+        })?;
+    let mut args = vec!["-y".to_string(), WAYSTATION_MCP_PACKAGE.to_string()];
+
+    // npx.cmd can't be spawned without a shell on Windows, so run npm's npx
+    // script with node directly. npx.cmd itself runs the copy next to it.
+    let command = if cfg!(target_os = "windows") {
+        let npx_dir = Path::new(&npx).parent().unwrap_or(&node_dir);
+        let npx_script = [node_dir.as_path(), npx_dir]
+            .iter()
+            .map(|dir| dir.join("node_modules/npm/bin/npx-cli.js"))
+            .find(|script| script.is_file())
+            .ok_or_else(|| {
+                CommandError::new(
+                    ErrorCode::EnvironmentSetupFailed,
+                    "Could not find npm's npx script for this Node.js",
+                )
+                .with_details(format!("Looked next to {} and {}", node, npx))
+            })?;
+        args.insert(0, npx_script.to_string_lossy().to_string());
+        node
+    } else {
+        npx
+    };
+
+    let mut env = BTreeMap::new();
+    env.insert("PATH".to_string(), server_path(&node_dir)?);
+    Ok(McpServerSpec {
+        command,
+        args,
+        env,
+        cwd: None,
+    })
 }
 
+// Applies `change` to each client. Per-client failures are reported in the
+// result; the command only fails when every targeted client failed.
+fn change_clients(
+    clients: Option<Vec<String>>,
+    project_dir: Option<String>,
+    change: impl Fn(&dyn McpClient) -> CommandResult<bool>,
+) -> CommandResult<Vec<ClientChange>> {
+    let clients = resolve_clients(clients, project_dir.map(PathBuf::from))?;
+    if clients.is_empty() {
+        return Err(CommandError::new(
+            ErrorCode::NotFound,
+            "No supported MCP clients were found",
+        ));
+    }
+
+    let results: Vec<ClientChange> = clients
+        .iter()
+        .map(|client| {
+            let result = change(client.as_ref());
+            if let Err(e) = &result {
+                error!("{}: {}", client.name(), e);
+            }
+            ClientChange {
+                client: client.id(),
+                changed: *result.as_ref().unwrap_or(&false),
+                restart_required: client.restart_required(),
+                error: result.err(),
+            }
+        })
+        .collect();
+
+    let all_failed = results.iter().all(|result| result.error.is_some());
+    if let Some(error) = results.iter().find_map(|result| result.error.clone()) {
+        if all_failed {
+            return Err(error);
+        }
+    }
+    Ok(results)
+}
+
+/// Adds the WayStation MCP server to the given clients (see
+/// `mcp_clients::resolve_clients`); Claude Desktop when none are given.
+/// `project_dir` is the project whose `.mcp.json` to edit, if any.
+/// Node.js is set up first, since the entry points at it.
 #[tauri::command]
-pub fn install_waystation_mcp() -> Result<String, String> {
-    info!("Installing waystation-mcp...");
+pub async fn install_waystation_mcp(
+    app_handle: AppHandle,
+    clients: Option<Vec<String>>,
+    project_dir: Option<String>,
+) -> CommandResult<Vec<ClientChange>> {
+    // Setting up Node.js may mean installing it, so keep it off the main thread
+    tauri::async_runtime::spawn_blocking(move || {
+        install_waystation_mcp_blocking(&app_handle, clients, project_dir)
+    })
+    .await
+    .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+}
 
-    let mut config_json = get_config()?;
+fn install_waystation_mcp_blocking(
+    app_handle: &AppHandle,
+    clients: Option<Vec<String>>,
+    project_dir: Option<String>,
+) -> CommandResult<Vec<ClientChange>> {
+    info!("Installing waystation-mcp...");
 
-    if let Some(mcp_servers) = config_json
-        .get_mut("mcpServers")
-        .and_then(|v| v.as_object_mut())
-    {
-        let app_config = json!({
-            "command": "npx",
-            "args": ["-y", "@waystation/mcp"]
-        });
+    // Progress shows up as environment-progress events
+    ensure_environment_sync(app_handle)?;
+    let spec = waystation_server_spec()?;
+    let results = change_clients(clients, project_dir, |client| {
+        debug!("Adding config for waystation to {}", client.name());
+        client
+            .install_server(WAYSTATION_MCP_NAME, &spec)
+            .map(|()| true)
+    })?;
 
-        debug!("Adding config for waystation: {:?}", app_config);
-        mcp_servers.insert("WayStation".to_string(), app_config);
-        save_config(&config_json)?;
+    info!("Successfully installed waystation-mcp");
+    Ok(results)
+}
 
-        info!("Successfully installed waystation-mcp");
-        Ok("Added waystation-mcp configuration".to_string())
-    } else {
-        let err = "Failed to find mcpServers in config".to_string();
-        error!("{}", err);
-        Err(err)
+/// Points existing WayStation entries at the Node.js currently in use, e.g.
+/// after the preferred runtime changed. Clients without the entry are left
+/// alone. Returns the ids of the clients that were updated.
+pub fn repoint_waystation_mcp() -> CommandResult<Vec<&'static str>> {
+    let spec = waystation_server_spec()?;
+    let mut updated = Vec::new();
+    for client in all_clients().iter().filter(|client| client.is_detected()) {
+        let entry = match client.server_config(WAYSTATION_MCP_NAME) {
+            Ok(Some(entry)) => entry,
+            Ok(None) => continue,
+            Err(e) => {
+                warn!("{}: {}", client.name(), e);
+                continue;
+            }
+        };
+        if entry == client.server_entry(&spec) {
+            continue;
+        }
+        match client.install_server(WAYSTATION_MCP_NAME, &spec) {
+            Ok(()) => {
+                info!("Pointed {} at {}", client.name(), spec.command);
+                updated.push(client.id());
+            }
+            Err(e) => warn!("Failed to update {}: {}", client.name(), e),
+        }
     }
+    Ok(updated)
 }
 
 #[tauri::command]
-pub fn uninstall_waystation_mcp() -> Result<String, String> {
+pub fn uninstall_waystation_mcp(
+    clients: Option<Vec<String>>,
+    project_dir: Option<String>,
+) -> CommandResult<Vec<ClientChange>> {
     info!("Uninstalling waystation-mcp...");
 
-    let mut config_json = get_config()?;
+    let results = change_clients(clients, project_dir, |client| {
+        let removed = client.remove_server(WAYSTATION_MCP_NAME)?;
+        if !removed {
+            warn!(
+                "waystation-mcp configuration was not found in {}",
+                client.name()
+            );
+        }
+        Ok(removed)
+    })?;
+
+    info!("Successfully uninstalled waystation-mcp");
+    Ok(results)
+}
 
-    if let Some(mcp_servers) = config_json
-        .get_mut("mcpServers")
-        .and_then(|v| v.as_object_mut())
-    {
-        if mcp_servers.remove("WayStation").is_some() {
-            save_config(&config_json)?;
-            info!("Successfully uninstalled waystation-mcp");
-            Ok("Removed waystation-mcp configuration".to_string())
-        } else {
-            warn!("waystation-mcp configuration was not found");
-            Ok("waystation-mcp configuration was not found".to_string())
+/// What `uninstall_waystation` managed to undo. Every step is attempted even
+/// if an earlier one failed; failures are collected in `errors`.
+#[derive(Debug, Default, Serialize)]
+pub struct UninstallReport {
+    pub config_entry_removed: bool,
+    pub token_deleted: bool,
+    pub onboarding_reset: bool,
+    pub npx_cache_cleared: bool,
+    pub claude_restarted: bool,
+    pub errors: Vec<String>,
+}
+
+/// Removes everything the launcher set up for the WayStation MCP server: the
+/// config entries, the MCP token file and the onboarding marker, optionally
+/// the cached npm package, and finally restarts Claude so it drops the server.
+/// Accounts stay signed in; signing out is up to the user.
+#[tauri::command]
+pub async fn uninstall_waystation(
+    clear_npx_cache: Option<bool>,
+    restart_claude: Option<bool>,
+) -> CommandResult<UninstallReport> {
+    info!("Uninstalling WayStation...");
+
+    // A refresh in flight would write the MCP token file again after we
+    // delete it; later refreshes leave a missing file alone
+    let _refresh_guard = crate::token_refresh::REFRESH_LOCK.lock().await;
+
+    // Restarting Claude waits for it to exit, so keep it off the main thread
+    tauri::async_runtime::spawn_blocking(move || {
+        uninstall_waystation_blocking(clear_npx_cache, restart_claude)
+    })
+    .await
+    .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+}
+
+fn uninstall_waystation_blocking(
+    clear_npx_cache: Option<bool>,
+    restart_claude: Option<bool>,
+) -> CommandResult<UninstallReport> {
+    let mut report = UninstallReport::default();
+
+    // Remove the server from every client we can find, not just Claude Desktop
+    match uninstall_waystation_mcp(Some(vec!["all".to_string()]), None) {
+        Ok(results) => {
+            report.config_entry_removed = results.iter().any(|r| r.changed);
+            for result in results {
+                if let Some(e) = result.error {
+                    report.errors.push(format!("{}: {}", result.client, e));
+                }
+            }
         }
-    } else {
-        let err = "Failed to find mcpServers in config".to_string();
-        error!("{}", err);
-        Err(err)
+        Err(e) => report.errors.push(e.to_string()),
     }
+
+    match crate::delete_way_key() {
+        Ok(()) => report.token_deleted = true,
+        Err(e) => report.errors.push(e),
+    }
+
+    let onboarding_file =
+        get_app_directory().map(|app_directory| app_directory.join(ONBOARDING_MARKER));
+    match onboarding_file {
+        Ok(path) if path.exists() => match fs::remove_file(&path) {
+            Ok(()) => report.onboarding_reset = true,
+            Err(e) => report
+                .errors
+                .push(format!("Failed to delete onboarding marker: {}", e)),
+        },
+        Ok(_) => report.onboarding_reset = true,
+        Err(e) => report.errors.push(e),
+    }
+
+    if clear_npx_cache.unwrap_or(false) {
+        match crate::environment::clear_npx_cache(WAYSTATION_MCP_PACKAGE) {
+            Ok(removed) => {
+                debug!("Removed {} npx cache entries", removed);
+                report.npx_cache_cleared = true;
+            }
+            Err(e) => report.errors.push(e),
+        }
+    }
+
+    // Only restart a Claude that is actually there to pick up the change
+    if restart_claude.unwrap_or(true) && get_claude_path().is_some() {
+        match restart_claude_blocking(None) {
+            Ok(_) => report.claude_restarted = true,
+            Err(e) => report.errors.push(e.to_string()),
+        }
+    }
+
+    for e in &report.errors {
+        warn!("Uninstall: {}", e);
+    }
+    info!("WayStation uninstalled");
+    Ok(report)
 }
 
 pub fn get_app_directory() -> Result<std::path::PathBuf, String> {
@@ -278,16 +756,16 @@ pub fn get_app_directory() -> Result<std::path::PathBuf, String> {
 
 
 #[tauri::command]
-pub fn check_onboarding_completed() -> Result<bool, String> {
-    let app_directory = get_app_directory()?;
-    let onboarding_file = app_directory.join("onboarding_completed");
+pub fn check_onboarding_completed() -> CommandResult<bool> {
+    let app_directory = get_app_directory().map_err(|e| CommandError::new(ErrorCode::Io, e))?;
+    let onboarding_file = app_directory.join(ONBOARDING_MARKER);
 
     debug!("Checking onboarding file at: {}", onboarding_file.display());
     Ok(onboarding_file.exists())
 }
 
 #[tauri::command]
-pub fn check_claude_installed() -> Result<bool, String> {
+pub fn check_claude_installed() -> CommandResult<bool> {
     if let Some(path) = get_claude_path() {
         debug!("Claude installation found at: {}", path.display());
         Ok(true)
@@ -296,3 +774,118 @@ pub fn check_claude_installed() -> Result<bool, String> {
         Ok(false)
     }
 }
+
+// Reads the version of the Claude installation at `path`. On Linux the
+// version comes from the package that owns the install, see
+// `claude_linux::ClaudeInstall::version`.
+#[cfg(not(target_os = "linux"))]
+fn get_claude_version(path: &Path) -> Option<String> {
+    #[cfg(target_os = "macos")]
+    {
+        // Pull CFBundleShortVersionString out of the XML Info.plist
+        let plist = fs::read_to_string(path.join("Contents/Info.plist")).ok()?;
+        let after_key = plist
+            .split("<key>CFBundleShortVersionString</key>")
+            .nth(1)?;
+        let value = after_key
+            .split("<string>")
+            .nth(1)?
+            .split("</string>")
+            .next()?;
+        Some(value.trim().to_string())
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        // The version lives in the exe's version resource
+        let script = format!(
+            "(Get-Item -LiteralPath '{}').VersionInfo.ProductVersion",
+            path.display().to_string().replace('\'', "''")
+        );
+        let output = Command::new("powershell")
+            .args(["-NoProfile", "-NonInteractive", "-Command", &script])
+            .output()
+            .ok()?;
+        let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        (output.status.success() && !version.is_empty()).then_some(version)
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+// Path, version and running processes of Claude Desktop. Detection on Linux
+// runs package managers, so the installation is only looked up once.
+fn get_claude_install_state() -> (Option<PathBuf>, Option<String>, Vec<u32>) {
+    #[cfg(target_os = "linux")]
+    {
+        let Some(install) = crate::claude_linux::find_claude() else {
+            return (None, None, Vec::new());
+        };
+        // Processes can't be told apart for unresolved desktop launchers
+        let pids = install
+            .process_matcher()
+            .map(|matcher| crate::claude_process::claude_pids_matching(&matcher))
+            .unwrap_or_default();
+        (Some(install.path().to_path_buf()), install.version(), pids)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let path = get_claude_path();
+        let version = path.as_deref().and_then(get_claude_version);
+        (path, version, crate::claude_process::claude_pids())
+    }
+}
+
+/// Installation, process and config state of Claude Desktop.
+#[derive(Debug, Serialize)]
+pub struct ClaudeStatus {
+    pub installed: bool,
+    pub path: Option<PathBuf>,
+    pub version: Option<String>,
+    pub running: bool,
+    pub pids: Vec<u32>,
+    pub config_path: Option<PathBuf>,
+    pub config_exists: bool,
+    /// Whether the config file is valid JSON; false when it doesn't exist.
+    pub config_valid: bool,
+    pub config_error: Option<CommandError>,
+}
+
+/// Reports the state of Claude Desktop without changing anything, e.g. so a
+/// restart can be skipped when Claude isn't running.
+#[tauri::command]
+pub async fn get_claude_status() -> CommandResult<ClaudeStatus> {
+    // Detection runs package managers and scans processes, so keep it off the
+    // main thread
+    tauri::async_runtime::spawn_blocking(get_claude_status_blocking)
+        .await
+        .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+}
+
+fn get_claude_status_blocking() -> CommandResult<ClaudeStatus> {
+    let (path, version, pids) = get_claude_install_state();
+
+    let config_path = get_config_path().ok();
+    // Unlike get_config this doesn't create the file if it's missing
+    let config = read_existing_config();
+    let config_exists =
+        matches!(config, Ok(Some(_))) || config_path.as_ref().is_some_and(|path| path.exists());
+    let config_error = config.err();
+
+    Ok(ClaudeStatus {
+        installed: path.is_some(),
+        path,
+        version,
+        running: !pids.is_empty(),
+        pids,
+        config_path,
+        config_exists,
+        config_valid: config_exists && config_error.is_none(),
+        config_error,
+    })
+}
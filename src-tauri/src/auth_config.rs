@@ -0,0 +1,266 @@
+// Identity provider settings. The production WayStation tenant is the default;
+// another issuer (e.g. staging) can be selected through `~/.waystation/auth.json`
+// or WAYSTATION_AUTH_* environment variables, and its endpoints are discovered
+// from `/.well-known/openid-configuration`. When discovery of the production
+// tenant fails, only the endpoints the app has always used are filled in; the
+// others stay unset until discovery succeeds.
+
+use crate::app::get_app_directory;
+use log::{debug, info, warn};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::OnceCell;
+
+// Defaults for the production tenant, also used when discovery fails for it
+const DEFAULT_ISSUER: &str = "https://clerk.waystation.ai";
+const AUTH_URL: &str = "https://clerk.waystation.ai/oauth/authorize";
+const TOKEN_URL: &str = "https://clerk.waystation.ai/oauth/token";
+const USER_INFO_URL: &str = "https://clerk.waystation.ai/oauth/userinfo";
+const CLIENT_ID: &str = "5xEs1bi3TY8JNVHx";
+const REDIRECT_URI: &str = "waystation://oauth/callback";
+
+const SETTINGS_FILE: &str = "auth.json";
+const DISCOVERY_PATH: &str = "/.well-known/openid-configuration";
+// Applies to every request to the identity provider, so a server that stops
+// answering can't hang sign-in or the refresh scheduler
+const HTTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Only a configuration backed by a successful discovery is cached
+static AUTH_CONFIG: OnceCell<AuthConfig> = OnceCell::const_new();
+
+/// Resolved identity provider configuration.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthConfig {
+    pub issuer: String,
+    pub client_id: String,
+    pub redirect_uri: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub userinfo_endpoint: String,
+    pub device_authorization_endpoint: Option<String>,
+    /// Only missing when discovery failed; ID tokens can't be validated then.
+    pub jwks_uri: Option<String>,
+    pub revocation_endpoint: Option<String>,
+}
+
+// User-provided overrides; anything left unset is discovered or defaulted
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct AuthSettings {
+    issuer: Option<String>,
+    client_id: Option<String>,
+    redirect_uri: Option<String>,
+    authorization_endpoint: Option<String>,
+    token_endpoint: Option<String>,
+    userinfo_endpoint: Option<String>,
+    device_authorization_endpoint: Option<String>,
+    jwks_uri: Option<String>,
+    revocation_endpoint: Option<String>,
+}
+
+// Subset of the OpenID Provider Metadata we use
+#[derive(Debug, Deserialize)]
+struct DiscoveryDocument {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    userinfo_endpoint: Option<String>,
+    device_authorization_endpoint: Option<String>,
+    jwks_uri: Option<String>,
+    revocation_endpoint: Option<String>,
+}
+
+/// Returns the identity provider configuration, resolving it on first use.
+/// When discovery fails the fallback endpoints are used for this call only,
+/// so a network blip at startup doesn't stick until the app restarts.
+pub async fn get_auth_config() -> Result<AuthConfig, String> {
+    if let Some(config) = AUTH_CONFIG.get() {
+        return Ok(config.clone());
+    }
+    let (config, discovered) = resolve_auth_config().await?;
+    if discovered {
+        let _ = AUTH_CONFIG.set(config.clone());
+    }
+    Ok(config)
+}
+
+/// HTTP client for requests to the identity provider.
+pub fn http_client() -> Client {
+    Client::builder()
+        .timeout(HTTP_TIMEOUT)
+        .build()
+        .unwrap_or_else(|e| {
+            warn!("Failed to build HTTP client, using defaults: {}", e);
+            Client::new()
+        })
+}
+
+fn settings_path() -> Result<PathBuf, String> {
+    match std::env::var("WAYSTATION_AUTH_CONFIG") {
+        Ok(path) => Ok(PathBuf::from(path)),
+        Err(_) => Ok(get_app_directory()?.join(SETTINGS_FILE)),
+    }
+}
+
+fn load_settings() -> Result<AuthSettings, String> {
+    let path = settings_path()?;
+    let mut settings = if path.exists() {
+        debug!("Loading auth settings from {}", path.display());
+        let json_data = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read auth settings: {}", e))?;
+        serde_json::from_str(&json_data)
+            .map_err(|e| format!("Failed to parse auth settings: {}", e))?
+    } else {
+        AuthSettings::default()
+    };
+
+    // Environment variables take precedence over the settings file
+    let env = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+    if let Some(issuer) = env("WAYSTATION_AUTH_ISSUER") {
+        settings.issuer = Some(issuer);
+    }
+    if let Some(client_id) = env("WAYSTATION_AUTH_CLIENT_ID") {
+        settings.client_id = Some(client_id);
+    }
+    if let Some(redirect_uri) = env("WAYSTATION_AUTH_REDIRECT_URI") {
+        settings.redirect_uri = Some(redirect_uri);
+    }
+
+    Ok(settings)
+}
+
+async fn discover(issuer: &str) -> Result<DiscoveryDocument, String> {
+    let url = format!("{}{}", issuer, DISCOVERY_PATH);
+    debug!("Fetching OpenID configuration from {}", url);
+
+    let response = http_client()
+        .get(&url)
+        .send()
+        .await
+        .map_err(|e| format!("OpenID discovery failed: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "OpenID discovery failed with status {}",
+            response.status()
+        ));
+    }
+
+    let document: DiscoveryDocument = response
+        .json()
+        .await
+        .map_err(|e| format!("Invalid OpenID configuration: {}", e))?;
+
+    // OpenID Connect Discovery 1.0, section 4.3
+    if document.issuer.trim_end_matches('/') != issuer {
+        return Err(format!(
+            "OpenID configuration issuer {} does not match {}",
+            document.issuer, issuer
+        ));
+    }
+
+    Ok(document)
+}
+
+// Returns the configuration and whether discovery succeeded
+async fn resolve_auth_config() -> Result<(AuthConfig, bool), String> {
+    let settings = load_settings()?;
+    let issuer = settings
+        .issuer
+        .clone()
+        .unwrap_or_else(|| DEFAULT_ISSUER.to_string())
+        .trim_end_matches('/')
+        .to_string();
+    let is_default_issuer = issuer == DEFAULT_ISSUER;
+
+    let discovered = match discover(&issuer).await {
+        Ok(document) => Some(document),
+        // The built-in endpoints are a known-good fallback for production only;
+        // another tenant's endpoints must come from discovery or the settings.
+        Err(e) if is_default_issuer => {
+            warn!("{}, using built-in endpoints", e);
+            None
+        }
+        Err(e) => {
+            if settings.authorization_endpoint.is_none() || settings.token_endpoint.is_none() {
+                return Err(e);
+            }
+            warn!("{}, using configured endpoints", e);
+            None
+        }
+    };
+
+    // Explicit settings win over discovery, which wins over the built-in
+    // defaults. Endpoints without a default are only known from discovery.
+    let pick = |configured: Option<String>, discovered: Option<String>, default: &str| {
+        configured
+            .or(discovered)
+            .or_else(|| is_default_issuer.then(|| default.to_string()))
+    };
+
+    let authorization_endpoint = pick(
+        settings.authorization_endpoint,
+        discovered
+            .as_ref()
+            .map(|d| d.authorization_endpoint.clone()),
+        AUTH_URL,
+    )
+    .ok_or("No authorization endpoint configured")?;
+    let token_endpoint = pick(
+        settings.token_endpoint,
+        discovered.as_ref().map(|d| d.token_endpoint.clone()),
+        TOKEN_URL,
+    )
+    .ok_or("No token endpoint configured")?;
+    let userinfo_endpoint = pick(
+        settings.userinfo_endpoint,
+        discovered
+            .as_ref()
+            .and_then(|d| d.userinfo_endpoint.clone()),
+        USER_INFO_URL,
+    )
+    .ok_or("No userinfo endpoint configured")?;
+    let device_authorization_endpoint = settings.device_authorization_endpoint.or_else(|| {
+        discovered
+            .as_ref()
+            .and_then(|d| d.device_authorization_endpoint.clone())
+    });
+    let jwks_uri = settings
+        .jwks_uri
+        .or_else(|| discovered.as_ref().and_then(|d| d.jwks_uri.clone()));
+    if jwks_uri.is_none() && discovered.is_some() {
+        return Err(format!(
+            "Issuer {} does not publish a jwks_uri, so ID tokens can't be validated; set jwks_uri in {}",
+            issuer, SETTINGS_FILE
+        ));
+    }
+    let revocation_endpoint = settings.revocation_endpoint.or_else(|| {
+        discovered
+            .as_ref()
+            .and_then(|d| d.revocation_endpoint.clone())
+    });
+
+    let client_id = match settings.client_id {
+        Some(client_id) => client_id,
+        None if is_default_issuer => CLIENT_ID.to_string(),
+        None => return Err(format!("No client_id configured for issuer {}", issuer)),
+    };
+
+    let config = AuthConfig {
+        issuer,
+        client_id,
+        redirect_uri: settings
+            .redirect_uri
+            .unwrap_or_else(|| REDIRECT_URI.to_string()),
+        authorization_endpoint,
+        token_endpoint,
+        userinfo_endpoint,
+        device_authorization_endpoint,
+        jwks_uri,
+        revocation_endpoint,
+    };
+
+    info!("Using identity provider {}", config.issuer);
+    Ok((config, discovered.is_some()))
+}
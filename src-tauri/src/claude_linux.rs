@@ -0,0 +1,455 @@
+// Claude Desktop on Linux. There is no official build, so we look for the
+// community packages: .deb/AUR (a `claude-desktop` binary), AppImages, Flatpaks,
+// and as a last resort any `.desktop` launcher that mentions Claude.
+//
+// Note that `claude` on PATH is Claude Code, not the desktop app.
+
+use crate::claude_process::ProcessMatcher;
+use log::debug;
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+const BINARY_NAMES: &[&str] = &["claude-desktop"];
+
+const BINARY_PATHS: &[&str] = &["/usr/bin/claude-desktop", "/usr/local/bin/claude-desktop"];
+
+// Executables looked for in /opt/<dir> when <dir> mentions Claude, e.g.
+// /opt/Claude/claude or /opt/claude-desktop/claude-desktop
+const OPT_BINARY_NAMES: &[&str] = &["claude-desktop", "claude"];
+
+// Where packages keep the Electron app their wrapper script starts
+const PACKAGE_ROOTS: &[&str] = &["/usr/lib", "/usr/lib64", "/usr/share", "/opt"];
+
+// Directories shared by many programs, never an install prefix on their own
+const SHARED_BIN_DIRS: &[&str] = &["/bin", "/sbin", "/usr/bin", "/usr/sbin", "/usr/local/bin"];
+
+const FLATPAK_IDS: &[&str] = &["com.anthropic.Claude", "com.anthropic.claude-desktop"];
+
+/// How Claude Desktop is installed, which decides how it is stopped and
+/// relaunched.
+#[derive(Debug, Clone)]
+pub enum ClaudeInstall {
+    /// A plain executable, as installed by the .deb and AUR packages.
+    Binary(PathBuf),
+    AppImage(PathBuf),
+    Flatpak {
+        app_id: String,
+        location: PathBuf,
+    },
+    /// A launcher whose command we couldn't resolve to one of the above.
+    DesktopEntry {
+        desktop_id: String,
+        path: PathBuf,
+    },
+}
+
+impl ClaudeInstall {
+    pub fn path(&self) -> &Path {
+        match self {
+            ClaudeInstall::Binary(path) | ClaudeInstall::AppImage(path) => path,
+            ClaudeInstall::Flatpak { location, .. } => location,
+            ClaudeInstall::DesktopEntry { path, .. } => path,
+        }
+    }
+
+    /// Short name of the packaging format, for logs and the frontend.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ClaudeInstall::Binary(_) => "binary",
+            ClaudeInstall::AppImage(_) => "appimage",
+            ClaudeInstall::Flatpak { .. } => "flatpak",
+            ClaudeInstall::DesktopEntry { .. } => "desktop_entry",
+        }
+    }
+
+    /// What identifies Claude's processes for this install. Only executable
+    /// paths are compared, never arguments, so an editor or `tail` that merely
+    /// has a Claude path on its command line is left alone. `None` when
+    /// nothing does, see `DesktopEntry`.
+    pub fn process_matcher(&self) -> Option<ProcessMatcher> {
+        let matcher = match self {
+            ClaudeInstall::Binary(path) => {
+                let mut matcher = ProcessMatcher::default();
+                matcher.paths.push(path.clone());
+                if let Ok(resolved) = fs::canonicalize(path) {
+                    // A symlink into the package's own directory, e.g. /opt/Claude
+                    if let Some(dir) = resolved.parent().filter(|dir| !is_shared_bin_dir(dir)) {
+                        matcher.dirs.push(dir.to_path_buf());
+                    }
+                    matcher.paths.push(resolved);
+                }
+                // Wrapper scripts exec Electron from the package's directory
+                if let Some(name) = path.file_name() {
+                    matcher.dirs.extend(
+                        PACKAGE_ROOTS
+                            .iter()
+                            .map(|root| Path::new(root).join(name))
+                            .filter(|dir| dir.is_dir()),
+                    );
+                }
+                matcher
+            }
+            ClaudeInstall::AppImage(path) => ProcessMatcher {
+                paths: vec![path.clone()],
+                appimage: Some(path.clone()),
+                ..Default::default()
+            },
+            // Everything in the sandbox descends from the processes flatpak
+            // reports for the app
+            ClaudeInstall::Flatpak { app_id, .. } => ProcessMatcher {
+                roots: flatpak_pids(app_id),
+                ..Default::default()
+            },
+            // Without a resolved program there is nothing exact to go on, and
+            // guessing risks stopping unrelated processes
+            ClaudeInstall::DesktopEntry { .. } => return None,
+        };
+        Some(matcher)
+    }
+
+    /// Kills the whole sandbox for Flatpak installs, where killing individual
+    /// processes from outside may leave some behind. Returns whether it applied.
+    pub fn force_kill(&self) -> Result<bool, String> {
+        let ClaudeInstall::Flatpak { app_id, .. } = self else {
+            return Ok(false);
+        };
+        let status = Command::new("flatpak")
+            .args(["kill", app_id])
+            .stderr(Stdio::null())
+            .status()
+            .map_err(|e| format!("Failed to run flatpak kill: {}", e))?;
+        Ok(status.success())
+    }
+
+    /// The installed version, from the package manager that owns the install
+    /// or, for AppImages, the file name.
+    pub fn version(&self) -> Option<String> {
+        match self {
+            ClaudeInstall::Binary(path) => {
+                // Wrapper scripts in /usr/bin are symlinked or owned directly
+                let path = fs::canonicalize(path).unwrap_or_else(|_| path.clone());
+                dpkg_version(&path).or_else(|| pacman_version(&path))
+            }
+            ClaudeInstall::AppImage(path) => appimage_version(path),
+            ClaudeInstall::Flatpak { app_id, .. } => flatpak_version(app_id),
+            ClaudeInstall::DesktopEntry { .. } => None,
+        }
+    }
+
+    /// Starts the app detached from us.
+    pub fn launch(&self) -> Result<(), String> {
+        let mut command = match self {
+            ClaudeInstall::Binary(path) | ClaudeInstall::AppImage(path) => Command::new(path),
+            ClaudeInstall::Flatpak { app_id, .. } => {
+                let mut command = Command::new("flatpak");
+                command.args(["run", app_id]);
+                command
+            }
+            ClaudeInstall::DesktopEntry { desktop_id, .. } => {
+                let mut command = Command::new("gtk-launch");
+                command.arg(desktop_id);
+                command
+            }
+        };
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map(|_| ())
+            .map_err(|e| format!("Failed to launch {}: {}", self.path().display(), e))
+    }
+}
+
+fn is_executable(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+fn is_appimage(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("appimage"))
+}
+
+fn mentions_claude(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().to_lowercase().contains("claude"))
+}
+
+fn find_binary() -> Option<PathBuf> {
+    let on_path = env::var_os("PATH").into_iter().flat_map(|paths| {
+        env::split_paths(&paths)
+            .flat_map(|dir| BINARY_NAMES.iter().map(move |name| dir.join(name)))
+            .collect::<Vec<_>>()
+    });
+    on_path
+        .chain(BINARY_PATHS.iter().map(PathBuf::from))
+        .chain(opt_binaries())
+        .inspect(|path| debug!("Checking for Claude at: {}", path.display()))
+        .find(|path| is_executable(path))
+}
+
+// Candidate executables in the directories under /opt that mention Claude
+fn opt_binaries() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = fs::read_dir("/opt")
+        .into_iter()
+        .flat_map(|entries| entries.flatten().map(|entry| entry.path()))
+        .filter(|dir| mentions_claude(dir) && dir.is_dir())
+        .collect();
+    // read_dir order is arbitrary; keep detection stable
+    dirs.sort();
+    dirs.iter()
+        .flat_map(|dir| OPT_BINARY_NAMES.iter().map(move |name| dir.join(name)))
+        .collect()
+}
+
+// The first `dir/name` on PATH that is executable
+fn find_on_path(name: &str) -> Option<PathBuf> {
+    let paths = env::var_os("PATH")?;
+    env::split_paths(&paths)
+        .map(|dir| dir.join(name))
+        .find(|path| is_executable(path))
+}
+
+fn find_appimage() -> Option<PathBuf> {
+    let home = dirs::home_dir()?;
+    ["Applications", ".local/bin", "Downloads"]
+        .iter()
+        .filter_map(|dir| fs::read_dir(home.join(dir)).ok())
+        .flat_map(|entries| entries.flatten().map(|entry| entry.path()))
+        .find(|path| is_appimage(path) && mentions_claude(path) && is_executable(path))
+}
+
+fn flatpak_location(app_id: &str) -> Option<PathBuf> {
+    command_output("flatpak", &["info", "--show-location", app_id]).map(PathBuf::from)
+}
+
+fn is_shared_bin_dir(dir: &Path) -> bool {
+    SHARED_BIN_DIRS
+        .iter()
+        .any(|shared| dir == Path::new(shared))
+}
+
+// PIDs of the running instances of a Flatpak app; prints "pid\tapplication"
+// per instance when not on a terminal
+fn flatpak_pids(app_id: &str) -> Vec<u32> {
+    command_output("flatpak", &["ps", "--columns=pid,application"])
+        .map(|output| {
+            output
+                .lines()
+                .filter_map(|line| {
+                    let mut fields = line.split_whitespace();
+                    let pid = fields.next()?.parse().ok()?;
+                    (fields.next()? == app_id).then_some(pid)
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn find_flatpak() -> Option<ClaudeInstall> {
+    FLATPAK_IDS.iter().find_map(|app_id| {
+        debug!("Checking for Claude flatpak {}", app_id);
+        let location = flatpak_location(app_id)?;
+        Some(ClaudeInstall::Flatpak {
+            app_id: app_id.to_string(),
+            location,
+        })
+    })
+}
+
+// Output of a command that succeeded, trimmed
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program)
+        .args(args)
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!stdout.is_empty()).then_some(stdout)
+}
+
+fn dpkg_version(path: &Path) -> Option<String> {
+    // Prints "package: /path"
+    let owner = command_output("dpkg-query", &["-S", &path.to_string_lossy()])?;
+    let package = owner.lines().next()?.split(':').next()?.trim().to_string();
+    command_output("dpkg-query", &["-W", "-f=${Version}", &package])
+}
+
+fn pacman_version(path: &Path) -> Option<String> {
+    // Prints "/path is owned by package version"
+    let owner = command_output("pacman", &["-Qo", &path.to_string_lossy()])?;
+    owner.split_whitespace().last().map(str::to_string)
+}
+
+fn flatpak_version(app_id: &str) -> Option<String> {
+    let info = command_output("flatpak", &["info", app_id])?;
+    info.lines()
+        .find_map(|line| line.trim().strip_prefix("Version:"))
+        .map(|version| version.trim().to_string())
+}
+
+// AppImages are usually named like Claude-0.9.3-x86_64.AppImage
+fn appimage_version(path: &Path) -> Option<String> {
+    let stem = path.file_stem()?.to_string_lossy().to_string();
+    stem.split(['-', '_'])
+        .find(|part| {
+            part.chars().next().is_some_and(|c| c.is_ascii_digit())
+                && part.contains('.')
+                && part.chars().all(|c| c.is_ascii_digit() || c == '.')
+        })
+        .map(str::to_string)
+}
+
+fn applications_dirs() -> Vec<PathBuf> {
+    let mut roots: Vec<PathBuf> = dirs::data_dir().into_iter().collect();
+    let data_dirs =
+        env::var("XDG_DATA_DIRS").unwrap_or_else(|_| "/usr/local/share:/usr/share".to_string());
+    roots.extend(env::split_paths(&data_dirs));
+    if let Some(data_dir) = dirs::data_dir() {
+        roots.push(data_dir.join("flatpak/exports/share"));
+    }
+    roots.push(PathBuf::from("/var/lib/flatpak/exports/share"));
+    roots
+        .into_iter()
+        .map(|dir| dir.join("applications"))
+        .collect()
+}
+
+// The `Exec=` command of a desktop entry, split into words with field codes
+// such as %U dropped. Quoting is handled loosely.
+fn desktop_exec(contents: &str) -> Option<Vec<String>> {
+    let exec = contents
+        .lines()
+        .map(str::trim)
+        .find_map(|line| line.strip_prefix("Exec="))?;
+    let words: Vec<String> = exec
+        .split_whitespace()
+        .filter(|word| !word.starts_with('%'))
+        .map(|word| word.trim_matches('"').to_string())
+        .collect();
+    (!words.is_empty()).then_some(words)
+}
+
+// Works out what a launcher actually runs
+fn install_from_desktop_entry(path: &Path, exec: &[String]) -> ClaudeInstall {
+    if exec[0] == "flatpak" || exec[0].ends_with("/flatpak") {
+        if let Some(app_id) = exec
+            .iter()
+            .skip(1)
+            .find(|word| !word.starts_with('-') && *word != "run")
+        {
+            if let Some(location) = flatpak_location(app_id) {
+                return ClaudeInstall::Flatpak {
+                    app_id: app_id.clone(),
+                    location,
+                };
+            }
+        }
+    }
+
+    // `env VAR=value program` sets variables for the program it starts
+    let program = exec
+        .iter()
+        .skip_while(|word| *word == "env" || word.ends_with("/env"))
+        .find(|word| !word.contains('='));
+    let program = program.and_then(|program| {
+        let path = PathBuf::from(program);
+        if path.is_absolute() {
+            Some(path).filter(|path| is_executable(path))
+        } else if !program.contains('/') {
+            find_on_path(program)
+        } else {
+            None
+        }
+    });
+    if let Some(program) = program {
+        return if is_appimage(&program) {
+            ClaudeInstall::AppImage(program)
+        } else {
+            ClaudeInstall::Binary(program)
+        };
+    }
+
+    let desktop_id = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    ClaudeInstall::DesktopEntry {
+        desktop_id,
+        path: path.to_path_buf(),
+    }
+}
+
+fn find_desktop_entry() -> Option<ClaudeInstall> {
+    applications_dirs()
+        .iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flat_map(|entries| entries.flatten().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension().is_some_and(|ext| ext == "desktop") && mentions_claude(path)
+        })
+        .find_map(|path| {
+            debug!("Checking desktop entry {}", path.display());
+            let contents = fs::read_to_string(&path).ok()?;
+            // Skip launchers for Claude Code and other tools
+            let is_desktop_app = contents
+                .lines()
+                .any(|line| line.trim() == "Name=Claude" || line.trim() == "Name=Claude Desktop");
+            if !is_desktop_app {
+                return None;
+            }
+            let exec = desktop_exec(&contents)?;
+            Some(install_from_desktop_entry(&path, &exec))
+        })
+}
+
+/// Looks for Claude Desktop, preferring native packages over sandboxed ones.
+pub fn find_claude() -> Option<ClaudeInstall> {
+    let install = find_binary()
+        .map(ClaudeInstall::Binary)
+        .or_else(|| find_appimage().map(ClaudeInstall::AppImage))
+        .or_else(find_flatpak)
+        .or_else(find_desktop_entry);
+    if let Some(install) = &install {
+        debug!(
+            "Found Claude ({}) at {}",
+            install.kind(),
+            install.path().display()
+        );
+    }
+    install
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry_install(exec: &str) -> ClaudeInstall {
+        let exec = desktop_exec(&format!("[Desktop Entry]\nName=Claude\nExec={}\n", exec))
+            .expect("Exec line");
+        install_from_desktop_entry(Path::new("/usr/share/applications/claude.desktop"), &exec)
+    }
+
+    #[test]
+    fn resolves_launcher_program_through_env_and_path() {
+        let install = entry_install("env ELECTRON_OZONE_PLATFORM_HINT=auto sh %U");
+        let ClaudeInstall::Binary(path) = &install else {
+            panic!("expected a binary, got {:?}", install);
+        };
+        assert!(path.is_absolute() && path.ends_with("sh"));
+        assert!(install.process_matcher().is_some());
+    }
+
+    #[test]
+    fn unresolved_launcher_has_no_process_matcher() {
+        let install = entry_install("claude-desktop-that-does-not-exist %U");
+        assert!(matches!(install, ClaudeInstall::DesktopEntry { .. }));
+        assert!(install.process_matcher().is_none());
+    }
+}
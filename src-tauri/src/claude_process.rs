@@ -0,0 +1,472 @@
+// Finding and stopping the running Claude Desktop processes. Claude is asked
+// to quit the way a user would, so open conversations are saved, and is only
+// force-killed when it hasn't exited within the timeout.
+
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use log::{debug, info, warn};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+use std::process::Command;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+use sysinfo::{Pid, Process, ProcessRefreshKind, System, UpdateKind};
+
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+// How long to wait for processes to disappear after a force-kill
+const KILL_GRACE: Duration = Duration::from_secs(3);
+
+/// What happened while shutting Claude down.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct ShutdownReport {
+    /// Claude processes found before asking it to quit.
+    pub pids: Vec<u32>,
+    /// Whether Claude exited on its own after being asked to quit.
+    pub graceful: bool,
+    /// Whether it had to be force-killed after the timeout.
+    pub force_killed: bool,
+    pub elapsed_ms: u64,
+}
+
+/// Identifies Claude's processes by executable path: a process matches when
+/// its executable, or an absolute argv[0] for wrappers that set it, is one of
+/// `paths` or lies inside one of `dirs`. Arguments are never looked at.
+#[derive(Debug, Clone, Default)]
+pub struct ProcessMatcher {
+    pub paths: Vec<PathBuf>,
+    pub dirs: Vec<PathBuf>,
+    /// An AppImage, whose runtime mounts it at a random directory and exports
+    /// that as APPDIR, with APPIMAGE set to the file, to what it starts.
+    pub appimage: Option<PathBuf>,
+    /// Processes that are Claude along with all their descendants, such as
+    /// the sandbox of a Flatpak.
+    pub roots: Vec<u32>,
+}
+
+impl ProcessMatcher {
+    fn matches_path(&self, path: &Path) -> bool {
+        // `starts_with` compares whole components, so /opt/Claude doesn't
+        // cover /opt/Claude-old
+        self.paths.iter().any(|known| known == path)
+            || self.dirs.iter().any(|dir| path.starts_with(dir))
+    }
+
+    // The mount point of our AppImage, if `environ` belongs to a process it started
+    fn appimage_dir<'a>(&self, environ: &'a [String]) -> Option<&'a Path> {
+        let appimage = self.appimage.as_ref()?;
+        let var = |name: &str| {
+            environ.iter().find_map(|entry| {
+                entry
+                    .strip_prefix(name)
+                    .and_then(|rest| rest.strip_prefix('='))
+            })
+        };
+        if Path::new(var("APPIMAGE")?) != appimage {
+            return None;
+        }
+        Some(Path::new(var("APPDIR")?)).filter(|dir| dir.is_absolute())
+    }
+
+    /// Whether a process with this executable, argv[0] and environment is
+    /// Claude. Ancestry (`roots`) is checked separately.
+    pub fn matches(&self, exe: Option<&Path>, argv0: Option<&str>, environ: &[String]) -> bool {
+        let argv0 = argv0.map(Path::new).filter(|path| path.is_absolute());
+        let candidates = || exe.into_iter().chain(argv0);
+        if candidates().any(|path| self.matches_path(path)) {
+            return true;
+        }
+        self.appimage_dir(environ)
+            .is_some_and(|dir| candidates().any(|path| path.starts_with(dir)))
+    }
+
+    /// Whether `pid` or one of its ancestors is in `roots`. `parent` looks up
+    /// a process's parent.
+    pub fn descends_from_root(&self, pid: u32, parent: impl Fn(u32) -> Option<u32>) -> bool {
+        if self.roots.is_empty() {
+            return false;
+        }
+        let mut current = Some(pid);
+        // Bounded in case the table changes under us and forms a cycle
+        for _ in 0..64 {
+            let Some(pid) = current else {
+                return false;
+            };
+            if self.roots.contains(&pid) {
+                return true;
+            }
+            current = parent(pid);
+        }
+        false
+    }
+}
+
+fn is_claude_process(
+    system: &System,
+    pid: Pid,
+    process: &Process,
+    matcher: &ProcessMatcher,
+) -> bool {
+    #[cfg(target_os = "macos")]
+    {
+        // The main app and its helpers all run from inside the bundle
+        let _ = (system, pid, matcher);
+        process
+            .exe()
+            .is_some_and(|exe| exe.to_string_lossy().contains("/Claude.app/"))
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        // The desktop app and its helpers run from its install directory;
+        // the Claude Code CLI is also claude.exe, so the name alone won't do
+        let _ = (system, pid);
+        process.exe().is_some_and(|exe| {
+            matcher
+                .dirs
+                .iter()
+                .any(|dir| lowercase(exe).starts_with(lowercase(dir)))
+        })
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    {
+        let argv0 = process.cmd().first().map(String::as_str);
+        matcher.matches(process.exe(), argv0, process.environ())
+            || matcher.descends_from_root(pid.as_u32(), |pid| {
+                system
+                    .process(Pid::from_u32(pid))
+                    .and_then(Process::parent)
+                    .map(Pid::as_u32)
+            })
+    }
+}
+
+// Windows paths compare case-insensitively
+#[cfg(target_os = "windows")]
+fn lowercase(path: &Path) -> PathBuf {
+    PathBuf::from(path.to_string_lossy().to_lowercase())
+}
+
+// What identifies Claude's processes on Linux depends on how it was installed.
+// Fails when we can't tell, rather than "stopping" nothing and reporting a
+// restart that never reloaded Claude.
+fn process_matcher() -> CommandResult<ProcessMatcher> {
+    #[cfg(target_os = "linux")]
+    {
+        let Some(install) = crate::claude_linux::find_claude() else {
+            return Ok(ProcessMatcher::default());
+        };
+        install.process_matcher().ok_or_else(|| {
+            CommandError::new(
+                ErrorCode::ClaudeRestartFailed,
+                "Can't tell which processes belong to Claude; restart it yourself",
+            )
+            .with_details(format!(
+                "launched by {}, whose command couldn't be resolved",
+                install.path().display()
+            ))
+        })
+    }
+
+    // Squirrel installs the app under one directory, e.g.
+    // %LOCALAPPDATA%\AnthropicClaude, with versioned app-x.y.z folders inside
+    #[cfg(target_os = "windows")]
+    {
+        Ok(ProcessMatcher {
+            dirs: crate::app::get_claude_path()
+                .and_then(|path| path.parent().map(Path::to_path_buf))
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        })
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+    {
+        Ok(ProcessMatcher::default())
+    }
+}
+
+// taskkill arguments addressing exactly these processes
+#[cfg(target_os = "windows")]
+fn taskkill_pids(pids: &[Pid]) -> Vec<String> {
+    pids.iter()
+        .flat_map(|pid| ["/PID".to_string(), pid.to_string()])
+        .collect()
+}
+
+fn refresh(system: &mut System) {
+    system.refresh_processes_specifics(
+        ProcessRefreshKind::new()
+            .with_exe(UpdateKind::OnlyIfNotSet)
+            .with_cmd(UpdateKind::OnlyIfNotSet)
+            .with_environ(UpdateKind::OnlyIfNotSet),
+    );
+}
+
+fn find_pids(system: &System, matcher: &ProcessMatcher) -> Vec<Pid> {
+    let own_pid = sysinfo::get_current_pid().ok();
+    let mut pids: Vec<Pid> = system
+        .processes()
+        .iter()
+        .filter(|(pid, process)| {
+            Some(**pid) != own_pid && is_claude_process(system, **pid, process, matcher)
+        })
+        .map(|(pid, _)| *pid)
+        .collect();
+    pids.sort();
+    pids
+}
+
+/// PIDs of all running Claude Desktop processes, helpers included. Empty when
+/// they can't be told apart from other processes.
+pub fn claude_pids() -> Vec<u32> {
+    process_matcher()
+        .map(|matcher| claude_pids_matching(&matcher))
+        .unwrap_or_default()
+}
+
+/// PIDs of the running processes `matcher` identifies as Claude.
+pub fn claude_pids_matching(matcher: &ProcessMatcher) -> Vec<u32> {
+    let mut system = System::new();
+    refresh(&mut system);
+    find_pids(&system, matcher)
+        .into_iter()
+        .map(Pid::as_u32)
+        .collect()
+}
+
+// Asks Claude to quit as if the user had chosen Quit from its menu
+fn request_quit(system: &System, pids: &[Pid]) {
+    #[cfg(target_os = "macos")]
+    {
+        let _ = (system, pids);
+        if let Err(e) = Command::new("osascript")
+            .args(["-e", "tell application \"Claude\" to quit"])
+            .output()
+        {
+            warn!("Failed to ask Claude to quit: {}", e);
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        // Without /F taskkill sends WM_CLOSE instead of terminating
+        let _ = system;
+        if let Err(e) = Command::new("taskkill").args(taskkill_pids(pids)).output() {
+            warn!("Failed to ask Claude to quit: {}", e);
+        }
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    {
+        for pid in pids {
+            if let Some(process) = system.process(*pid) {
+                process.kill_with(sysinfo::Signal::Term);
+            }
+        }
+    }
+}
+
+fn force_kill(system: &System, pids: &[Pid]) {
+    #[cfg(target_os = "linux")]
+    {
+        if let Some(install) = crate::claude_linux::find_claude() {
+            if let Err(e) = install.force_kill() {
+                warn!("{}", e);
+            }
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        // Takes the child processes down with the main one
+        let _ = Command::new("taskkill")
+            .args(["/F", "/T"])
+            .args(taskkill_pids(pids))
+            .output();
+    }
+
+    for pid in pids {
+        if let Some(process) = system.process(*pid) {
+            process.kill();
+        }
+    }
+}
+
+// Polls until none of `pids` is running or the deadline passes; returns the
+// ones still alive
+fn wait_for_exit(system: &mut System, pids: &[Pid], timeout: Duration) -> Vec<Pid> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        refresh(system);
+        let remaining: Vec<Pid> = pids
+            .iter()
+            .copied()
+            .filter(|pid| system.process(*pid).is_some())
+            .collect();
+        if remaining.is_empty() || Instant::now() >= deadline {
+            return remaining;
+        }
+        sleep(POLL_INTERVAL);
+    }
+}
+
+/// Asks Claude to quit, waits up to `timeout` for every Claude process to
+/// exit and force-kills whatever is left. Fails only if processes survive
+/// the force-kill.
+pub fn shutdown_claude(timeout: Duration) -> CommandResult<ShutdownReport> {
+    let started = Instant::now();
+    let matcher = process_matcher()?;
+    let mut system = System::new();
+    refresh(&mut system);
+    let pids = find_pids(&system, &matcher);
+    let mut report = ShutdownReport {
+        pids: pids.iter().map(|pid| pid.as_u32()).collect(),
+        ..Default::default()
+    };
+    if pids.is_empty() {
+        debug!("Claude is not running");
+        return Ok(report);
+    }
+
+    info!("Asking Claude to quit ({} processes)", pids.len());
+    request_quit(&system, &pids);
+    let remaining = wait_for_exit(&mut system, &pids, timeout);
+
+    if remaining.is_empty() {
+        report.graceful = true;
+    } else {
+        warn!(
+            "Claude did not quit within {} ms, force-killing {} processes",
+            timeout.as_millis(),
+            remaining.len()
+        );
+        force_kill(&system, &remaining);
+        report.force_killed = true;
+
+        let survivors = wait_for_exit(&mut system, &remaining, KILL_GRACE);
+        if !survivors.is_empty() {
+            let survivors: Vec<String> = survivors.iter().map(|pid| pid.to_string()).collect();
+            return Err(
+                CommandError::new(ErrorCode::ClaudeRestartFailed, "Claude did not exit")
+                    .with_details(format!("still running: {}", survivors.join(", "))),
+            );
+        }
+    }
+
+    report.elapsed_ms = started.elapsed().as_millis() as u64;
+    info!(
+        "Claude exited after {} ms ({})",
+        report.elapsed_ms,
+        if report.graceful {
+            "graceful"
+        } else {
+            "force-killed"
+        }
+    );
+    Ok(report)
+}
+
+/// Quits Claude Desktop without relaunching it. `timeout_ms` is how long to
+/// wait before force-killing.
+#[tauri::command]
+pub async fn quit_claude_app(timeout_ms: Option<u64>) -> CommandResult<ShutdownReport> {
+    let timeout = timeout_ms
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT);
+    // Waiting for Claude to exit takes seconds, so keep it off the main thread
+    tauri::async_runtime::spawn_blocking(move || shutdown_claude(timeout))
+        .await
+        .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deb_matcher() -> ProcessMatcher {
+        ProcessMatcher {
+            paths: vec![PathBuf::from("/usr/bin/claude-desktop")],
+            dirs: vec![PathBuf::from("/usr/lib/claude-desktop")],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn matches_executables_inside_the_install() {
+        let matcher = deb_matcher();
+        let electron = Path::new("/usr/lib/claude-desktop/node_modules/electron/dist/electron");
+        assert!(matcher.matches(Some(electron), None, &[]));
+        assert!(matcher.matches(Some(Path::new("/usr/bin/claude-desktop")), None, &[]));
+    }
+
+    #[test]
+    fn ignores_processes_that_only_mention_claude_in_arguments() {
+        let matcher = deb_matcher();
+        // `vim /usr/lib/claude-desktop/app.asar`, `tail -f ...` and the like
+        assert!(!matcher.matches(Some(Path::new("/usr/bin/vim")), Some("vim"), &[]));
+        assert!(!matcher.matches(Some(Path::new("/usr/bin/tail")), Some("/usr/bin/tail"), &[]));
+    }
+
+    #[test]
+    fn compares_whole_path_components() {
+        let matcher = deb_matcher();
+        let sibling = Path::new("/usr/lib/claude-desktop-old/electron");
+        assert!(!matcher.matches(Some(sibling), None, &[]));
+        assert!(!matcher.matches(Some(Path::new("/usr/bin/claude-desktop-dev")), None, &[]));
+    }
+
+    #[test]
+    fn matches_absolute_argv0_of_wrappers() {
+        let matcher = deb_matcher();
+        let exe = Path::new("/usr/lib/electron/electron");
+        assert!(matcher.matches(Some(exe), Some("/usr/lib/claude-desktop/claude"), &[]));
+        // A relative argv[0] says nothing about where the program lives
+        assert!(!matcher.matches(Some(exe), Some("claude-desktop"), &[]));
+    }
+
+    #[test]
+    fn matches_only_our_appimage_mount() {
+        let appimage = PathBuf::from("/home/me/Applications/Claude.AppImage");
+        let matcher = ProcessMatcher {
+            paths: vec![appimage.clone()],
+            appimage: Some(appimage),
+            ..Default::default()
+        };
+        let exe = Path::new("/tmp/.mount_ClaudeAbc123/claude");
+        let ours = vec![
+            "APPIMAGE=/home/me/Applications/Claude.AppImage".to_string(),
+            "APPDIR=/tmp/.mount_ClaudeAbc123".to_string(),
+        ];
+        let other = vec![
+            "APPIMAGE=/home/me/Applications/Claude-old.AppImage".to_string(),
+            "APPDIR=/tmp/.mount_ClaudeAbc123".to_string(),
+        ];
+        assert!(matcher.matches(Some(exe), None, &ours));
+        assert!(!matcher.matches(Some(exe), None, &other));
+        assert!(!matcher.matches(Some(exe), None, &[]));
+        // A shell started from inside the AppImage isn't Claude
+        assert!(!matcher.matches(Some(Path::new("/usr/bin/bash")), Some("bash"), &ours));
+    }
+
+    #[test]
+    fn matches_descendants_of_roots() {
+        let matcher = ProcessMatcher {
+            roots: vec![100],
+            ..Default::default()
+        };
+        // 300 -> 200 -> 100 -> 1
+        let parent = |pid| match pid {
+            300 => Some(200),
+            200 => Some(100),
+            100 => Some(1),
+            _ => None,
+        };
+        assert!(matcher.descends_from_root(300, parent));
+        assert!(matcher.descends_from_root(100, parent));
+        assert!(!matcher.descends_from_root(1, parent));
+        assert!(!ProcessMatcher::default().descends_from_root(300, parent));
+    }
+}
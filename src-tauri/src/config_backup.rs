@@ -0,0 +1,341 @@
+// Rotating snapshots of `claude_desktop_config.json`, taken before every write
+// so a bad install/uninstall (or a bad hand edit) can be rolled back.
+
+use crate::app::{get_app_directory, get_config_path, replace_config};
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::file_utils::{sha256_hex, write_atomic};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use similar::TextDiff;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+// Oldest snapshots beyond this are deleted
+const MAX_BACKUPS: usize = 20;
+const INDEX_FILE: &str = "index.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupEntry {
+    pub id: String,
+    pub created_at: String,
+    pub reason: String,
+    pub size: u64,
+    sha256: String,
+}
+
+/// Difference between a snapshot and the config currently on disk.
+#[derive(Debug, Serialize)]
+pub struct BackupDiff {
+    pub id: String,
+    /// Unified diff from the snapshot to the current file.
+    pub unified: String,
+    /// MCP servers present now but not in the snapshot.
+    pub added_servers: Vec<String>,
+    /// MCP servers in the snapshot that are gone now.
+    pub removed_servers: Vec<String>,
+    /// MCP servers present in both with a different definition.
+    pub changed_servers: Vec<String>,
+}
+
+fn backup_dir() -> Result<PathBuf, String> {
+    Ok(get_app_directory()?.join("backups").join("claude_config"))
+}
+
+fn load_index(dir: &Path) -> Result<Vec<BackupEntry>, String> {
+    let index_path = dir.join(INDEX_FILE);
+    if !index_path.exists() {
+        return Ok(Vec::new());
+    }
+    let json_data = fs::read_to_string(&index_path)
+        .map_err(|e| format!("Failed to read backup index: {}", e))?;
+    serde_json::from_str(&json_data).map_err(|e| format!("Failed to parse backup index: {}", e))
+}
+
+fn save_index(dir: &Path, entries: &[BackupEntry]) -> Result<(), String> {
+    let json_data = serde_json::to_string_pretty(entries).map_err(|e| e.to_string())?;
+    write_atomic(&dir.join(INDEX_FILE), json_data.as_bytes())
+}
+
+fn snapshot_path(dir: &Path, id: &str) -> PathBuf {
+    dir.join(format!("{}.json", id))
+}
+
+/// Copies the config at `config_path` into the backup history. The file is
+/// copied byte for byte, so even a config that no longer parses is kept.
+/// Nothing is stored when the file doesn't exist or is unchanged since the
+/// latest snapshot.
+pub fn snapshot(config_path: &Path, reason: &str) -> Result<Option<BackupEntry>, String> {
+    snapshot_in(&backup_dir()?, config_path, reason)
+}
+
+// `snapshot` with the backups kept in `dir`
+fn snapshot_in(
+    dir: &Path,
+    config_path: &Path,
+    reason: &str,
+) -> Result<Option<BackupEntry>, String> {
+    if !config_path.exists() {
+        return Ok(None);
+    }
+    let contents =
+        fs::read(config_path).map_err(|e| format!("Failed to read config for backup: {}", e))?;
+
+    fs::create_dir_all(dir).map_err(|e| format!("Failed to create backup directory: {}", e))?;
+    let mut entries = load_index(dir)?;
+
+    let sha256 = sha256_hex(&contents);
+    if entries.last().is_some_and(|latest| latest.sha256 == sha256) {
+        debug!("Config unchanged since the latest backup, skipping snapshot");
+        return Ok(None);
+    }
+
+    // Millisecond timestamps sort chronologically; bump on the rare collision
+    let mut millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+    while entries.iter().any(|entry| entry.id == millis.to_string()) {
+        millis += 1;
+    }
+    let id = millis.to_string();
+
+    write_atomic(&snapshot_path(dir, &id), &contents)?;
+    let entry = BackupEntry {
+        id,
+        created_at: OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .map_err(|e| e.to_string())?,
+        reason: reason.to_string(),
+        size: contents.len() as u64,
+        sha256,
+    };
+    entries.push(entry.clone());
+
+    // Rotate out the oldest snapshots
+    let excess = entries.len().saturating_sub(MAX_BACKUPS);
+    for old in entries.drain(..excess) {
+        if let Err(e) = fs::remove_file(snapshot_path(dir, &old.id)) {
+            warn!("Failed to delete old config backup {}: {}", old.id, e);
+        }
+    }
+
+    save_index(dir, &entries)?;
+    info!("Backed up Claude config as {} ({})", entry.id, reason);
+    Ok(Some(entry))
+}
+
+fn read_snapshot(id: &str) -> CommandResult<Vec<u8>> {
+    let dir = backup_dir().map_err(|e| CommandError::new(ErrorCode::Io, e))?;
+    read_snapshot_in(&dir, id)
+}
+
+fn read_snapshot_in(dir: &Path, id: &str) -> CommandResult<Vec<u8>> {
+    let entries = load_index(dir).map_err(|e| CommandError::new(ErrorCode::Io, e))?;
+    if !entries.iter().any(|entry| entry.id == id) {
+        return Err(CommandError::new(
+            ErrorCode::NotFound,
+            format!("No config backup found for {}", id),
+        ));
+    }
+    Ok(fs::read(snapshot_path(dir, id))?)
+}
+
+// Names of the MCP servers that differ between two configs
+fn server_changes(before: &[u8], after: &[u8]) -> (Vec<String>, Vec<String>, Vec<String>) {
+    let servers = |data: &[u8]| {
+        serde_json::from_slice::<Value>(data)
+            .ok()
+            .and_then(|config| config.get("mcpServers")?.as_object().cloned())
+            .unwrap_or_default()
+    };
+    let (before, after) = (servers(before), servers(after));
+
+    let added = after
+        .keys()
+        .filter(|name| !before.contains_key(*name))
+        .cloned()
+        .collect();
+    let removed = before
+        .keys()
+        .filter(|name| !after.contains_key(*name))
+        .cloned()
+        .collect();
+    let changed = before
+        .iter()
+        .filter(|(name, server)| after.get(*name).is_some_and(|other| other != *server))
+        .map(|(name, _)| name.clone())
+        .collect();
+    (added, removed, changed)
+}
+
+/// Lists config snapshots, newest first.
+#[tauri::command]
+pub fn list_config_backups() -> CommandResult<Vec<BackupEntry>> {
+    let dir = backup_dir().map_err(|e| CommandError::new(ErrorCode::Io, e))?;
+    list_backups_in(&dir)
+}
+
+fn list_backups_in(dir: &Path) -> CommandResult<Vec<BackupEntry>> {
+    let mut entries = load_index(dir).map_err(|e| CommandError::new(ErrorCode::Io, e))?;
+    entries.reverse();
+    Ok(entries)
+}
+
+/// Compares a snapshot with the config currently on disk.
+#[tauri::command]
+pub fn diff_config_backup(id: String) -> CommandResult<BackupDiff> {
+    let backup = read_snapshot(&id)?;
+    let config_path = get_config_path()?;
+    let current = if config_path.exists() {
+        fs::read(&config_path).map_err(|e| {
+            CommandError::new(ErrorCode::ConfigIo, "Failed to read config file")
+                .with_details(e.to_string())
+        })?
+    } else {
+        Vec::new()
+    };
+
+    let backup_text = String::from_utf8_lossy(&backup);
+    let current_text = String::from_utf8_lossy(&current);
+    let unified = TextDiff::from_lines(backup_text.as_ref(), current_text.as_ref())
+        .unified_diff()
+        .header(&format!("backup {}", id), "current")
+        .to_string();
+    let (added_servers, removed_servers, changed_servers) = server_changes(&backup, &current);
+
+    Ok(BackupDiff {
+        id,
+        unified,
+        added_servers,
+        removed_servers,
+        changed_servers,
+    })
+}
+
+/// Puts a snapshot back in place. The config being replaced is itself backed
+/// up first, so a restore can be undone.
+#[tauri::command]
+pub fn restore_config_backup(id: String) -> CommandResult<String> {
+    let backup = read_snapshot(&id)?;
+    replace_config(&backup, &format!("Before restoring backup {}", id))?;
+    info!("Restored Claude config from backup {}", id);
+    Ok(format!("Restored config backup {}", id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> TempDir {
+            static COUNTER: AtomicUsize = AtomicUsize::new(0);
+            let path = std::env::temp_dir().join(format!(
+                "waystation-config-backup-{}-{}",
+                std::process::id(),
+                COUNTER.fetch_add(1, Ordering::SeqCst)
+            ));
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    // A config file and a backup directory next to it
+    fn setup() -> (TempDir, PathBuf, PathBuf) {
+        let temp = TempDir::new();
+        let config_path = temp.0.join("claude_desktop_config.json");
+        let dir = temp.0.join("backups");
+        (temp, config_path, dir)
+    }
+
+    fn write_config(config_path: &Path, n: usize) -> String {
+        let contents = format!("{{\"mcpServers\": {{\"server-{}\": {{}}}}}}", n);
+        fs::write(config_path, &contents).unwrap();
+        contents
+    }
+
+    #[test]
+    fn skips_missing_and_unchanged_configs() {
+        let (_temp, config_path, dir) = setup();
+        assert!(snapshot_in(&dir, &config_path, "missing")
+            .unwrap()
+            .is_none());
+
+        write_config(&config_path, 1);
+        assert!(snapshot_in(&dir, &config_path, "first").unwrap().is_some());
+        assert!(snapshot_in(&dir, &config_path, "again").unwrap().is_none());
+        assert_eq!(list_backups_in(&dir).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn lists_backups_newest_first() {
+        let (_temp, config_path, dir) = setup();
+        for n in 0..3 {
+            write_config(&config_path, n);
+            snapshot_in(&dir, &config_path, &format!("edit {}", n)).unwrap();
+        }
+
+        let reasons: Vec<String> = list_backups_in(&dir)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.reason)
+            .collect();
+        assert_eq!(reasons, ["edit 2", "edit 1", "edit 0"]);
+    }
+
+    #[test]
+    fn rotates_out_the_oldest_backups() {
+        let (_temp, config_path, dir) = setup();
+        let mut ids = Vec::new();
+        for n in 0..MAX_BACKUPS + 3 {
+            write_config(&config_path, n);
+            ids.push(snapshot_in(&dir, &config_path, "edit").unwrap().unwrap().id);
+        }
+
+        let kept = list_backups_in(&dir).unwrap();
+        assert_eq!(kept.len(), MAX_BACKUPS);
+        assert_eq!(kept.last().unwrap().id, ids[3]);
+        for id in &ids[..3] {
+            assert!(!snapshot_path(&dir, id).exists());
+            assert_eq!(
+                read_snapshot_in(&dir, id).unwrap_err().code,
+                ErrorCode::NotFound
+            );
+        }
+        assert!(snapshot_path(&dir, &ids[3]).exists());
+    }
+
+    #[test]
+    fn restores_the_exact_bytes_of_a_backup() {
+        let (_temp, config_path, dir) = setup();
+        // Even a config that no longer parses is kept as it was
+        let broken = "{ \"mcpServers\": { // hand edit\n";
+        fs::write(&config_path, broken).unwrap();
+        let entry = snapshot_in(&dir, &config_path, "broken").unwrap().unwrap();
+        let later = write_config(&config_path, 1);
+        snapshot_in(&dir, &config_path, "fixed").unwrap();
+
+        assert_eq!(
+            read_snapshot_in(&dir, &entry.id).unwrap(),
+            broken.as_bytes()
+        );
+        assert_eq!(entry.size, broken.len() as u64);
+        assert_eq!(fs::read_to_string(&config_path).unwrap(), later);
+        assert_eq!(
+            read_snapshot_in(&dir, "123").unwrap_err().code,
+            ErrorCode::NotFound
+        );
+    }
+}
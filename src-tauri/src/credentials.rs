@@ -0,0 +1,724 @@
+use crate::{AuthData, UserInfo};
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::{engine::general_purpose, Engine as _};
+use log::{debug, error, info, warn};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// Service and account names used for the OS secret store entry
+const KEYRING_SERVICE: &str = "ai.waystation.launcher";
+const KEYRING_ACCOUNT: &str = "oauth";
+// Holds just the encrypted-file store's key, which fits where the tokens don't
+const KEYRING_FILE_KEY_ACCOUNT: &str = "file-store-key";
+
+// Encrypted-file fallback, relative to the app data dir
+const ENCRYPTED_STORE_PATH: &str = ".auth.enc";
+// Key file written beside the store by earlier versions; dropped on the next save
+const LEGACY_KEY_PATH: &str = ".auth.key";
+
+// Plaintext store written by previous versions, migrated on first launch
+const LEGACY_STORE_PATH: &str = ".auth.dat";
+
+// Serializes read-modify-write cycles on the stored accounts
+static ACCOUNTS_LOCK: Mutex<()> = Mutex::new(());
+
+/// Every signed-in account, keyed by `UserInfo.sub`, and which one is active.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct StoredAccounts {
+    pub active: Option<String>,
+    pub accounts: BTreeMap<String, AuthData>,
+}
+
+impl StoredAccounts {
+    fn from_json(json_data: &str) -> Result<Self, String> {
+        let mut accounts = match serde_json::from_str::<StoredAccounts>(json_data) {
+            Ok(accounts) => accounts,
+            Err(_) => {
+                // Stores written before multi-account support hold a single record
+                let auth_data: AuthData =
+                    serde_json::from_str(json_data).map_err(|e| e.to_string())?;
+                let mut accounts = StoredAccounts::default();
+                accounts.accounts.insert(String::new(), auth_data);
+                accounts
+            }
+        };
+
+        // Earlier versions stored accounts without a profile under a
+        // placeholder id. They are re-keyed by their ID token's subject; those
+        // without one can't be told apart, so they have to sign in again.
+        accounts.accounts = std::mem::take(&mut accounts.accounts)
+            .into_iter()
+            .filter_map(|(id, mut auth_data)| {
+                recover_user_info(&mut auth_data);
+                match account_id(&auth_data) {
+                    Ok(account_id) => {
+                        if accounts.active.as_deref() == Some(id.as_str()) || id.is_empty() {
+                            accounts.active = Some(account_id.clone());
+                        }
+                        Some((account_id, auth_data))
+                    }
+                    Err(e) => {
+                        warn!(
+                            "Dropping stored account {:?}, it has to sign in again: {}",
+                            id, e
+                        );
+                        None
+                    }
+                }
+            })
+            .collect();
+        if !accounts
+            .active
+            .as_ref()
+            .is_some_and(|id| accounts.accounts.contains_key(id))
+        {
+            accounts.active = None;
+        }
+        Ok(accounts)
+    }
+
+    pub fn active_account(&self) -> Option<&AuthData> {
+        self.active.as_ref().and_then(|id| self.accounts.get(id))
+    }
+
+    fn insert(&mut self, auth_data: AuthData, activate: bool) -> Result<(), String> {
+        let id = account_id(&auth_data)?;
+        if activate || self.active_account().is_none() {
+            self.active = Some(id.clone());
+        }
+        self.accounts.insert(id, auth_data);
+        Ok(())
+    }
+}
+
+/// Key an account is stored under: the user's subject identifier. Tokens
+/// without one can't be told apart from another account's, so they're refused.
+pub fn account_id(auth_data: &AuthData) -> Result<String, String> {
+    auth_data
+        .user_info
+        .as_ref()
+        .map(|user_info| user_info.sub.clone())
+        .filter(|sub| !sub.is_empty())
+        .ok_or_else(|| "The identity provider returned no user id for this account".to_string())
+}
+
+// Fills in the profile of tokens stored without one from the subject of their
+// ID token. The token was validated when it was stored; the subject only
+// decides which account the tokens are filed under.
+fn recover_user_info(auth_data: &mut AuthData) {
+    if auth_data.user_info.is_some() {
+        return;
+    }
+    auth_data.user_info = auth_data
+        .id_token
+        .as_deref()
+        .and_then(id_token_subject)
+        .map(|sub| UserInfo {
+            sub,
+            name: None,
+            email: None,
+            picture: None,
+        });
+}
+
+fn id_token_subject(id_token: &str) -> Option<String> {
+    let payload = id_token.split('.').nth(1)?;
+    let claims = general_purpose::URL_SAFE_NO_PAD
+        .decode(payload.trim_end_matches('='))
+        .ok()?;
+    let claims: serde_json::Value = serde_json::from_slice(&claims).ok()?;
+    claims
+        .get("sub")?
+        .as_str()
+        .filter(|sub| !sub.is_empty())
+        .map(str::to_string)
+}
+
+/// Persistent storage for the signed-in accounts' OAuth tokens. Backends only
+/// read and write the whole account set; the per-account operations are shared.
+pub trait CredentialStore: Send + Sync {
+    /// Short backend name, used in logs.
+    fn name(&self) -> &'static str;
+    fn load_accounts(&self) -> Result<StoredAccounts, String>;
+    fn save_accounts(&self, accounts: &StoredAccounts) -> Result<(), String>;
+    fn clear(&self) -> Result<(), String>;
+
+    /// Returns the active account's tokens.
+    fn load(&self) -> Result<Option<AuthData>, String> {
+        Ok(self.load_accounts()?.active_account().cloned())
+    }
+
+    /// Updates an account's tokens, e.g. after a refresh, without changing
+    /// which account is active.
+    fn save(&self, auth_data: &AuthData) -> Result<(), String> {
+        let _lock = ACCOUNTS_LOCK.lock().unwrap();
+        let mut accounts = self.load_accounts()?;
+        accounts.insert(auth_data.clone(), false)?;
+        self.save_accounts(&accounts)
+    }
+
+    /// Stores a newly signed-in account and makes it the active one.
+    fn add_account(&self, auth_data: &AuthData) -> Result<(), String> {
+        let _lock = ACCOUNTS_LOCK.lock().unwrap();
+        let mut accounts = self.load_accounts()?;
+        accounts.insert(auth_data.clone(), true)?;
+        self.save_accounts(&accounts)
+    }
+
+    /// Makes the account with the given id active and returns its tokens.
+    fn set_active(&self, id: &str) -> Result<AuthData, String> {
+        let _lock = ACCOUNTS_LOCK.lock().unwrap();
+        let mut accounts = self.load_accounts()?;
+        let auth_data = accounts
+            .accounts
+            .get(id)
+            .cloned()
+            .ok_or_else(|| format!("No account found for {}", id))?;
+        accounts.active = Some(id.to_string());
+        self.save_accounts(&accounts)?;
+        Ok(auth_data)
+    }
+
+    /// Forgets an account. When it was the active one, another remaining
+    /// account takes its place; the new active account is returned.
+    fn remove_account(&self, id: &str) -> Result<Option<AuthData>, String> {
+        let _lock = ACCOUNTS_LOCK.lock().unwrap();
+        let mut accounts = self.load_accounts()?;
+        accounts.accounts.remove(id);
+        if accounts.active.as_deref() == Some(id) {
+            accounts.active = accounts.accounts.keys().next().cloned();
+        }
+
+        if accounts.accounts.is_empty() {
+            self.clear()?;
+        } else {
+            self.save_accounts(&accounts)?;
+        }
+        Ok(accounts.active_account().cloned())
+    }
+
+    /// Forgets an account without making another one active, e.g. when its
+    /// tokens were rejected; the user picks the next account themselves.
+    fn forget_account(&self, id: &str) -> Result<(), String> {
+        let _lock = ACCOUNTS_LOCK.lock().unwrap();
+        let mut accounts = self.load_accounts()?;
+        accounts.accounts.remove(id);
+        if accounts.active.as_deref() == Some(id) {
+            accounts.active = None;
+        }
+
+        if accounts.accounts.is_empty() {
+            self.clear()
+        } else {
+            self.save_accounts(&accounts)
+        }
+    }
+}
+
+// Tauri managed state wrapping the backend selected at startup
+pub struct CredentialStoreManager(pub Box<dyn CredentialStore>);
+
+/// Stores tokens in the OS secret store (Keychain on macOS, Secret Service on Linux).
+pub struct KeyringStore {
+    entry: keyring::Entry,
+}
+
+impl KeyringStore {
+    pub fn new() -> Result<Self, String> {
+        let entry = keyring::Entry::new(KEYRING_SERVICE, KEYRING_ACCOUNT)
+            .map_err(|e| format!("Failed to open keyring entry: {}", e))?;
+        Ok(Self { entry })
+    }
+
+    // Checks that the secret store is reachable, e.g. that a Secret Service
+    // daemon is running on Linux. A missing entry still counts as available.
+    fn probe(&self) -> Result<(), String> {
+        match self.entry.get_password() {
+            Ok(_) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+impl CredentialStore for KeyringStore {
+    fn name(&self) -> &'static str {
+        "keyring"
+    }
+
+    fn load_accounts(&self) -> Result<StoredAccounts, String> {
+        match self.entry.get_password() {
+            Ok(json_data) => StoredAccounts::from_json(&json_data),
+            Err(keyring::Error::NoEntry) => Ok(StoredAccounts::default()),
+            Err(e) => Err(format!("Failed to read credentials from keyring: {}", e)),
+        }
+    }
+
+    fn save_accounts(&self, accounts: &StoredAccounts) -> Result<(), String> {
+        let json_data = serde_json::to_string(accounts).map_err(|e| e.to_string())?;
+        self.entry
+            .set_password(&json_data)
+            .map_err(|e| format!("Failed to write credentials to keyring: {}", e))
+    }
+
+    fn clear(&self) -> Result<(), String> {
+        match self.entry.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(format!("Failed to delete credentials from keyring: {}", e)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct EncryptedPayload {
+    // 1: keyed by the legacy key file, 2: keyed as `key_source` says
+    version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    key_source: Option<KeySource>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    salt: Option<String>,
+    nonce: String,
+    ciphertext: String,
+}
+
+// Where the encrypted-file store's key comes from
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum KeySource {
+    // A random key in the OS secret store
+    Keyring,
+    // Derived from the OS installation's machine id and the payload's salt
+    MachineId,
+}
+
+/// Stores tokens AES-256-GCM encrypted in the app data dir. Used when the OS
+/// secret store can't hold the tokens themselves.
+///
+/// The key is never stored beside the ciphertext: it is a random key in the
+/// OS secret store when that can hold it (Credential Manager on Windows),
+/// otherwise it is derived from the machine id. This keeps the tokens safe
+/// when the app data dir is copied off the machine, e.g. by a backup or sync
+/// tool. It does not protect against other programs running as the same
+/// user, which can read the machine id or the secret store just like we do.
+pub struct EncryptedFileStore {
+    store_path: PathBuf,
+    legacy_key_path: PathBuf,
+}
+
+impl EncryptedFileStore {
+    pub fn new(app_data_dir: &Path) -> Self {
+        Self {
+            store_path: app_data_dir.join(ENCRYPTED_STORE_PATH),
+            legacy_key_path: app_data_dir.join(LEGACY_KEY_PATH),
+        }
+    }
+
+    // The key a stored payload was encrypted with
+    fn payload_key(&self, payload: &EncryptedPayload) -> Result<Vec<u8>, String> {
+        match (payload.version, payload.key_source) {
+            (1, _) => {
+                let encoded =
+                    fs::read_to_string(&self.legacy_key_path).map_err(|e| e.to_string())?;
+                decode(&encoded, "encryption key")
+            }
+            (2, Some(KeySource::Keyring)) => keyring_file_key(false),
+            (2, Some(KeySource::MachineId)) => {
+                let salt = payload
+                    .salt
+                    .as_deref()
+                    .ok_or("Missing salt in credential store")?;
+                machine_key(&decode(salt, "salt")?)
+            }
+            (version, _) => Err(format!("Unsupported credential store version {}", version)),
+        }
+    }
+}
+
+impl CredentialStore for EncryptedFileStore {
+    fn name(&self) -> &'static str {
+        "encrypted-file"
+    }
+
+    fn load_accounts(&self) -> Result<StoredAccounts, String> {
+        if !self.store_path.exists() {
+            return Ok(StoredAccounts::default());
+        }
+
+        let json_data = fs::read_to_string(&self.store_path).map_err(|e| e.to_string())?;
+        let payload: EncryptedPayload =
+            serde_json::from_str(&json_data).map_err(|e| e.to_string())?;
+
+        let nonce = decode(&payload.nonce, "nonce")?;
+        let ciphertext = decode(&payload.ciphertext, "ciphertext")?;
+        if nonce.len() != 12 {
+            return Err("Invalid nonce length in credential store".to_string());
+        }
+
+        let plaintext = cipher(&self.payload_key(&payload)?)?
+            .decrypt(Nonce::from_slice(&nonce), ciphertext.as_ref())
+            .map_err(|_| "Failed to decrypt credential store".to_string())?;
+
+        StoredAccounts::from_json(&String::from_utf8_lossy(&plaintext))
+    }
+
+    fn save_accounts(&self, accounts: &StoredAccounts) -> Result<(), String> {
+        let plaintext = serde_json::to_vec(accounts).map_err(|e| e.to_string())?;
+
+        let (key_source, salt, key) = match keyring_file_key(true) {
+            Ok(key) => (KeySource::Keyring, None, key),
+            Err(e) => {
+                debug!(
+                    "Deriving the credential store key from the machine id: {}",
+                    e
+                );
+                let mut salt = vec![0u8; 16];
+                rand::thread_rng().fill_bytes(&mut salt);
+                let key = machine_key(&salt)?;
+                (KeySource::MachineId, Some(salt), key)
+            }
+        };
+
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = cipher(&key)?
+            .encrypt(&nonce, plaintext.as_ref())
+            .map_err(|_| "Failed to encrypt credentials".to_string())?;
+
+        let payload = EncryptedPayload {
+            version: 2,
+            key_source: Some(key_source),
+            salt: salt.map(|salt| general_purpose::STANDARD.encode(salt)),
+            nonce: general_purpose::STANDARD.encode(nonce),
+            ciphertext: general_purpose::STANDARD.encode(ciphertext),
+        };
+        let json_data = serde_json::to_string_pretty(&payload).map_err(|e| e.to_string())?;
+        write_private_file(&self.store_path, &json_data)?;
+
+        if self.legacy_key_path.exists() {
+            if let Err(e) = fs::remove_file(&self.legacy_key_path) {
+                warn!("Failed to remove legacy encryption key: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<(), String> {
+        for path in [&self.store_path, &self.legacy_key_path] {
+            if path.exists() {
+                fs::remove_file(path).map_err(|e| e.to_string())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn decode(encoded: &str, what: &str) -> Result<Vec<u8>, String> {
+    general_purpose::STANDARD
+        .decode(encoded.trim())
+        .map_err(|e| format!("Failed to decode {}: {}", what, e))
+}
+
+fn cipher(key: &[u8]) -> Result<Aes256Gcm, String> {
+    Aes256Gcm::new_from_slice(key).map_err(|e| format!("Invalid encryption key: {}", e))
+}
+
+// The file store's key from the OS secret store, created on first use when `create` is set
+fn keyring_file_key(create: bool) -> Result<Vec<u8>, String> {
+    let entry = keyring::Entry::new(KEYRING_SERVICE, KEYRING_FILE_KEY_ACCOUNT)
+        .map_err(|e| format!("Failed to open keyring entry: {}", e))?;
+    match entry.get_password() {
+        Ok(encoded) => decode(&encoded, "encryption key"),
+        Err(keyring::Error::NoEntry) if create => {
+            let mut key = vec![0u8; 32];
+            rand::thread_rng().fill_bytes(&mut key);
+            entry
+                .set_password(&general_purpose::STANDARD.encode(&key))
+                .map_err(|e| format!("Failed to write encryption key to keyring: {}", e))?;
+            Ok(key)
+        }
+        Err(e) => Err(format!("Failed to read encryption key from keyring: {}", e)),
+    }
+}
+
+fn machine_key(salt: &[u8]) -> Result<Vec<u8>, String> {
+    let mut hasher = Sha256::new();
+    hasher.update(b"waystation-credentials\0");
+    hasher.update(machine_id()?.as_bytes());
+    hasher.update(salt);
+    Ok(hasher.finalize().to_vec())
+}
+
+// A stable id of the OS installation, kept outside the app data dir
+fn machine_id() -> Result<String, String> {
+    #[cfg(target_os = "linux")]
+    {
+        ["/etc/machine-id", "/var/lib/dbus/machine-id"]
+            .iter()
+            .filter_map(|path| fs::read_to_string(path).ok())
+            .map(|id| id.trim().to_string())
+            .find(|id| !id.is_empty())
+            .ok_or_else(|| "No machine id found".to_string())
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        let output = std::process::Command::new("ioreg")
+            .args(["-rd1", "-c", "IOPlatformExpertDevice"])
+            .output()
+            .map_err(|e| format!("Failed to run ioreg: {}", e))?;
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .find(|line| line.contains("\"IOPlatformUUID\""))
+            .and_then(|line| line.rsplit('"').nth(1))
+            .map(str::to_string)
+            .ok_or_else(|| "No IOPlatformUUID found".to_string())
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        let output = std::process::Command::new("reg")
+            .args([
+                "query",
+                r"HKLM\SOFTWARE\Microsoft\Cryptography",
+                "/v",
+                "MachineGuid",
+            ])
+            .output()
+            .map_err(|e| format!("Failed to run reg: {}", e))?;
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .find(|line| line.contains("MachineGuid"))
+            .and_then(|line| line.split_whitespace().last())
+            .map(str::to_string)
+            .ok_or_else(|| "No MachineGuid found".to_string())
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+    {
+        Err("Machine id is not supported on this platform".to_string())
+    }
+}
+
+// Writes a file readable only by the current user
+fn write_private_file(path: &Path, contents: &str) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::io::Write;
+        use std::os::unix::fs::OpenOptionsExt;
+
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)
+            .map_err(|e| e.to_string())?;
+        file.write_all(contents.as_bytes())
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    {
+        fs::write(path, contents).map_err(|e| e.to_string())
+    }
+}
+
+/// Picks the best available backend: the OS secret store when reachable,
+/// otherwise the encrypted file in the app data dir.
+pub fn open_credential_store(app_data_dir: &Path) -> Box<dyn CredentialStore> {
+    // Windows Credential Manager caps secrets at 2.5 KB, which the combined
+    // access, refresh and ID tokens exceed, so Windows always uses the file store.
+    #[cfg(not(target_os = "windows"))]
+    {
+        match KeyringStore::new().and_then(|store| store.probe().map(|_| store)) {
+            Ok(store) => {
+                debug!("Using OS keyring for credentials");
+                return Box::new(store);
+            }
+            Err(e) => {
+                warn!(
+                    "OS keyring unavailable, falling back to encrypted file: {}",
+                    e
+                );
+            }
+        }
+    }
+
+    Box::new(EncryptedFileStore::new(app_data_dir))
+}
+
+/// Moves tokens from the plaintext `.auth.dat` written by earlier versions into
+/// `store`, then deletes the plaintext file.
+pub fn migrate_legacy_store(store: &dyn CredentialStore, app_data_dir: &Path) {
+    let legacy_path = app_data_dir.join(LEGACY_STORE_PATH);
+    if !legacy_path.exists() {
+        return;
+    }
+
+    info!("Migrating legacy credentials to {} store", store.name());
+
+    let mut auth_data: AuthData = match fs::read_to_string(&legacy_path)
+        .map_err(|e| e.to_string())
+        .and_then(|json_data| serde_json::from_str(&json_data).map_err(|e| e.to_string()))
+    {
+        Ok(auth_data) => auth_data,
+        Err(e) => {
+            // An unreadable legacy file holds nothing we can use; drop it rather
+            // than leaving a plaintext token on disk.
+            warn!("Discarding unreadable legacy credentials: {}", e);
+            let _ = fs::remove_file(&legacy_path);
+            return;
+        }
+    };
+
+    recover_user_info(&mut auth_data);
+    if let Err(e) = account_id(&auth_data) {
+        // Retrying can't help, and the plaintext token shouldn't stay behind
+        warn!("Discarding legacy credentials, sign in again: {}", e);
+        let _ = fs::remove_file(&legacy_path);
+        return;
+    }
+
+    if let Err(e) = store.add_account(&auth_data) {
+        // Keep the legacy file so the user stays signed in; we retry next launch
+        error!("Failed to migrate legacy credentials: {}", e);
+        return;
+    }
+
+    match fs::remove_file(&legacy_path) {
+        Ok(()) => info!("Legacy credentials migrated"),
+        Err(e) => error!("Failed to remove legacy credentials file: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // Just keeps the accounts in memory
+    #[derive(Default)]
+    struct MemoryStore(Mutex<StoredAccounts>);
+
+    impl CredentialStore for MemoryStore {
+        fn name(&self) -> &'static str {
+            "memory"
+        }
+
+        fn load_accounts(&self) -> Result<StoredAccounts, String> {
+            Ok(self.0.lock().unwrap().clone())
+        }
+
+        fn save_accounts(&self, accounts: &StoredAccounts) -> Result<(), String> {
+            *self.0.lock().unwrap() = accounts.clone();
+            Ok(())
+        }
+
+        fn clear(&self) -> Result<(), String> {
+            *self.0.lock().unwrap() = StoredAccounts::default();
+            Ok(())
+        }
+    }
+
+    // An unsigned ID token; only its claims are read here
+    fn id_token(sub: &str) -> String {
+        let claims = general_purpose::URL_SAFE_NO_PAD.encode(json!({ "sub": sub }).to_string());
+        format!("eyJhbGciOiJub25lIn0.{}.", claims)
+    }
+
+    // Migrates a legacy store holding `legacy` from a scratch directory,
+    // returning what was stored and whether the legacy file is still there
+    fn migrate(legacy: serde_json::Value, name: &str) -> (StoredAccounts, bool) {
+        let dir = std::env::temp_dir().join(format!(
+            "waystation-credentials-{}-{}",
+            std::process::id(),
+            name
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(LEGACY_STORE_PATH), legacy.to_string()).unwrap();
+        let store = MemoryStore::default();
+        migrate_legacy_store(&store, &dir);
+        let left_behind = dir.join(LEGACY_STORE_PATH).exists();
+        let _ = fs::remove_dir_all(&dir);
+        (store.load_accounts().unwrap(), left_behind)
+    }
+
+    #[test]
+    fn legacy_store_without_profile_is_keyed_by_id_token() {
+        let (accounts, left_behind) = migrate(
+            json!({ "access_token": "a", "id_token": id_token("user-1") }),
+            "id-token",
+        );
+        assert!(!left_behind);
+        assert_eq!(accounts.active.as_deref(), Some("user-1"));
+        assert_eq!(accounts.active_account().unwrap().access_token, "a");
+    }
+
+    #[test]
+    fn legacy_store_without_any_user_id_is_discarded() {
+        let (accounts, left_behind) = migrate(json!({ "access_token": "a" }), "no-user");
+        assert!(!left_behind);
+        assert!(accounts.accounts.is_empty());
+    }
+
+    #[test]
+    fn legacy_store_with_profile_is_migrated() {
+        let (accounts, left_behind) = migrate(
+            json!({ "access_token": "a", "user_info": { "sub": "user-1" } }),
+            "profile",
+        );
+        assert!(!left_behind);
+        assert_eq!(accounts.active.as_deref(), Some("user-1"));
+    }
+
+    #[test]
+    fn accounts_without_a_profile_are_keyed_by_id_token() {
+        let json_data = json!({
+            "active": "",
+            "accounts": { "": { "access_token": "a", "id_token": id_token("user-1") } }
+        });
+        let accounts = StoredAccounts::from_json(&json_data.to_string()).unwrap();
+        assert_eq!(accounts.active.as_deref(), Some("user-1"));
+        assert_eq!(
+            accounts
+                .active_account()
+                .unwrap()
+                .user_info
+                .as_ref()
+                .unwrap()
+                .sub,
+            "user-1"
+        );
+    }
+
+    #[test]
+    fn accounts_without_a_subject_are_dropped() {
+        let json_data = json!({
+            "active": "unknown",
+            "accounts": {
+                "unknown": { "access_token": "a" },
+                "user-1": { "access_token": "b", "user_info": { "sub": "user-1" } }
+            }
+        });
+        let accounts = StoredAccounts::from_json(&json_data.to_string()).unwrap();
+        assert_eq!(accounts.accounts.keys().collect::<Vec<_>>(), ["user-1"]);
+        assert_eq!(accounts.active, None);
+    }
+
+    #[test]
+    fn legacy_single_record_becomes_the_active_account() {
+        let json_data = json!({ "access_token": "a", "user_info": { "sub": "user-1" } });
+        let accounts = StoredAccounts::from_json(&json_data.to_string()).unwrap();
+        assert_eq!(accounts.active.as_deref(), Some("user-1"));
+        assert_eq!(accounts.active_account().unwrap().access_token, "a");
+    }
+}
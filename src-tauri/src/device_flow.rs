@@ -0,0 +1,195 @@
+// OAuth 2.0 device authorization grant (RFC 8628), for machines where the
+// launcher can't open a browser, e.g. over SSH.
+
+use crate::auth_config::{get_auth_config, http_client, AuthConfig};
+use crate::credentials::CredentialStoreManager;
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::{finish_login, TokenResponse};
+use log::{debug, error, info};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+
+const DEVICE_CODE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+// Defaults from RFC 8628 when the server doesn't say otherwise
+const DEFAULT_POLL_INTERVAL_SECS: u64 = 5;
+const SLOW_DOWN_INCREMENT_SECS: u64 = 5;
+
+// Bumped on every new device login or cancellation; a polling task stops as
+// soon as the value no longer matches the one it started with
+static DEVICE_LOGIN_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Deserialize)]
+struct DeviceAuthorizationResponse {
+    device_code: String,
+    user_code: String,
+    verification_uri: String,
+    verification_uri_complete: Option<String>,
+    expires_in: u64,
+    interval: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenErrorResponse {
+    error: String,
+    error_description: Option<String>,
+}
+
+/// What the user needs to finish signing in on another device.
+#[derive(Debug, Serialize, Clone)]
+pub struct DeviceLogin {
+    pub user_code: String,
+    pub verification_uri: String,
+    pub verification_uri_complete: Option<String>,
+    pub expires_in: u64,
+}
+
+/// Starts a device-code login and polls for its completion in the background.
+/// The outcome is reported with the `auth-success` / `auth-error` events.
+#[tauri::command]
+pub async fn start_device_login(app_handle: AppHandle) -> CommandResult<DeviceLogin> {
+    let config = get_auth_config()
+        .await
+        .map_err(|e| CommandError::new(ErrorCode::AuthConfig, e))?;
+    let Some(device_authorization_endpoint) = &config.device_authorization_endpoint else {
+        return Err(CommandError::new(
+            ErrorCode::AuthConfig,
+            "The identity provider does not support device login",
+        ));
+    };
+
+    let client = http_client();
+    let params = [
+        ("client_id", config.client_id.as_str()),
+        ("scope", "openid profile email"),
+    ];
+
+    let response = client
+        .post(device_authorization_endpoint)
+        .form(&params)
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        let error_text = response.text().await?;
+        return Err(CommandError::new(
+            ErrorCode::AuthFailed,
+            "Device authorization request failed",
+        )
+        .with_details(error_text));
+    }
+
+    let authorization: DeviceAuthorizationResponse = response.json().await?;
+
+    let device_login = DeviceLogin {
+        user_code: authorization.user_code.clone(),
+        verification_uri: authorization.verification_uri.clone(),
+        verification_uri_complete: authorization.verification_uri_complete.clone(),
+        expires_in: authorization.expires_in,
+    };
+
+    let generation = DEVICE_LOGIN_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    tauri::async_runtime::spawn(async move {
+        let result = poll_for_tokens(&client, &config, &authorization, generation).await;
+        let result = match result {
+            Ok(Some(tokens)) => {
+                let credentials = app_handle.state::<CredentialStoreManager>();
+                finish_login(&client, tokens, None, credentials.0.as_ref())
+                    .await
+                    .map(Some)
+            }
+            Ok(None) => Ok(None),
+            Err(e) => Err(CommandError::new(ErrorCode::AuthFailed, e)),
+        };
+
+        match result {
+            Ok(Some(auth_data)) => {
+                info!("Device login completed");
+                let _ = app_handle.emit("auth-success", &auth_data);
+            }
+            Ok(None) => debug!("Device login cancelled"),
+            Err(e) => {
+                error!("Device login failed: {}", e);
+                let _ = app_handle.emit("auth-error", e);
+            }
+        }
+    });
+
+    Ok(device_login)
+}
+
+/// Stops polling for any in-flight device login.
+#[tauri::command]
+pub fn cancel_device_login() -> CommandResult<()> {
+    DEVICE_LOGIN_GENERATION.fetch_add(1, Ordering::SeqCst);
+    Ok(())
+}
+
+// Polls the token endpoint until the user approves or denies the request.
+// Returns Ok(None) when the login was cancelled or superseded.
+async fn poll_for_tokens(
+    client: &Client,
+    config: &AuthConfig,
+    authorization: &DeviceAuthorizationResponse,
+    generation: u64,
+) -> Result<Option<TokenResponse>, String> {
+    let deadline = Instant::now() + Duration::from_secs(authorization.expires_in);
+    let mut interval = authorization.interval.unwrap_or(DEFAULT_POLL_INTERVAL_SECS);
+
+    loop {
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+
+        if DEVICE_LOGIN_GENERATION.load(Ordering::SeqCst) != generation {
+            return Ok(None);
+        }
+        if Instant::now() >= deadline {
+            return Err("Device code expired before the login was approved".to_string());
+        }
+
+        let params = [
+            ("client_id", config.client_id.as_str()),
+            ("device_code", authorization.device_code.as_str()),
+            ("grant_type", DEVICE_CODE_GRANT_TYPE),
+        ];
+
+        let response = match client
+            .post(&config.token_endpoint)
+            .form(&params)
+            .send()
+            .await
+        {
+            Ok(response) => response,
+            Err(e) => {
+                // Keep polling through transient network errors until the code expires
+                debug!("Device token poll failed: {}", e);
+                continue;
+            }
+        };
+
+        if response.status().is_success() {
+            let tokens: TokenResponse = response.json().await.map_err(|e| e.to_string())?;
+            return Ok(Some(tokens));
+        }
+
+        let error_text = response.text().await.map_err(|e| e.to_string())?;
+        let token_error: TokenErrorResponse = serde_json::from_str(&error_text)
+            .map_err(|_| format!("Token request failed: {}", error_text))?;
+
+        match token_error.error.as_str() {
+            "authorization_pending" => {}
+            "slow_down" => interval += SLOW_DOWN_INCREMENT_SECS,
+            "access_denied" => return Err("Login was denied".to_string()),
+            "expired_token" => {
+                return Err("Device code expired before the login was approved".to_string())
+            }
+            _ => {
+                return Err(format!(
+                    "Token request failed: {}",
+                    token_error.error_description.unwrap_or(token_error.error)
+                ))
+            }
+        }
+    }
+}
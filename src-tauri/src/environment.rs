@@ -1,643 +1,409 @@
 // Modified from original Apache 2.0 licensed code: Removed UVX support and adjusted for WayStation MCP
 
-use log::{debug, error, info};
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::node_runtime::{
+    cancel_install, find_installed_node, install_with_rollback, installer_provider,
+    is_install_cancelled, node_install_version, node_requirement, recover_interrupted_install,
+    reset_install_cancellation, InstallEvent, OutputStream, INSTALL_CANCELLED,
+};
+use log::{debug, error, info, warn};
 use once_cell::sync::Lazy;
-use std::process::Command;
+use serde::Serialize;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Mutex;
+use std::sync::{Mutex, PoisonError, TryLockError};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, State};
 
-static NVM_INSTALLED: AtomicBool = AtomicBool::new(false);
 static NODE_INSTALLED: AtomicBool = AtomicBool::new(false);
 static ENVIRONMENT_SETUP_STARTED: AtomicBool = AtomicBool::new(false);
 static ENVIRONMENT_SETUP_COMPLETED: AtomicBool = AtomicBool::new(false);
-static NODE_VERSION: &str = "v20.9.0";
 static IS_TEST_MODE: AtomicBool = AtomicBool::new(false);
+static EXIT_DEFERRED: AtomicBool = AtomicBool::new(false);
 
 // Lock to prevent concurrent environment setup operations
 static ENVIRONMENT_SETUP_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
 
-pub fn is_test_mode() -> bool {
-    IS_TEST_MODE.load(Ordering::SeqCst)
+// Keeps the status from growing without bound on a chatty install
+const MAX_OUTPUT_LINES: usize = 500;
+// Share of the overall progress taken by installing Node.js
+const INSTALL_START_PERCENT: u8 = 10;
+const INSTALL_END_PERCENT: u8 = 95;
+// How long closing the app waits for a cancelled install to roll back
+const EXIT_ROLLBACK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where environment setup is at. There is no step for installing nvm: setup
+/// uses a version manager only if one is already installed, and otherwise
+/// installs Node.js itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SetupStep {
+    Idle,
+    /// Looking for an installed Node.js that meets the requirement.
+    Detecting,
+    /// Installing Node.js with a runtime provider.
+    InstallingNode,
+    Ready,
+    Failed,
+    /// Stopped by `cancel_environment_setup`; partial installs were removed.
+    Cancelled,
 }
 
-pub fn get_nvm_node_paths() -> Result<(String, String), String> {
-    debug!("get_nvm_node_paths called, test_mode: {}", is_test_mode());
-
-    if is_test_mode() {
-        debug!("Using test mode paths for nvm/node");
-        return Ok((
-            "/test/.nvm/versions/node/v20.9.0/bin/node".to_string(),
-            "/test/.nvm/versions/node/v20.9.0/bin/npx".to_string(),
-        ));
-    }
-
-    #[cfg(target_os = "macos")]
-    {
-        let shell_command = format!(
-            r#"
-          export NVM_DIR="$HOME/.nvm"
-          [ -s "$NVM_DIR/nvm.sh" ] && \. "$NVM_DIR/nvm.sh"
-          nvm use {} > /dev/null 2>&1
-          which node
-          which npx
-      "#,
-            NODE_VERSION
-        );
-
-        let output = Command::new("bash")
-            .arg("-c")
-            .arg(shell_command)
-            .output()
-            .map_err(|e| format!("Failed to get node paths: {}", e))?;
-
-        if !output.status.success() {
-            return Err("Failed to get node and npx paths".to_string());
-        }
-
-        let output_str = String::from_utf8_lossy(&output.stdout);
-        let mut lines = output_str.lines();
-
-        let node_path = lines
-            .next()
-            .ok_or("Failed to get node path")?
-            .trim()
-            .to_string();
-
-        let npx_path = lines
-            .next()
-            .ok_or("Failed to get npx path")?
-            .trim()
-            .to_string();
-
-        // Only validate paths in non-test mode
-        if !is_test_mode() && !node_path.contains(".nvm/versions/node") {
-            debug!("Node path validation failed: {}", node_path);
-            return Err("Node path is not from nvm installation".to_string());
-        }
+#[derive(Debug, Clone, Serialize)]
+pub struct OutputLine {
+    pub stream: OutputStream,
+    pub line: String,
+}
 
-        return Ok((node_path, npx_path));
-    }
+#[derive(Debug, Clone, Serialize)]
+pub struct EnvironmentStatus {
+    pub step: SetupStep,
+    /// Overall progress, 0-100.
+    pub percent: u8,
+    /// What setup is doing now, e.g. "Verifying checksum".
+    pub message: String,
+    /// The Node.js version in use, like "v20.9.0", once one was found.
+    pub node_version: Option<String>,
+    /// Name of the provider that Node.js comes from.
+    pub node_provider: Option<String>,
+    /// Installer output of the latest run, up to the last 500 lines.
+    pub output: Vec<OutputLine>,
+    pub error: Option<CommandError>,
+}
 
-    #[cfg(target_os = "windows")]
-    {
-        // On Windows, we'll check for Node.js in common installation locations
-        // and use the system-installed Node.js rather than NVM
-        
-        // First try to find node in PATH
-        let node_output = Command::new("where")
-            .arg("node.exe")
-            .output()
-            .map_err(|e| format!("Failed to locate node.exe: {}", e))?;
-            
-        let npx_output = Command::new("where")
-            .arg("npx.cmd")
-            .output()
-            .map_err(|e| format!("Failed to locate npx.cmd: {}", e))?;
-            
-        if node_output.status.success() && npx_output.status.success() {
-            let node_path = String::from_utf8_lossy(&node_output.stdout)
-                .lines()
-                .next()
-                .ok_or("Failed to get node path")?
-                .trim()
-                .to_string();
-                
-            let npx_path = String::from_utf8_lossy(&npx_output.stdout)
-                .lines()
-                .next()
-                .ok_or("Failed to get npx path")?
-                .trim()
-                .to_string();
-                
-            return Ok((node_path, npx_path));
+impl Default for EnvironmentStatus {
+    fn default() -> Self {
+        EnvironmentStatus {
+            step: SetupStep::Idle,
+            percent: 0,
+            message: "Environment setup has not started".to_string(),
+            node_version: None,
+            node_provider: None,
+            output: Vec::new(),
+            error: None,
         }
-        
-        // If not found in PATH, check common installation locations
-        let program_files = std::env::var("ProgramFiles").unwrap_or_default();
-        let appdata = std::env::var("APPDATA").unwrap_or_default();
-        
-        let possible_node_paths = [
-            format!("{}\\nodejs\\node.exe", program_files),
-            format!("{}\\nodejs\\node.exe", appdata),
-        ];
-        
-        let possible_npx_paths = [
-            format!("{}\\nodejs\\npx.cmd", program_files),
-            format!("{}\\nodejs\\npx.cmd", appdata),
-        ];
-        
-        for (node_path, npx_path) in possible_node_paths.iter().zip(possible_npx_paths.iter()) {
-            if std::path::Path::new(node_path).exists() && std::path::Path::new(npx_path).exists() {
-                return Ok((node_path.clone(), npx_path.clone()));
-            }
-        }
-        
-        return Err("Node.js installation not found".to_string());
     }
+}
 
-    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
-    {
-        // For other Unix-like systems
-        let shell_command = format!(
-            r#"
-          export NVM_DIR="$HOME/.nvm"
-          [ -s "$NVM_DIR/nvm.sh" ] && \. "$NVM_DIR/nvm.sh"
-          nvm use {} > /dev/null 2>&1
-          which node
-          which npx
-      "#,
-            NODE_VERSION
-        );
-
-        let output = Command::new("bash")
-            .arg("-c")
-            .arg(shell_command)
-            .output()
-            .map_err(|e| format!("Failed to get node paths: {}", e))?;
-
-        if !output.status.success() {
-            return Err("Failed to get node and npx paths".to_string());
-        }
-
-        let output_str = String::from_utf8_lossy(&output.stdout);
-        let mut lines = output_str.lines();
-
-        let node_path = lines
-            .next()
-            .ok_or("Failed to get node path")?
-            .trim()
-            .to_string();
+/// Payload of the `environment-progress` event. It carries only the newest
+/// output line; `get_environment_status` returns all of them.
+#[derive(Debug, Clone, Serialize)]
+pub struct EnvironmentProgress {
+    pub step: SetupStep,
+    pub percent: u8,
+    pub message: String,
+    pub output: Option<OutputLine>,
+    pub error: Option<CommandError>,
+}
 
-        let npx_path = lines
-            .next()
-            .ok_or("Failed to get npx path")?
-            .trim()
-            .to_string();
+// State management: status of the current or last environment setup
+#[derive(Default)]
+pub struct EnvironmentStatusManager(Mutex<EnvironmentStatus>);
 
-        return Ok((node_path, npx_path));
-    }
+// Records setup progress in the managed status and emits it to the frontend
+struct SetupReporter {
+    app_handle: AppHandle,
 }
 
-fn check_node_version() -> Result<String, String> {
-    if is_test_mode() {
-        return Ok(NODE_VERSION.to_string());
+impl SetupReporter {
+    fn update(&self, change: impl FnOnce(&mut EnvironmentStatus), output: Option<OutputLine>) {
+        let state = self.app_handle.state::<EnvironmentStatusManager>();
+        let progress = {
+            let mut status = state.0.lock().unwrap();
+            change(&mut status);
+            if let Some(line) = &output {
+                status.output.push(line.clone());
+                if status.output.len() > MAX_OUTPUT_LINES {
+                    let excess = status.output.len() - MAX_OUTPUT_LINES;
+                    status.output.drain(..excess);
+                }
+            }
+            EnvironmentProgress {
+                step: status.step,
+                percent: status.percent,
+                message: status.message.clone(),
+                output,
+                error: status.error.clone(),
+            }
+        };
+        let _ = self.app_handle.emit("environment-progress", &progress);
     }
 
-    // If we already confirmed node is installed with correct version, return early
-    if NODE_INSTALLED.load(Ordering::SeqCst) {
-        debug!("Node.js already confirmed as installed");
-        return Ok(NODE_VERSION.to_string());
+    // Starts a new run, keeping the Node.js found by an earlier one
+    fn start(&self) {
+        self.update(
+            |status| {
+                status.output.clear();
+                status.error = None;
+            },
+            None,
+        );
     }
 
-    #[cfg(target_os = "macos")]
-    {
-        // Check NVM-installed node first
-        let shell_command = format!(
-            r#"
-            export NVM_DIR="$HOME/.nvm"
-            [ -s "$NVM_DIR/nvm.sh" ] && \. "$NVM_DIR/nvm.sh"
-            nvm list | grep -w "{}" || true
-        "#,
-            NODE_VERSION
+    fn step(&self, step: SetupStep, percent: u8, message: &str) {
+        debug!("Environment setup: {}", message);
+        self.update(
+            |status| {
+                status.step = step;
+                status.percent = percent;
+                status.message = message.to_string();
+            },
+            None,
         );
-
-        let output = Command::new("bash")
-            .arg("-c")
-            .arg(shell_command)
-            .output()
-            .map_err(|e| format!("Failed to check nvm node version: {}", e))?;
-
-        let output_str = String::from_utf8_lossy(&output.stdout);
-        if output_str.contains(NODE_VERSION) {
-            info!("Node.js {} is already installed via nvm", NODE_VERSION);
-            NODE_INSTALLED.store(true, Ordering::SeqCst);
-            return Ok(NODE_VERSION.to_string());
-        }
     }
 
-    // Check system node (works on all platforms)
-    let node_command = if cfg!(target_os = "windows") {
-        "node.exe"
-    } else {
-        "node"
-    };
-
-    let version_command = Command::new(node_command)
-        .arg("--version")
-        .output()
-        .map_err(|e| format!("Failed to check node version: {}", e))?;
-
-    if version_command.status.success() {
-        let version = String::from_utf8_lossy(&version_command.stdout)
-            .trim()
-            .to_string();
-
-        if version == NODE_VERSION {
-            info!("Node.js {} is already installed system-wide", NODE_VERSION);
-            NODE_INSTALLED.store(true, Ordering::SeqCst);
-            return Ok(version);
+    fn install_event(&self, event: InstallEvent) {
+        match event {
+            InstallEvent::Step(message) => {
+                debug!("Node.js install: {}", message);
+                self.update(|status| status.message = message, None);
+            }
+            InstallEvent::Percent(percent) => {
+                let span = u16::from(INSTALL_END_PERCENT - INSTALL_START_PERCENT);
+                let percent =
+                    INSTALL_START_PERCENT + (u16::from(percent.min(100)) * span / 100) as u8;
+                self.update(|status| status.percent = percent, None);
+            }
+            InstallEvent::Output(stream, line) => {
+                debug!("Node.js install: {}", line);
+                self.update(|_| {}, Some(OutputLine { stream, line }));
+            }
         }
+    }
 
-        info!("Found Node.js {} but {} is required", version, NODE_VERSION);
-        return Ok(version);
+    fn ready(&self, node_version: Option<String>, node_provider: Option<String>) {
+        self.update(
+            |status| {
+                status.step = SetupStep::Ready;
+                status.percent = 100;
+                status.message = "Node environment is ready".to_string();
+                if node_version.is_some() {
+                    status.node_version = node_version;
+                    status.node_provider = node_provider;
+                }
+            },
+            None,
+        );
     }
 
-    Err("Node.js not found".to_string())
-}
+    fn cancelled(&self) {
+        self.update(
+            |status| {
+                status.step = SetupStep::Cancelled;
+                status.message = "Environment setup was cancelled".to_string();
+            },
+            None,
+        );
+    }
 
-fn check_nvm_version() -> Result<String, String> {
-    if is_test_mode() {
-        return Ok("0.40.1".to_string());
+    fn fail(&self, error: CommandError) {
+        self.update(
+            |status| {
+                status.step = SetupStep::Failed;
+                status.message = "Environment setup failed".to_string();
+                status.error = Some(error);
+            },
+            None,
+        );
     }
+}
 
-    #[cfg(target_os = "macos")]
-    {
-        let shell_command = r#"
-            export NVM_DIR="$HOME/.nvm"
-            [ -s "$NVM_DIR/nvm.sh" ] && \. "$NVM_DIR/nvm.sh"
-            nvm --version
-        "#;
-
-        let output = Command::new("bash")
-            .arg("-c")
-            .arg(shell_command)
-            .output()
-            .map_err(|e| format!("Failed to check nvm version: {}", e))?;
-
-        if !output.status.success() {
-            return Err("Failed to get nvm version".to_string());
-        }
+pub fn is_test_mode() -> bool {
+    IS_TEST_MODE.load(Ordering::SeqCst)
+}
 
-        return Ok(String::from_utf8_lossy(&output.stdout).trim().to_string());
-    }
+/// Paths of the node and npx executables of the best installed Node.js
+/// version meeting the requirement (see `node_runtime`).
+pub fn get_node_paths() -> Result<(String, String), String> {
+    debug!("get_node_paths called, test_mode: {}", is_test_mode());
 
-    #[cfg(target_os = "windows")]
-    {
-        // On Windows, check if nvm-windows is installed
-        let appdata = std::env::var("APPDATA").unwrap_or_default();
-        let nvm_home = std::env::var("NVM_HOME").unwrap_or_else(|_| format!("{}\\nvm", appdata));
-        
-        let nvm_exe = format!("{}\\nvm.exe", nvm_home);
-        
-        if !std::path::Path::new(&nvm_exe).exists() {
-            return Err("NVM for Windows not found".to_string());
-        }
-        
-        let output = Command::new(&nvm_exe)
-            .arg("version")
-            .output()
-            .map_err(|e| format!("Failed to check nvm version: {}", e))?;
-            
-        if !output.status.success() {
-            return Err("Failed to get nvm version".to_string());
-        }
-        
-        return Ok(String::from_utf8_lossy(&output.stdout).trim().to_string());
+    if is_test_mode() {
+        debug!("Using test mode paths for node");
+        return Ok((
+            "/test/.nvm/versions/node/v20.9.0/bin/node".to_string(),
+            "/test/.nvm/versions/node/v20.9.0/bin/npx".to_string(),
+        ));
     }
 
-    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
-    {
-        let shell_command = r#"
-            export NVM_DIR="$HOME/.nvm"
-            [ -s "$NVM_DIR/nvm.sh" ] && \. "$NVM_DIR/nvm.sh"
-            nvm --version
-        "#;
-
-        let output = Command::new("bash")
-            .arg("-c")
-            .arg(shell_command)
-            .output()
-            .map_err(|e| format!("Failed to check nvm version: {}", e))?;
-
-        if !output.status.success() {
-            return Err("Failed to get nvm version".to_string());
-        }
+    let requirement = node_requirement()?;
+    let node = find_installed_node(&requirement)?
+        .ok_or_else(|| format!("No installed Node.js satisfies {}", requirement))?;
+    let paths = node.paths()?;
+    Ok((
+        paths.node.to_string_lossy().to_string(),
+        paths.npx.to_string_lossy().to_string(),
+    ))
+}
 
-        return Ok(String::from_utf8_lossy(&output.stdout).trim().to_string());
-    }
+/// Forgets the result of a previous setup, e.g. after the preferred runtime
+/// provider changed.
+pub(crate) fn reset_environment() {
+    NODE_INSTALLED.store(false, Ordering::SeqCst);
+    ENVIRONMENT_SETUP_COMPLETED.store(false, Ordering::SeqCst);
 }
 
-fn install_node() -> Result<(), String> {
+fn ensure_node_environment(reporter: &SetupReporter) -> Result<String, String> {
     if is_test_mode() {
-        return Ok(());
+        return Ok("Node environment is ready".to_string());
     }
 
-    // Double-check node version to avoid race conditions
-    match check_node_version() {
-        Ok(version) if version == NODE_VERSION => {
-            info!(
-                "Node.js {} is already installed, skipping installation",
-                NODE_VERSION
-            );
-            NODE_INSTALLED.store(true, Ordering::Relaxed);
-            return Ok(());
-        }
-        _ => {}
+    // If we already confirmed node is installed, return early
+    if NODE_INSTALLED.load(Ordering::SeqCst) {
+        debug!("Node.js already confirmed as installed");
+        reporter.ready(None, None);
+        return Ok("Node environment is ready".to_string());
     }
 
-    info!("Installing Node.js {}", NODE_VERSION);
-
-    #[cfg(target_os = "macos")]
-    {
-        // Verify nvm is properly installed before using it
-        if !check_nvm_installed() {
-            return Err("nvm is required to install Node.js".to_string());
-        }
-
-        let shell_command = format!(
-            r#"
-            export NVM_DIR="$HOME/.nvm"
-            [ -s "$NVM_DIR/nvm.sh" ] && \. "$NVM_DIR/nvm.sh"
-            nvm install {} --no-progress
-        "#,
-            NODE_VERSION
-        );
-
-        let output = Command::new("bash")
-            .arg("-c")
-            .arg(shell_command)
-            .output()
-            .map_err(|e| format!("Failed to run node installation: {}", e))?;
-
-        if !output.status.success() {
-            return Err(format!(
-                "Node installation failed: {}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
-        }
+    reporter.step(SetupStep::Detecting, 0, "Looking for Node.js");
+    if let Err(e) = recover_interrupted_install() {
+        warn!("{}", e);
     }
-
-    #[cfg(target_os = "windows")]
-    {
-        // On Windows, we'll use nvm-windows if available, otherwise direct the user to install Node.js
-        if check_nvm_installed() {
-            let appdata = std::env::var("APPDATA").unwrap_or_default();
-            let nvm_home = std::env::var("NVM_HOME").unwrap_or_else(|_| format!("{}\\nvm", appdata));
-            let nvm_exe = format!("{}\\nvm.exe", nvm_home);
-            
-            let version_without_v = NODE_VERSION.trim_start_matches('v');
-            
-            let output = Command::new(&nvm_exe)
-                .args(&["install", version_without_v])
-                .output()
-                .map_err(|e| format!("Failed to run node installation: {}", e))?;
-                
-            if !output.status.success() {
-                return Err(format!(
-                    "Node installation failed: {}",
-                    String::from_utf8_lossy(&output.stderr)
-                ));
-            }
-            
-            // Use the installed version
-            let use_output = Command::new(&nvm_exe)
-                .args(&["use", version_without_v])
-                .output()
-                .map_err(|e| format!("Failed to use installed Node.js version: {}", e))?;
-                
-            if !use_output.status.success() {
-                return Err(format!(
-                    "Failed to use installed Node.js version: {}",
-                    String::from_utf8_lossy(&use_output.stderr)
-                ));
-            }
-        } else {
+    let requirement = node_requirement()?;
+    let node = if let Some(node) = find_installed_node(&requirement)? {
+        debug!(
+            "Using Node.js {} from {} (requires {})",
+            node.version,
+            node.provider.name(),
+            requirement
+        );
+        node
+    } else {
+        // Only install when nothing satisfies the requirement
+        let install_version = node_install_version();
+        let satisfies = semver::Version::parse(install_version.trim_start_matches('v'))
+            .is_ok_and(|version| requirement.matches(&version));
+        if !satisfies {
             return Err(format!(
-                "Node.js {} is required but not installed. Please install it from https://nodejs.org/",
-                NODE_VERSION
+                "Node.js {} does not satisfy {}; set a matching install version",
+                install_version, requirement
             ));
         }
-    }
 
-    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
-    {
-        // Verify nvm is properly installed before using it
-        if !check_nvm_installed() {
-            return Err("nvm is required to install Node.js".to_string());
+        let provider = installer_provider()?;
+        if is_install_cancelled() {
+            return Err(INSTALL_CANCELLED.to_string());
         }
-
-        let shell_command = format!(
-            r#"
-            export NVM_DIR="$HOME/.nvm"
-            [ -s "$NVM_DIR/nvm.sh" ] && \. "$NVM_DIR/nvm.sh"
-            nvm install {} --no-progress
-        "#,
-            NODE_VERSION
+        info!(
+            "No Node.js satisfying {} found. Installing {} with {}...",
+            requirement,
+            install_version,
+            provider.name()
         );
-
-        let output = Command::new("bash")
-            .arg("-c")
-            .arg(shell_command)
-            .output()
-            .map_err(|e| format!("Failed to run node installation: {}", e))?;
-
-        if !output.status.success() {
+        reporter.step(
+            SetupStep::InstallingNode,
+            INSTALL_START_PERCENT,
+            &format!(
+                "Installing Node.js {} with {}",
+                install_version,
+                provider.name()
+            ),
+        );
+        install_with_rollback(provider.as_ref(), &install_version, &|event| {
+            reporter.install_event(event)
+        })?;
+
+        reporter.step(
+            SetupStep::InstallingNode,
+            INSTALL_END_PERCENT,
+            "Checking the new Node.js install",
+        );
+        let Some(node) = find_installed_node(&requirement)? else {
             return Err(format!(
-                "Node installation failed: {}",
-                String::from_utf8_lossy(&output.stderr)
+                "Node.js {} was installed but could not be found",
+                install_version
             ));
-        }
-    }
+        };
+        info!("Node.js {} installed successfully", install_version);
+        node
+    };
+    NODE_INSTALLED.store(true, Ordering::SeqCst);
+    reporter.ready(Some(node.version), Some(node.provider.name().to_string()));
 
-    NODE_INSTALLED.store(true, Ordering::Relaxed);
-    info!("Node.js {} installed successfully", NODE_VERSION);
-    Ok(())
-}
+    // Mark environment setup as completed
+    ENVIRONMENT_SETUP_COMPLETED.store(true, Ordering::SeqCst);
 
-fn check_nvm_installed() -> bool {
-    if is_test_mode() {
-        return true;
-    }
+    Ok("Node environment is ready".to_string())
+}
 
-    // If we've already confirmed nvm is installed, return early
-    if NVM_INSTALLED.load(Ordering::Relaxed) {
-        debug!("NVM already confirmed as installed");
-        return true;
+// Failures caused by a cancellation get their own code
+fn setup_error(message: String) -> CommandError {
+    if is_install_cancelled() {
+        CommandError::new(ErrorCode::EnvironmentSetupCancelled, message)
+    } else {
+        CommandError::new(ErrorCode::EnvironmentSetupFailed, message)
     }
+}
 
-    #[cfg(target_os = "macos")]
-    {
-        // First check if .nvm directory exists
-        let nvm_dir = dirs::home_dir()
-            .map(|path| path.join(".nvm"))
-            .filter(|path| path.exists());
-
-        if nvm_dir.is_none() {
-            info!("NVM directory not found");
-            return false;
-        }
-
-        // Then check if we can run nvm to confirm it's properly installed
-        match check_nvm_version() {
-            Ok(version) => {
-                info!("NVM version {} is installed", version);
-                NVM_INSTALLED.store(true, Ordering::Relaxed);
-                true
-            }
-            Err(_) => {
-                info!("NVM directory exists but nvm command failed");
-                false
-            }
-        }
+// npm's cache directory, which holds the packages `npx` has downloaded
+fn npm_cache_dir() -> Option<std::path::PathBuf> {
+    if let Ok(cache_dir) = std::env::var("npm_config_cache") {
+        return Some(cache_dir.into());
     }
 
     #[cfg(target_os = "windows")]
     {
-        // Check for nvm-windows
-        let appdata = std::env::var("APPDATA").unwrap_or_default();
-        let nvm_home = std::env::var("NVM_HOME").unwrap_or_else(|_| format!("{}\\nvm", appdata));
-        
-        let nvm_exe = format!("{}\\nvm.exe", nvm_home);
-        
-        if !std::path::Path::new(&nvm_exe).exists() {
-            info!("NVM for Windows not found at {}", nvm_exe);
-            return false;
-        }
-        
-        // Check if we can run nvm to confirm it's properly installed
-        match check_nvm_version() {
-            Ok(version) => {
-                info!("NVM for Windows version {} is installed", version);
-                NVM_INSTALLED.store(true, Ordering::Relaxed);
-                true
-            }
-            Err(_) => {
-                info!("NVM for Windows exists but command failed");
-                false
-            }
-        }
+        dirs::data_local_dir().map(|dir| dir.join("npm-cache"))
     }
 
-    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    #[cfg(not(target_os = "windows"))]
     {
-        // First check if .nvm directory exists
-        let nvm_dir = dirs::home_dir()
-            .map(|path| path.join(".nvm"))
-            .filter(|path| path.exists());
-
-        if nvm_dir.is_none() {
-            info!("NVM directory not found");
-            return false;
-        }
-
-        // Then check if we can run nvm to confirm it's properly installed
-        match check_nvm_version() {
-            Ok(version) => {
-                info!("NVM version {} is installed", version);
-                NVM_INSTALLED.store(true, Ordering::Relaxed);
-                true
-            }
-            Err(_) => {
-                info!("NVM directory exists but nvm command failed");
-                false
-            }
-        }
+        dirs::home_dir().map(|dir| dir.join(".npm"))
     }
 }
 
-fn install_nvm() -> Result<(), String> {
-    if is_test_mode() {
-        return Ok(());
-    }
-
-    // Double-check nvm installation to avoid race conditions
-    if check_nvm_installed() {
-        info!("nvm is already installed, skipping installation");
-        return Ok(());
-    }
-
-    #[cfg(target_os = "macos")]
-    {
-        info!("Installing nvm...");
-
-        let shell_command = r#"
-            curl -o- https://raw.githubusercontent.com/nvm-sh/nvm/v0.40.1/install.sh | bash
-        "#;
-
-        let output = Command::new("bash")
-            .arg("-c")
-            .arg(shell_command)
-            .output()
-            .map_err(|e| format!("Failed to install nvm: {}", e))?;
-
-        if !output.status.success() {
-            return Err(format!(
-                "nvm installation failed: {}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
-        }
-    }
-
-    #[cfg(target_os = "windows")]
-    {
-        return Err("Automatic installation of NVM for Windows is not supported. Please install it manually from https://github.com/coreybutler/nvm-windows".to_string());
-    }
-
-    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
-    {
-        info!("Installing nvm...");
-
-        let shell_command = r#"
-            curl -o- https://raw.githubusercontent.com/nvm-sh/nvm/v0.40.1/install.sh | bash
-        "#;
-
-        let output = Command::new("bash")
-            .arg("-c")
-            .arg(shell_command)
-            .output()
-            .map_err(|e| format!("Failed to install nvm: {}", e))?;
-
-        if !output.status.success() {
-            return Err(format!(
-                "nvm installation failed: {}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
+/// Deletes the npx cache entries that contain `package`, so the next `npx -y`
+/// downloads it afresh. Returns how many entries were removed.
+pub fn clear_npx_cache(package: &str) -> Result<usize, String> {
+    let npx_dir = match npm_cache_dir() {
+        Some(cache_dir) => cache_dir.join("_npx"),
+        None => return Err("Could not determine npm cache directory".to_string()),
+    };
+    if !npx_dir.exists() {
+        debug!("No npx cache at {}", npx_dir.display());
+        return Ok(0);
+    }
+
+    let entries =
+        std::fs::read_dir(&npx_dir).map_err(|e| format!("Failed to read npx cache: {}", e))?;
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        // Each entry is a hash-named directory with its own node_modules
+        if path.join("node_modules").join(package).exists() {
+            std::fs::remove_dir_all(&path)
+                .map_err(|e| format!("Failed to delete {}: {}", path.display(), e))?;
+            info!("Removed {} from the npx cache", package);
+            removed += 1;
         }
     }
-
-    NVM_INSTALLED.store(true, Ordering::Relaxed);
-    info!("nvm installed successfully");
-    Ok(())
+    Ok(removed)
 }
 
-fn ensure_node_environment() -> Result<String, String> {
-    if is_test_mode() {
-        return Ok("Node environment is ready".to_string());
-    }
-
-    // First check if we have nvm installed, install if needed
-    if !check_nvm_installed() {
-        install_nvm()?;
-    }
-
-    // Check if we have the correct node version, install if needed
-    match check_node_version() {
-        Ok(version) => {
-            if version != NODE_VERSION {
-                info!(
-                    "Node.js {} found, but {} required. Installing...",
-                    version, NODE_VERSION
-                );
-                install_node()?;
-            } else {
-                debug!("Node.js {} is already installed", NODE_VERSION);
-                NODE_INSTALLED.store(true, Ordering::Relaxed);
-            }
+// Runs one setup with the setup lock held, reporting how it went
+fn run_setup(reporter: &SetupReporter) -> CommandResult<()> {
+    reporter.start();
+    match ensure_node_environment(reporter) {
+        Ok(_) => {
+            info!("Environment setup completed");
+            Ok(())
         }
-        Err(_) => {
-            info!("Node.js not found. Installing...");
-            install_node()?;
+        Err(e) if is_install_cancelled() => {
+            info!("Environment setup cancelled");
+            reporter.cancelled();
+            Err(setup_error(e))
+        }
+        Err(e) => {
+            error!("Failed to ensure node environment: {}", e);
+            let error = setup_error(e);
+            reporter.fail(error.clone());
+            Err(error)
         }
     }
-
-    // Mark environment setup as completed
-    ENVIRONMENT_SETUP_COMPLETED.store(true, Ordering::SeqCst);
-
-    Ok("Node environment is ready".to_string())
 }
 
-// New synchronous environment setup function for config.rs to use
-pub fn ensure_environment_sync() -> Result<String, String> {
+/// Sets up Node.js and waits for it to finish, reporting progress the same
+/// way `ensure_environment` does. For callers that need Node.js before they
+/// can go on, such as installing the MCP server.
+pub fn ensure_environment_sync(app_handle: &AppHandle) -> CommandResult<String> {
     if is_test_mode() {
         return Ok("Environment setup completed".to_string());
     }
@@ -656,7 +422,9 @@ pub fn ensure_environment_sync() -> Result<String, String> {
         Err(_) => {
             info!("Another environment setup is already in progress, waiting...");
             // Block until lock is available for synchronous operation
-            ENVIRONMENT_SETUP_LOCK.lock().unwrap()
+            ENVIRONMENT_SETUP_LOCK
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
         }
     };
 
@@ -664,16 +432,19 @@ pub fn ensure_environment_sync() -> Result<String, String> {
     if ENVIRONMENT_SETUP_COMPLETED.load(Ordering::SeqCst) {
         return Ok("Environment setup completed while waiting".to_string());
     }
+    reset_install_cancellation();
 
-    // Ensure node environment is ready
-    ensure_node_environment()?;
-
-    info!("Synchronous environment setup completed");
+    run_setup(&SetupReporter {
+        app_handle: app_handle.clone(),
+    })?;
     Ok("Environment setup completed".to_string())
 }
 
+/// Starts setting up Node.js in the background. Progress is emitted as
+/// `environment-progress` events and can be polled with
+/// `get_environment_status`.
 #[tauri::command]
-pub fn ensure_environment() -> Result<String, String> {
+pub fn ensure_environment(app_handle: AppHandle) -> CommandResult<String> {
     if is_test_mode() {
         return Ok("Environment setup started".to_string());
     }
@@ -683,29 +454,96 @@ pub fn ensure_environment() -> Result<String, String> {
         info!("Environment setup already in progress, skipping");
         return Ok("Environment setup already in progress".to_string());
     }
+    // Cleared here rather than in the thread so a cancel sent right after
+    // this returns isn't lost
+    reset_install_cancellation();
 
     // Use a thread-safe approach for environment setup
-    std::thread::spawn(|| {
+    std::thread::spawn(move || {
         // Use a mutex to prevent concurrent setup operations
         let _lock = match ENVIRONMENT_SETUP_LOCK.try_lock() {
             Ok(guard) => guard,
             Err(_) => {
-                info!("Another environment setup is already in progress");
-                ENVIRONMENT_SETUP_STARTED.store(false, Ordering::SeqCst);
-                return;
+                // A synchronous setup is running and reports its progress
+                // through the same status; take over once it is done
+                info!("Another environment setup is already in progress, waiting...");
+                ENVIRONMENT_SETUP_LOCK
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
             }
         };
+        if ENVIRONMENT_SETUP_COMPLETED.load(Ordering::SeqCst) {
+            debug!("Environment setup completed while waiting");
+            ENVIRONMENT_SETUP_STARTED.store(false, Ordering::SeqCst);
+            return;
+        }
 
         info!("Starting environment setup");
-
-        // Ensure node environment is ready
-        if let Err(e) = ensure_node_environment() {
-            error!("Failed to ensure node environment: {}", e);
+        let reporter = SetupReporter { app_handle };
+        if run_setup(&reporter).is_ok() {
+            // Entries written for an earlier runtime, or before entries
+            // used absolute paths, follow the one now in use
+            if let Err(e) = crate::app::repoint_waystation_mcp() {
+                warn!("Failed to update WayStation MCP entries: {}", e);
+            }
         }
 
-        info!("Environment setup completed");
         ENVIRONMENT_SETUP_STARTED.store(false, Ordering::SeqCst);
     });
 
     Ok("Environment setup started".to_string())
 }
+
+/// Status of the current or last environment setup.
+#[tauri::command]
+pub fn get_environment_status(
+    state: State<'_, EnvironmentStatusManager>,
+) -> CommandResult<EnvironmentStatus> {
+    Ok(state.0.lock().unwrap().clone())
+}
+
+// Whether an environment setup is running right now
+fn setup_running() -> bool {
+    // A setup that panicked poisons the lock but isn't running any more
+    ENVIRONMENT_SETUP_STARTED.load(Ordering::SeqCst)
+        || matches!(
+            ENVIRONMENT_SETUP_LOCK.try_lock(),
+            Err(TryLockError::WouldBlock)
+        )
+}
+
+/// Stops a running environment setup. Any Node.js install in progress is
+/// killed along with its child processes and the partly installed version
+/// removed. Returns whether there was a setup to cancel.
+#[tauri::command]
+pub fn cancel_environment_setup() -> CommandResult<bool> {
+    if !setup_running() {
+        return Ok(false);
+    }
+    cancel_install();
+    Ok(true)
+}
+
+/// Cancels a running setup when the app is closing. Returns whether exiting
+/// should wait for the install to roll back; `exit` is then called from
+/// another thread once it has, or after a timeout. Whatever is left is
+/// cleaned up on the next setup.
+pub fn cancel_setup_on_exit(exit: impl FnOnce() + Send + 'static) -> bool {
+    // Only once, so the exit we call ourselves goes through
+    if !setup_running() || EXIT_DEFERRED.swap(true, Ordering::SeqCst) {
+        return false;
+    }
+    cancel_install();
+    std::thread::spawn(move || {
+        let deadline = Instant::now() + EXIT_ROLLBACK_TIMEOUT;
+        while setup_running() {
+            if Instant::now() >= deadline {
+                warn!("Environment setup did not stop before exit");
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        exit();
+    });
+    true
+}
@@ -0,0 +1,104 @@
+// Errors returned by Tauri commands. They serialize as
+// `{ code, message, details? }` so the frontend can branch on `code` and
+// offer a recovery action instead of just showing the message.
+
+use serde::Serialize;
+use std::fmt;
+
+/// Stable, machine-readable error codes. Serialized in snake_case; existing
+/// values must not be renamed since the frontend matches on them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// Claude Desktop could not be found on this machine.
+    ClaudeNotInstalled,
+    /// Claude Desktop could not be stopped or relaunched.
+    ClaudeRestartFailed,
+    /// The operation isn't available on this OS or for this client.
+    UnsupportedPlatform,
+    /// The Claude config file could not be read or written.
+    ConfigIo,
+    /// The Claude config file exists but is not valid JSON.
+    ConfigCorrupt,
+    /// Another process holds the config file lock; worth retrying.
+    ConfigLocked,
+    /// The config file was edited elsewhere in a way that clashes with our change.
+    ConfigConflict,
+    /// No account is signed in, or its session can no longer be renewed.
+    NotAuthenticated,
+    /// The identity provider rejected the login or returned something invalid.
+    AuthFailed,
+    /// The identity provider settings are missing or invalid.
+    AuthConfig,
+    /// A server could not be reached.
+    Network,
+    /// Credentials could not be read from or written to secure storage.
+    CredentialStorage,
+    /// Node.js / nvm could not be set up.
+    EnvironmentSetupFailed,
+    /// Environment setup was cancelled before it finished.
+    EnvironmentSetupCancelled,
+    /// A local file or directory operation failed.
+    Io,
+    /// The request referenced something that doesn't exist.
+    NotFound,
+    /// The request would create something that already exists.
+    AlreadyExists,
+    /// The request's arguments failed validation.
+    InvalidInput,
+    /// Anything not covered above.
+    Internal,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandError {
+    pub code: ErrorCode,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<String>,
+}
+
+pub type CommandResult<T> = Result<T, CommandError>;
+
+impl CommandError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    pub fn with_details(mut self, details: impl Into<String>) -> Self {
+        self.details = Some(details.into());
+        self
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.details {
+            Some(details) => write!(f, "{}: {}", self.message, details),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+impl From<reqwest::Error> for CommandError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_decode() {
+            Self::new(ErrorCode::Internal, "Unexpected response from the server")
+                .with_details(e.to_string())
+        } else {
+            Self::new(ErrorCode::Network, "Could not reach the server").with_details(e.to_string())
+        }
+    }
+}
+
+impl From<std::io::Error> for CommandError {
+    fn from(e: std::io::Error) -> Self {
+        Self::new(ErrorCode::Io, e.to_string())
+    }
+}
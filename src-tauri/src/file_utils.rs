@@ -1,8 +1,126 @@
-use serde_json::{json, Value};
-use std::fs;
-use std::path::PathBuf;
+use fs2::FileExt;
+use log::{debug, warn};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
 
-pub fn ensure_config_file(config_path: &PathBuf) -> Result<(), String> {
+// Lock files held by this process. Whether flock conflicts between threads
+// of one process differs by platform, so our own threads are checked here
+// and fail the same way a competing process would.
+static LOCAL_LOCKS: Mutex<BTreeSet<PathBuf>> = Mutex::new(BTreeSet::new());
+
+/// Advisory lock on a sidecar `<file>.lock` next to a file we rewrite. The
+/// file itself can't carry the lock since it is replaced on every write.
+/// Released when dropped.
+pub struct FileLock {
+    file: File,
+    lock_path: PathBuf,
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+        LOCAL_LOCKS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&self.lock_path);
+    }
+}
+
+fn lock_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".lock");
+    path.with_file_name(file_name)
+}
+
+/// Takes an exclusive lock for `path`. Fails right away when another process
+/// that honours the same lock file holds it, rather than blocking the caller.
+/// The same goes for another thread of this process.
+pub fn lock_file(path: &Path) -> Result<FileLock, String> {
+    let lock_path = lock_path(path);
+    let mut local = LOCAL_LOCKS.lock().unwrap_or_else(PoisonError::into_inner);
+    if local.contains(&lock_path) {
+        return Err(format!(
+            "{} is locked by another operation in progress",
+            path.display()
+        ));
+    }
+
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .map_err(|e| format!("Failed to open lock file {}: {}", lock_path.display(), e))?;
+    file.try_lock_exclusive()
+        .map_err(|e| format!("{} is locked by another process: {}", path.display(), e))?;
+
+    local.insert(lock_path.clone());
+    Ok(FileLock { file, lock_path })
+}
+
+/// Replaces `path` with `contents` without ever exposing a partially written
+/// file: the data goes to a temp file in the same directory, is fsynced and
+/// then renamed over the original. The original's permissions are kept.
+///
+/// A symlink is followed and its target replaced, so a config that is
+/// symlinked into e.g. a dotfiles repo stays a symlink.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), String> {
+    let resolved = fs::canonicalize(path);
+    let path = resolved.as_deref().unwrap_or(path);
+    let dir = path
+        .parent()
+        .ok_or_else(|| format!("{} has no parent directory", path.display()))?;
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(path.file_name().unwrap_or_default());
+    temp_name.push(format!(".tmp-{}", std::process::id()));
+    let temp_path = dir.join(temp_name);
+
+    let result = write_and_rename(path, &temp_path, contents);
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+fn write_and_rename(path: &Path, temp_path: &Path, contents: &[u8]) -> Result<(), String> {
+    let mut temp_file = File::create(temp_path)
+        .map_err(|e| format!("Failed to create {}: {}", temp_path.display(), e))?;
+    temp_file
+        .write_all(contents)
+        .map_err(|e| format!("Failed to write {}: {}", temp_path.display(), e))?;
+    temp_file
+        .sync_all()
+        .map_err(|e| format!("Failed to sync {}: {}", temp_path.display(), e))?;
+    drop(temp_file);
+
+    if let Ok(metadata) = fs::metadata(path) {
+        fs::set_permissions(temp_path, metadata.permissions())
+            .map_err(|e| format!("Failed to copy permissions: {}", e))?;
+    }
+
+    fs::rename(temp_path, path)
+        .map_err(|e| format!("Failed to replace {}: {}", path.display(), e))?;
+    debug!("Atomically replaced {}", path.display());
+
+    // Persist the rename itself; not possible (or needed) on Windows
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        if let Err(e) = File::open(dir).and_then(|dir| dir.sync_all()) {
+            warn!("Failed to sync {}: {}", dir.display(), e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Creates an empty Claude Desktop config at `config_path` if there is none.
+/// The caller holds the lock.
+pub fn ensure_config_file(config_path: &Path) -> Result<(), String> {
     if !config_path.exists() {
         let initial_config = json!({
             "mcpServers": {}
@@ -16,12 +134,132 @@ pub fn ensure_config_file(config_path: &PathBuf) -> Result<(), String> {
                 .map_err(|e| format!("Failed to create config directory: {}", e))?;
         }
 
-        fs::write(config_path, config_str)
+        write_atomic(config_path, config_str.as_bytes())
             .map_err(|e| format!("Failed to write initial config file: {}", e))?;
     }
     Ok(())
 }
 
+/// Turns JSON with comments and trailing commas, as VS Code and Zed write
+/// their settings, into plain JSON. Plain JSON comes back unchanged.
+pub fn strip_jsonc(text: &str) -> String {
+    // Drop comments first so a comment can't hide a trailing comma
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    let mut in_string = false;
+    while let Some(c) = chars.next() {
+        if in_string {
+            plain.push(c);
+            match c {
+                '\\' => plain.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match (c, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                plain.push(c);
+            }
+            ('/', Some('/')) => {
+                // Keep the newline so error positions still line up
+                while chars.next_if(|&c| c != '\n').is_some() {}
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut prev = ' ';
+                for c in chars.by_ref() {
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    if c == '\n' {
+                        plain.push(c);
+                    }
+                    prev = c;
+                }
+                plain.push(' ');
+            }
+            _ => plain.push(c),
+        }
+    }
+
+    let mut json = String::with_capacity(plain.len());
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in plain.char_indices() {
+        if in_string {
+            in_string = escaped || c != '"';
+            escaped = !escaped && c == '\\';
+        } else if c == '"' {
+            in_string = true;
+        } else if c == ',' {
+            let next = plain[i + 1..].trim_start().chars().next();
+            if matches!(next, Some('}') | Some(']')) {
+                continue;
+            }
+        }
+        json.push(c);
+    }
+    json
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Three-way merges JSON documents: `ours` and `theirs` are two edits of
+/// `base`. Objects are merged key by key; any other value changed differently
+/// on both sides is a conflict, reported as the JSON pointer to it.
+pub fn merge_json(base: &Value, ours: &Value, theirs: &Value) -> Result<Value, String> {
+    merge_value(Some(base), Some(ours), Some(theirs), "")
+        .map(|merged| merged.unwrap_or_else(|| json!({})))
+}
+
+// `None` stands for a key that is absent on that side
+fn merge_value(
+    base: Option<&Value>,
+    ours: Option<&Value>,
+    theirs: Option<&Value>,
+    path: &str,
+) -> Result<Option<Value>, String> {
+    if ours == theirs || theirs == base {
+        return Ok(ours.cloned());
+    }
+    if ours == base {
+        return Ok(theirs.cloned());
+    }
+
+    match (ours, theirs) {
+        (Some(Value::Object(ours)), Some(Value::Object(theirs))) => {
+            let empty = Map::new();
+            let base = base.and_then(Value::as_object).unwrap_or(&empty);
+            let mut merged = Map::new();
+            let keys = base.keys().chain(ours.keys()).chain(theirs.keys());
+            for key in keys {
+                if merged.contains_key(key) {
+                    continue;
+                }
+                let key_path = format!("{}/{}", path, key.replace('~', "~0").replace('/', "~1"));
+                if let Some(value) =
+                    merge_value(base.get(key), ours.get(key), theirs.get(key), &key_path)?
+                {
+                    merged.insert(key.clone(), value);
+                }
+            }
+            Ok(Some(Value::Object(merged)))
+        }
+        _ => Err(if path.is_empty() {
+            "/".to_string()
+        } else {
+            path.to_string()
+        }),
+    }
+}
+
 pub fn ensure_mcp_servers(config_json: &mut Value) -> Result<(), String> {
     if !config_json.is_object() {
         *config_json = json!({
@@ -35,3 +273,137 @@ pub fn ensure_mcp_servers(config_json: &mut Value) -> Result<(), String> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(servers: Value) -> Value {
+        json!({ "globalShortcut": "", "mcpServers": servers })
+    }
+
+    #[test]
+    fn merge_keeps_edits_to_different_keys() {
+        let base = config(json!({ "a": { "command": "a" } }));
+        let ours = config(json!({ "a": { "command": "a" }, "ours": { "command": "x" } }));
+        let theirs = config(json!({ "a": { "command": "a" }, "theirs": { "command": "y" } }));
+        assert_eq!(
+            merge_json(&base, &ours, &theirs).unwrap(),
+            config(json!({
+                "a": { "command": "a" },
+                "ours": { "command": "x" },
+                "theirs": { "command": "y" }
+            }))
+        );
+    }
+
+    #[test]
+    fn merge_applies_removals_from_either_side() {
+        let base = config(json!({ "a": { "command": "a" }, "b": { "command": "b" } }));
+        let ours = config(json!({ "b": { "command": "b" } }));
+        let theirs = config(json!({ "a": { "command": "a" } }));
+        assert_eq!(
+            merge_json(&base, &ours, &theirs).unwrap(),
+            config(json!({}))
+        );
+    }
+
+    #[test]
+    fn merge_takes_their_edit_when_we_left_it_alone() {
+        let base = config(json!({ "a": { "args": ["1"] } }));
+        let ours = config(json!({ "a": { "args": ["1"] }, "b": {} }));
+        let theirs = config(json!({ "a": { "args": ["2"] } }));
+        assert_eq!(
+            merge_json(&base, &ours, &theirs).unwrap(),
+            config(json!({ "a": { "args": ["2"] }, "b": {} }))
+        );
+    }
+
+    #[test]
+    fn merge_reports_conflicting_edits() {
+        let base = config(json!({ "a/b": { "command": "old" } }));
+        let ours = config(json!({ "a/b": { "command": "ours" } }));
+        let theirs = config(json!({ "a/b": { "command": "theirs" } }));
+        assert_eq!(
+            merge_json(&base, &ours, &theirs).unwrap_err(),
+            "/mcpServers/a~1b/command"
+        );
+    }
+
+    #[test]
+    fn merge_accepts_identical_edits() {
+        let base = config(json!({}));
+        let ours = config(json!({ "a": { "command": "same" } }));
+        assert_eq!(merge_json(&base, &ours, &ours).unwrap(), ours);
+    }
+
+    #[test]
+    fn merge_treats_removal_against_edit_as_conflict() {
+        let base = config(json!({ "a": { "command": "old" } }));
+        let ours = config(json!({}));
+        let theirs = config(json!({ "a": { "command": "new" } }));
+        assert_eq!(
+            merge_json(&base, &ours, &theirs).unwrap_err(),
+            "/mcpServers/a"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_atomic_replaces_the_symlink_target() {
+        let dir =
+            std::env::temp_dir().join(format!("waystation-file-utils-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let target = dir.join("target.json");
+        let link = dir.join("link.json");
+        fs::write(&target, "old").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        write_atomic(&link, b"new").unwrap();
+        let is_symlink = fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink();
+        let contents = fs::read_to_string(&target).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+        assert!(is_symlink);
+        assert_eq!(contents, "new");
+    }
+
+    #[test]
+    fn lock_file_fails_fast_within_the_process() {
+        let dir = std::env::temp_dir().join(format!("waystation-lock-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.json");
+
+        let lock = lock_file(&path).unwrap();
+        let second = std::thread::scope(|scope| scope.spawn(|| lock_file(&path).err()).join());
+        let other_file = lock_file(&dir.join("other.json")).is_ok();
+        drop(lock);
+        let relocked = lock_file(&path).is_ok();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert!(second.unwrap().is_some_and(|e| e.contains("in progress")));
+        assert!(other_file);
+        assert!(relocked);
+    }
+
+    #[test]
+    fn strip_jsonc_removes_comments_and_trailing_commas() {
+        let jsonc = r#"{
+            // line comment
+            "url": "http://example.com/*not a comment*/", /* block */
+            "list": [1, 2,],
+            "quote": "a \"// b\"",
+        }"#;
+        let value: Value = serde_json::from_str(&strip_jsonc(jsonc)).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "url": "http://example.com/*not a comment*/",
+                "list": [1, 2],
+                "quote": "a \"// b\""
+            })
+        );
+    }
+}
@@ -0,0 +1,136 @@
+// ID token validation against the identity provider's published keys
+
+use crate::auth_config::{get_auth_config, http_client};
+use crate::UserInfo;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use log::debug;
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// How long a fetched key set is trusted before it is fetched again
+const JWKS_TTL: Duration = Duration::from_secs(60 * 60);
+// Allowed clock skew for exp/iat checks
+const LEEWAY_SECS: u64 = 60;
+
+struct CachedJwks {
+    keys: JwkSet,
+    fetched_at: Instant,
+}
+
+static JWKS_CACHE: Lazy<tokio::sync::Mutex<Option<CachedJwks>>> =
+    Lazy::new(|| tokio::sync::Mutex::new(None));
+
+/// Claims we read from a verified ID token.
+#[derive(Debug, Deserialize)]
+pub struct IdTokenClaims {
+    pub sub: String,
+    pub iat: u64,
+    pub nonce: Option<String>,
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub picture: Option<String>,
+}
+
+impl IdTokenClaims {
+    pub fn user_info(&self) -> UserInfo {
+        UserInfo {
+            sub: self.sub.clone(),
+            name: self.name.clone(),
+            email: self.email.clone(),
+            picture: self.picture.clone(),
+        }
+    }
+}
+
+async fn fetch_jwks(jwks_uri: &str) -> Result<JwkSet, String> {
+    debug!("Fetching JWKS from {}", jwks_uri);
+    let response = http_client()
+        .get(jwks_uri)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to fetch JWKS: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "Failed to fetch JWKS: status {}",
+            response.status()
+        ));
+    }
+    response
+        .json()
+        .await
+        .map_err(|e| format!("Invalid JWKS: {}", e))
+}
+
+// Looks up the decoding key for `kid`, refetching the key set when it is stale
+// or doesn't know the key yet (the provider may have rotated its keys)
+async fn decoding_key(jwks_uri: &str, kid: &str) -> Result<DecodingKey, String> {
+    let mut cache = JWKS_CACHE.lock().await;
+
+    let cached_key = cache
+        .as_ref()
+        .filter(|cached| cached.fetched_at.elapsed() < JWKS_TTL)
+        .and_then(|cached| cached.keys.find(kid).cloned());
+
+    let jwk = match cached_key {
+        Some(jwk) => jwk,
+        None => {
+            let keys = fetch_jwks(jwks_uri).await?;
+            let jwk = keys.find(kid).cloned();
+            *cache = Some(CachedJwks {
+                keys,
+                fetched_at: Instant::now(),
+            });
+            jwk.ok_or_else(|| format!("No signing key found for kid {}", kid))?
+        }
+    };
+
+    DecodingKey::from_jwk(&jwk).map_err(|e| format!("Invalid signing key: {}", e))
+}
+
+/// Verifies the signature (RS256 or ES256) and the `iss`, `aud`, `exp`, `iat`
+/// and, when given, `nonce` claims of an ID token.
+pub async fn validate_id_token(
+    id_token: &str,
+    expected_nonce: Option<&str>,
+) -> Result<IdTokenClaims, String> {
+    let config = get_auth_config().await?;
+
+    let header = decode_header(id_token).map_err(|e| format!("Invalid ID token: {}", e))?;
+    if !matches!(header.alg, Algorithm::RS256 | Algorithm::ES256) {
+        return Err(format!("Unsupported ID token algorithm {:?}", header.alg));
+    }
+    let kid = header.kid.ok_or("ID token has no key ID")?;
+    let jwks_uri = config.jwks_uri.as_deref().ok_or(
+        "The identity provider's signing keys are unknown until its configuration can be discovered",
+    )?;
+    let key = decoding_key(jwks_uri, &kid).await?;
+
+    let mut validation = Validation::new(header.alg);
+    validation.set_issuer(&[&config.issuer]);
+    validation.set_audience(&[&config.client_id]);
+    validation.set_required_spec_claims(&["exp", "iat", "iss", "aud", "sub"]);
+    validation.leeway = LEEWAY_SECS;
+
+    let claims = decode::<IdTokenClaims>(id_token, &key, &validation)
+        .map_err(|e| format!("ID token validation failed: {}", e))?
+        .claims;
+
+    // jsonwebtoken checks exp but not iat
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    if claims.iat > now + LEEWAY_SECS {
+        return Err("ID token validation failed: issued in the future".to_string());
+    }
+
+    if let Some(expected_nonce) = expected_nonce {
+        if claims.nonce.as_deref() != Some(expected_nonce) {
+            return Err("ID token validation failed: nonce mismatch".to_string());
+        }
+    }
+
+    Ok(claims)
+}
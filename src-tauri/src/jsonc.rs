@@ -0,0 +1,508 @@
+// In-place edits of JSON with comments, as VS Code and Zed write their
+// settings. Only the member being changed is rewritten, so the user's
+// comments, trailing commas and formatting everywhere else stay as they were.
+
+use serde::Serialize;
+use serde_json::ser::{PrettyFormatter, Serializer};
+use serde_json::Value;
+
+// Byte ranges of a parsed value; objects keep their members so edits can find
+// their way down
+struct Node {
+    start: usize,
+    end: usize,
+    members: Option<Vec<Member>>,
+}
+
+struct Member {
+    key: String,
+    key_start: usize,
+    value: Node,
+    // Where the comma after the member is, if there is one
+    comma: Option<usize>,
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> String {
+        let line = self.text[..self.pos.min(self.text.len())]
+            .lines()
+            .count()
+            .max(1);
+        format!("{} on line {}", message, line)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.pos).copied()
+    }
+
+    // Skips whitespace and comments
+    fn skip(&mut self) -> Result<(), String> {
+        loop {
+            let rest = &self.text[self.pos..];
+            if rest.starts_with("//") {
+                self.pos += rest.find('\n').unwrap_or(rest.len());
+            } else if let Some(comment) = rest.strip_prefix("/*") {
+                let end = comment
+                    .find("*/")
+                    .ok_or_else(|| self.error("Unterminated comment"))?;
+                self.pos += end + 4;
+            } else if let Some(c) = rest.chars().next().filter(|c| c.is_whitespace()) {
+                self.pos += c.len_utf8();
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<(), String> {
+        if self.peek() != Some(expected) {
+            return Err(self.error(&format!("Expected '{}'", expected as char)));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let start = self.pos;
+        self.expect(b'"')?;
+        loop {
+            match self.peek() {
+                Some(b'\\') => self.pos += 2,
+                Some(b'"') => break,
+                Some(_) => self.pos += 1,
+                None => return Err(self.error("Unterminated string")),
+            }
+        }
+        self.pos += 1;
+        let literal = self
+            .text
+            .get(start..self.pos)
+            .ok_or_else(|| self.error("Unterminated string"))?;
+        serde_json::from_str(literal).map_err(|_| self.error("Invalid string"))
+    }
+
+    fn value(&mut self) -> Result<Node, String> {
+        let start = self.pos;
+        let members = match self.peek() {
+            Some(b'{') => Some(self.object()?),
+            Some(b'[') => {
+                self.array()?;
+                None
+            }
+            Some(b'"') => {
+                self.string()?;
+                None
+            }
+            Some(_) => {
+                // Numbers, true, false and null
+                let rest = &self.text[self.pos..];
+                let length = rest
+                    .find(|c: char| c.is_whitespace() || ",]}/".contains(c))
+                    .unwrap_or(rest.len());
+                if length == 0 {
+                    return Err(self.error("Expected a value"));
+                }
+                serde_json::from_str::<Value>(&rest[..length])
+                    .map_err(|_| self.error("Invalid value"))?;
+                self.pos += length;
+                None
+            }
+            None => return Err(self.error("Expected a value")),
+        };
+        Ok(Node {
+            start,
+            end: self.pos,
+            members,
+        })
+    }
+
+    fn array(&mut self) -> Result<(), String> {
+        self.expect(b'[')?;
+        loop {
+            self.skip()?;
+            if self.peek() == Some(b']') {
+                self.pos += 1;
+                return Ok(());
+            }
+            self.value()?;
+            self.skip()?;
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {}
+                _ => return Err(self.error("Expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Vec<Member>, String> {
+        self.expect(b'{')?;
+        let mut members = Vec::new();
+        loop {
+            self.skip()?;
+            if self.peek() == Some(b'}') {
+                self.pos += 1;
+                return Ok(members);
+            }
+            let key_start = self.pos;
+            let key = self.string()?;
+            self.skip()?;
+            self.expect(b':')?;
+            self.skip()?;
+            let value = self.value()?;
+            self.skip()?;
+            let comma = match self.peek() {
+                Some(b',') => {
+                    self.pos += 1;
+                    Some(self.pos - 1)
+                }
+                Some(b'}') => None,
+                _ => return Err(self.error("Expected ',' or '}'")),
+            };
+            members.push(Member {
+                key,
+                key_start,
+                value,
+                comma,
+            });
+        }
+    }
+}
+
+fn parse(text: &str) -> Result<Node, String> {
+    let mut parser = Parser { text, pos: 0 };
+    parser.skip()?;
+    let root = parser.value()?;
+    parser.skip()?;
+    if parser.pos != text.len() {
+        return Err(parser.error("Unexpected text after the settings"));
+    }
+    if root.members.is_none() {
+        return Err("The settings are not a JSON object".to_string());
+    }
+    Ok(root)
+}
+
+// Leading whitespace of the line `pos` is on
+fn line_indent(text: &str, pos: usize) -> &str {
+    let line_start = text[..pos].rfind('\n').map_or(0, |i| i + 1);
+    let line = &text[line_start..];
+    &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+}
+
+// One level of indentation, as the file already uses it
+fn indent_unit(text: &str, root: &Node) -> String {
+    root.members
+        .as_ref()
+        .and_then(|members| members.first())
+        .map(|member| line_indent(text, member.key_start))
+        .filter(|indent| !indent.is_empty())
+        .unwrap_or("  ")
+        .to_string()
+}
+
+// `value` pretty-printed to sit at `indent`
+fn format_value(value: &Value, unit: &str, indent: &str) -> String {
+    let mut out = Vec::new();
+    let mut serializer =
+        Serializer::with_formatter(&mut out, PrettyFormatter::with_indent(unit.as_bytes()));
+    value
+        .serialize(&mut serializer)
+        .expect("serializing a JSON value can't fail");
+    String::from_utf8(out)
+        .expect("serde_json writes UTF-8")
+        .replace('\n', &format!("\n{}", indent))
+}
+
+// Follows `path` from the root. Returns the deepest object reached and the
+// part of the path that doesn't exist yet.
+fn find<'n, 'p>(root: &'n Node, path: &'p [&'p str]) -> Result<(&'n Node, &'p [&'p str]), String> {
+    let mut node = root;
+    for (i, key) in path.iter().enumerate() {
+        let members = node
+            .members
+            .as_ref()
+            .expect("only objects are descended into");
+        match members.iter().find(|member| member.key == *key) {
+            Some(member) if member.value.members.is_some() => node = &member.value,
+            Some(_) => return Err(format!("\"{}\" is not an object", path[..=i].join("."))),
+            None => return Ok((node, &path[i..])),
+        }
+    }
+    Ok((node, &[]))
+}
+
+/// Sets `name` in the object at `path` to `value`, creating the object and
+/// its parents when they are missing, and returns the edited text. An empty
+/// text is taken to be an empty object.
+pub fn set_member(text: &str, path: &[&str], name: &str, value: &Value) -> Result<String, String> {
+    if text.trim().is_empty() {
+        return set_member("{}\n", path, name, value);
+    }
+    let root = parse(text)?;
+    let unit = indent_unit(text, &root);
+    let (object, missing) = find(&root, path)?;
+    let members = object.members.as_ref().expect("find returns objects");
+
+    // Nest the value in whatever part of the path is missing
+    let (name, value) = match missing.split_first() {
+        Some((first, rest)) => {
+            let nested = rest.iter().rev().fold(
+                serde_json::json!({ name: value }),
+                |inner, key| serde_json::json!({ *key: inner }),
+            );
+            (*first, nested)
+        }
+        None => (name, value.clone()),
+    };
+
+    let mut edited = text.to_string();
+    if let Some(member) = members.iter().find(|member| member.key == name) {
+        let indent = line_indent(text, member.key_start);
+        edited.replace_range(
+            member.value.start..member.value.end,
+            &format_value(&value, &unit, indent),
+        );
+        return Ok(edited);
+    }
+
+    let key = serde_json::to_string(name).expect("serializing a string can't fail");
+    let close = object.end - 1;
+    // A closing brace on a line of its own gets the new member on the line
+    // before it; otherwise the object is on one line and stays that way
+    let own_line = text[..close]
+        .rfind('\n')
+        .is_some_and(|newline| text[newline + 1..close].trim().is_empty());
+    match members.last() {
+        Some(last) if own_line => {
+            let indent = line_indent(text, last.key_start);
+            let line_start = text[..close].rfind('\n').map_or(0, |i| i + 1);
+            edited.insert_str(
+                line_start,
+                &format!(
+                    "{}{}: {}\n",
+                    indent,
+                    key,
+                    format_value(&value, &unit, indent)
+                ),
+            );
+            // Trailing commas are left alone; a missing one is added
+            if last.comma.is_none() {
+                edited.insert(last.value.end, ',');
+            }
+        }
+        Some(last) => {
+            let member = format!(", {}: {}", key, format_value(&value, &unit, ""));
+            let at = last.comma.unwrap_or(last.value.end);
+            edited.insert_str(at, &member);
+        }
+        None => {
+            let outer = line_indent(text, object.start);
+            let indent = format!("{}{}", outer, unit);
+            let body = &text[object.start + 1..close];
+            edited.replace_range(
+                object.start + 1..close,
+                &format!(
+                    "{}\n{}{}: {}\n{}",
+                    body.trim_end(),
+                    indent,
+                    key,
+                    format_value(&value, &unit, &indent),
+                    outer
+                ),
+            );
+        }
+    }
+    Ok(edited)
+}
+
+/// Removes `name` from the object at `path`, returning the edited text, or
+/// `None` when there was nothing to remove.
+pub fn remove_member(text: &str, path: &[&str], name: &str) -> Result<Option<String>, String> {
+    if text.trim().is_empty() {
+        return Ok(None);
+    }
+    let root = parse(text)?;
+    let (object, missing) = find(&root, path)?;
+    if !missing.is_empty() {
+        return Ok(None);
+    }
+    let members = object.members.as_ref().expect("find returns objects");
+    let Some(index) = members.iter().position(|member| member.key == name) else {
+        return Ok(None);
+    };
+    let member = &members[index];
+
+    // From the start of the member's line when nothing else is on it...
+    let line_start = text[..member.key_start].rfind('\n').map_or(0, |i| i + 1);
+    let mut start = if text[line_start..member.key_start].trim().is_empty() {
+        line_start
+    } else {
+        member.key_start
+    };
+    // ...through its comma and the rest of the line when that is blank or
+    // just a comment on it
+    let mut end = member.comma.map_or(member.value.end, |comma| comma + 1);
+    let rest_of_line = text[end..].find('\n').map_or(text.len(), |i| end + i + 1);
+    let tail = text[end..rest_of_line].trim();
+    if tail.is_empty() || (start == line_start && tail.starts_with("//")) {
+        end = rest_of_line;
+    } else if start == member.key_start {
+        // Keep a single space between what's left on the line
+        end += text[end..].len() - text[end..].trim_start_matches([' ', '\t']).len();
+    }
+    // The last member takes the comma before it along, so strict JSON stays
+    // strict
+    if member.comma.is_none() {
+        if let Some(previous) = index.checked_sub(1).map(|i| &members[i]) {
+            let comma = previous.comma.expect("members before the last have commas");
+            if start == line_start {
+                let mut edited = text.to_string();
+                edited.replace_range(start..end, "");
+                edited.remove(comma);
+                return Ok(Some(edited));
+            }
+            start = comma;
+        }
+    }
+    let mut edited = text.to_string();
+    edited.replace_range(start..end, "");
+    Ok(Some(edited))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn parsed(text: &str) -> Value {
+        serde_json::from_str(&crate::file_utils::strip_jsonc(text)).unwrap()
+    }
+
+    const ZED: &str = r#"// Zed settings
+//
+// For information on how to configure Zed, see the Zed
+// documentation: https://zed.dev/docs/configuring-zed
+{
+  "theme": "One Dark", // picked in the theme selector
+  /* fonts */
+  "buffer_font_size": 15,
+}
+"#;
+
+    #[test]
+    fn adds_member_and_keeps_comments() {
+        let edited =
+            set_member(ZED, &["context_servers"], "waystation", &json!({ "a": 1 })).unwrap();
+        assert_eq!(
+            edited,
+            r#"// Zed settings
+//
+// For information on how to configure Zed, see the Zed
+// documentation: https://zed.dev/docs/configuring-zed
+{
+  "theme": "One Dark", // picked in the theme selector
+  /* fonts */
+  "buffer_font_size": 15,
+  "context_servers": {
+    "waystation": {
+      "a": 1
+    }
+  }
+}
+"#
+        );
+        assert_eq!(parsed(&edited)["context_servers"]["waystation"]["a"], 1);
+    }
+
+    #[test]
+    fn replaces_existing_member() {
+        let text = "{\n    \"mcp\": {\n        \"servers\": {\n            \"waystation\": { \"old\": true } // ours\n        }\n    }\n}\n";
+        let edited = set_member(
+            text,
+            &["mcp", "servers"],
+            "waystation",
+            &json!({ "new": 1 }),
+        )
+        .unwrap();
+        assert_eq!(
+            edited,
+            "{\n    \"mcp\": {\n        \"servers\": {\n            \"waystation\": {\n                \"new\": 1\n            } // ours\n        }\n    }\n}\n"
+        );
+    }
+
+    #[test]
+    fn adds_comma_to_strict_json() {
+        let text = "{\n  \"mcpServers\": {\n    \"other\": {}\n  }\n}\n";
+        let edited = set_member(text, &["mcpServers"], "waystation", &json!({})).unwrap();
+        assert_eq!(
+            edited,
+            "{\n  \"mcpServers\": {\n    \"other\": {},\n    \"waystation\": {}\n  }\n}\n"
+        );
+        // Removing it again restores the original
+        let removed = remove_member(&edited, &["mcpServers"], "waystation")
+            .unwrap()
+            .unwrap();
+        assert_eq!(removed, text);
+    }
+
+    #[test]
+    fn fills_empty_objects_and_files() {
+        assert_eq!(
+            set_member("", &["mcpServers"], "w", &json!(1)).unwrap(),
+            "{\n  \"mcpServers\": {\n    \"w\": 1\n  }\n}\n"
+        );
+        assert_eq!(
+            set_member("{\"mcpServers\": {}}", &["mcpServers"], "w", &json!(1)).unwrap(),
+            "{\"mcpServers\": {\n  \"w\": 1\n}}"
+        );
+        assert_eq!(
+            set_member("{\"a\": 1}", &[], "w", &json!(2)).unwrap(),
+            "{\"a\": 1, \"w\": 2}"
+        );
+    }
+
+    #[test]
+    fn removes_member_with_its_line() {
+        let text = "{\n  // servers\n  \"s\": {\n    \"waystation\": {\n      \"command\": \"node\"\n    }, // ours\n    \"other\": 1,\n  }\n}\n";
+        let edited = remove_member(text, &["s"], "waystation").unwrap().unwrap();
+        assert_eq!(
+            edited,
+            "{\n  // servers\n  \"s\": {\n    \"other\": 1,\n  }\n}\n"
+        );
+        let edited = remove_member(&edited, &["s"], "other").unwrap().unwrap();
+        assert_eq!(edited, "{\n  // servers\n  \"s\": {\n  }\n}\n");
+        assert_eq!(remove_member(&edited, &["s"], "other").unwrap(), None);
+        assert_eq!(remove_member(&edited, &["t"], "other").unwrap(), None);
+    }
+
+    #[test]
+    fn removes_member_on_a_shared_line() {
+        assert_eq!(
+            remove_member("{\"a\": 1, \"b\": 2}", &[], "b")
+                .unwrap()
+                .unwrap(),
+            "{\"a\": 1}"
+        );
+        assert_eq!(
+            remove_member("{\"a\": 1, \"b\": 2}", &[], "a")
+                .unwrap()
+                .unwrap(),
+            "{\"b\": 2}"
+        );
+    }
+
+    #[test]
+    fn refuses_what_it_cannot_edit() {
+        assert!(set_member("{\"s\": []}", &["s"], "w", &json!(1)).is_err());
+        assert!(set_member("[]", &[], "w", &json!(1)).is_err());
+        assert!(set_member("{\"a\": 1", &[], "w", &json!(1)).is_err());
+        assert!(set_member("{\"a\": 1} x", &[], "w", &json!(1)).is_err());
+        assert!(set_member("{/* open", &[], "w", &json!(1)).is_err());
+        assert!(remove_member("{\"a\" 1}", &[], "a").is_err());
+    }
+}
@@ -1,32 +1,57 @@
+pub mod accounts;
 pub mod app;
+pub mod auth_config;
+#[cfg(target_os = "linux")]
+pub mod claude_linux;
+pub mod claude_process;
+pub mod config_backup;
+pub mod credentials;
+pub mod device_flow;
 pub mod environment;
+pub mod error;
 pub mod file_utils;
+pub mod id_token;
+pub mod jsonc;
+pub mod loopback;
+pub mod mcp_clients;
+pub mod mcp_servers;
+pub mod node_install;
+pub mod node_runtime;
+pub mod openpgp;
+pub mod revocation;
+pub mod token_refresh;
 
 use base64::{engine::general_purpose, Engine as _};
+use log::{debug, error, warn};
 use rand::{distributions::Alphanumeric, Rng};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Mutex;
-use tauri::{AppHandle, Manager, State};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, State};
 use url::Url;
 
+use auth_config::{get_auth_config, http_client};
+use credentials::{
+    account_id, migrate_legacy_store, open_credential_store, CredentialStore,
+    CredentialStoreManager,
+};
+use error::{CommandError, CommandResult, ErrorCode};
+use id_token::validate_id_token;
+use loopback::LoopbackListener;
+use revocation::revoke_token;
 use tauri_plugin_opener::OpenerExt;
 
-// Constants for OAuth configuration
-const AUTH_URL: &str = "https://clerk.waystation.ai/oauth/authorize";
-const TOKEN_URL: &str = "https://clerk.waystation.ai/oauth/token";
-const USER_INFO_URL: &str = "https://clerk.waystation.ai/oauth/userinfo";
-const CLIENT_ID: &str = "5xEs1bi3TY8JNVHx";
-const REDIRECT_URI: &str = "waystation://oauth/callback";
-const STORE_PATH: &str = ".auth.dat";
-
 // Structs for OAuth data
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AuthState {
     pub code_verifier: String,
     pub state: String,
+    pub nonce: String,
+    pub redirect_uri: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -55,8 +80,85 @@ pub struct AuthData {
     pub user_info: Option<UserInfo>,
 }
 
-// State management
-struct AuthStateManager(Mutex<Option<AuthState>>);
+// Outcome of each logout step; a failed step doesn't stop the others
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct LogoutResult {
+    pub refresh_token_revoked: bool,
+    pub access_token_revoked: bool,
+    pub credentials_deleted: bool,
+    // The MCP token file no longer holds the signed-out account's token
+    pub way_key_deleted: bool,
+    // The account that became active in place of the signed-out one
+    pub next_account: Option<AuthData>,
+    pub errors: Vec<String>,
+}
+
+// How long a login attempt stays redeemable after `login` started it
+const LOGIN_ATTEMPT_TTL: Duration = Duration::from_secs(10 * 60);
+
+struct PendingLogin {
+    auth_state: AuthState,
+    created_at: Instant,
+}
+
+// State management: in-flight login attempts keyed by their `state` value
+#[derive(Default)]
+struct AuthStateManager(Mutex<HashMap<String, PendingLogin>>);
+
+impl AuthStateManager {
+    fn insert(&self, auth_state: AuthState) {
+        let mut pending = self.0.lock().unwrap();
+        pending.retain(|_, login| login.created_at.elapsed() < LOGIN_ATTEMPT_TTL);
+        pending.insert(
+            auth_state.state.clone(),
+            PendingLogin {
+                auth_state,
+                created_at: Instant::now(),
+            },
+        );
+    }
+
+    // Removes and returns the attempt for `state`, so each redirect can only
+    // be redeemed once
+    fn take(&self, state: &str) -> CommandResult<AuthState> {
+        let mut pending = self.0.lock().unwrap();
+        pending.retain(|_, login| login.created_at.elapsed() < LOGIN_ATTEMPT_TTL);
+        pending
+            .remove(state)
+            .map(|login| login.auth_state)
+            .ok_or_else(|| {
+                CommandError::new(
+                    ErrorCode::AuthFailed,
+                    "Unknown, expired or already used login state, possible CSRF attack",
+                )
+            })
+    }
+
+    fn is_pending(&self, state: &str) -> bool {
+        self.0
+            .lock()
+            .unwrap()
+            .get(state)
+            .is_some_and(|login| login.created_at.elapsed() < LOGIN_ATTEMPT_TTL)
+    }
+
+    // Cancels one attempt, or all of them when `state` is None
+    fn cancel(&self, state: Option<&str>) -> usize {
+        let mut pending = self.0.lock().unwrap();
+        match state {
+            Some(state) => pending.remove(state).map_or(0, |_| 1),
+            None => {
+                let count = pending.len();
+                pending.clear();
+                count
+            }
+        }
+    }
+}
+
+// Whether the `waystation://` scheme is registered, i.e. whether the OAuth
+// redirect can come back as a deep link
+struct DeepLinkStatus(AtomicBool);
 
 // Helper functions for PKCE
 fn generate_code_verifier() -> String {
@@ -86,105 +188,249 @@ fn generate_state() -> String {
 #[tauri::command]
 async fn login(
     state: State<'_, AuthStateManager>,
+    deep_link_status: State<'_, DeepLinkStatus>,
     app_handle: AppHandle,
-) -> Result<String, String> {
+    loopback: Option<bool>,
+) -> CommandResult<String> {
+    let config = get_auth_config()
+        .await
+        .map_err(|e| CommandError::new(ErrorCode::AuthConfig, e))?;
     let code_verifier = generate_code_verifier();
     let code_challenge = generate_code_challenge(&code_verifier);
     let state_value = generate_state();
+    let nonce = generate_state();
+
+    // Fall back to a loopback redirect when deep links can't reach us
+    let use_loopback = loopback.unwrap_or(false)
+        || std::env::var("WAYSTATION_OAUTH_LOOPBACK").is_ok_and(|v| v == "1")
+        || !deep_link_status.0.load(Ordering::SeqCst);
+
+    let listener = if use_loopback {
+        Some(
+            LoopbackListener::bind()
+                .await
+                .map_err(|e| CommandError::new(ErrorCode::Io, e))?,
+        )
+    } else {
+        None
+    };
+    let redirect_uri = listener
+        .as_ref()
+        .map(|l| l.redirect_uri())
+        .unwrap_or_else(|| config.redirect_uri.clone());
 
     // Store PKCE and state values
     let auth_state = AuthState {
         code_verifier,
         state: state_value.clone(),
+        nonce: nonce.clone(),
+        redirect_uri: redirect_uri.clone(),
     };
-    *state.0.lock().unwrap() = Some(auth_state);
+    state.insert(auth_state);
 
     // Build authorization URL
-    let mut url = Url::parse(AUTH_URL).map_err(|e| e.to_string())?;
+    let mut url = Url::parse(&config.authorization_endpoint).map_err(|e| {
+        CommandError::new(ErrorCode::AuthConfig, "Invalid authorization endpoint")
+            .with_details(e.to_string())
+    })?;
     url.query_pairs_mut()
-        .append_pair("client_id", CLIENT_ID)
-        .append_pair("redirect_uri", REDIRECT_URI)
+        .append_pair("client_id", &config.client_id)
+        .append_pair("redirect_uri", &redirect_uri)
         .append_pair("response_type", "code")
-        .append_pair("scope", "profile email")
+        .append_pair("scope", "openid profile email")
         .append_pair("code_challenge", &code_challenge)
         .append_pair("code_challenge_method", "S256")
-        .append_pair("state", &state_value);
+        .append_pair("state", &state_value)
+        .append_pair("nonce", &nonce);
 
     // Open the URL in the default browser
     let _ = app_handle.opener().open_path(url.as_str(), None::<&str>);
 
+    // With a loopback redirect nothing goes through the frontend's deep link
+    // handler, so finish the login here and report back through events
+    if let Some(listener) = listener {
+        let app_handle = app_handle.clone();
+        tauri::async_runtime::spawn(async move {
+            // Stop listening once the attempt is cancelled or expires
+            let cancelled = async {
+                let state = app_handle.state::<AuthStateManager>();
+                while state.is_pending(&state_value) {
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+            };
+            let callback = tokio::select! {
+                callback = listener.wait_for_callback(&state_value) => callback,
+                _ = cancelled => {
+                    debug!("Loopback login cancelled");
+                    return;
+                }
+            };
+
+            let result = match callback {
+                Ok(callback) => {
+                    let state = app_handle.state::<AuthStateManager>();
+                    let credentials = app_handle.state::<CredentialStoreManager>();
+                    let result =
+                        complete_login(&callback.url, state.inner(), credentials.0.as_ref()).await;
+                    // Only now tell the browser whether signing in worked
+                    let page = match &result {
+                        Ok(_) => Ok(()),
+                        Err(e) => Err(e.to_string()),
+                    };
+                    callback.respond(page).await;
+                    result
+                }
+                Err(e) => Err(CommandError::new(ErrorCode::AuthFailed, e)),
+            };
+
+            match result {
+                Ok(auth_data) => {
+                    let _ = app_handle.emit("auth-success", &auth_data);
+                }
+                Err(e) => {
+                    error!("Loopback login failed: {}", e);
+                    let _ = app_handle.emit("auth-error", e);
+                }
+            }
+        });
+    }
+
     Ok("Authorization URL opened in browser".to_string())
 }
 
+/// Abandons an in-flight login, or every pending one when no state is given.
+#[tauri::command]
+fn cancel_login(state_value: Option<String>, state: State<'_, AuthStateManager>) -> usize {
+    state.cancel(state_value.as_deref())
+}
+
 #[tauri::command]
 async fn handle_redirect_uri(
     url: String,
     state: State<'_, AuthStateManager>,
-    app_handle: AppHandle,
-) -> Result<AuthData, String> {
+    credentials: State<'_, CredentialStoreManager>,
+) -> CommandResult<AuthData> {
+    complete_login(&url, state.inner(), credentials.0.as_ref()).await
+}
+
+// Verifies the redirect against the pending PKCE state, exchanges the code
+// for tokens and stores them. Shared by the deep link and loopback flows.
+async fn complete_login(
+    url: &str,
+    state: &AuthStateManager,
+    credentials: &dyn CredentialStore,
+) -> CommandResult<AuthData> {
     // Parse the URL
-    let url = Url::parse(&url).map_err(|e| e.to_string())?;
+    let url = Url::parse(url).map_err(|e| {
+        CommandError::new(ErrorCode::AuthFailed, "Invalid redirect URI").with_details(e.to_string())
+    })?;
 
     // Extract query parameters
     let query_params: HashMap<_, _> = url.query_pairs().into_owned().collect();
 
     // Get the authorization code and state
-    let code = query_params
-        .get("code")
-        .ok_or("No authorization code found in redirect URI")?;
-    let received_state = query_params
-        .get("state")
-        .ok_or("No state found in redirect URI")?;
-
-    // Verify state
-    let auth_state = state
-        .0
-        .lock()
-        .unwrap()
-        .clone()
-        .ok_or("No auth state found")?;
-    if received_state != &auth_state.state {
-        return Err("State mismatch, possible CSRF attack".to_string());
+    let received_state = query_params.get("state").ok_or_else(|| {
+        CommandError::new(ErrorCode::AuthFailed, "No state found in redirect URI")
+    })?;
+
+    // Verify state; this also consumes the pending attempt
+    let auth_state = state.take(received_state)?;
+
+    if let Some(error) = query_params.get("error") {
+        return Err(
+            CommandError::new(ErrorCode::AuthFailed, "Authorization failed")
+                .with_details(query_params.get("error_description").unwrap_or(error)),
+        );
     }
+    let code = query_params.get("code").ok_or_else(|| {
+        CommandError::new(
+            ErrorCode::AuthFailed,
+            "No authorization code found in redirect URI",
+        )
+    })?;
 
     // Exchange code for tokens
-    let client = Client::new();
+    let config = get_auth_config()
+        .await
+        .map_err(|e| CommandError::new(ErrorCode::AuthConfig, e))?;
+    let client = http_client();
     let params = [
-        ("client_id", CLIENT_ID),
+        ("client_id", config.client_id.as_str()),
         ("code", code),
         ("code_verifier", &auth_state.code_verifier),
         ("grant_type", "authorization_code"),
-        ("redirect_uri", REDIRECT_URI),
+        ("redirect_uri", &auth_state.redirect_uri),
     ];
 
     let token_response = client
-        .post(TOKEN_URL)
+        .post(&config.token_endpoint)
         .form(&params)
         .send()
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
 
     if !token_response.status().is_success() {
-        let error_text = token_response.text().await.map_err(|e| e.to_string())?;
-        return Err(format!("Token request failed: {}", error_text));
+        let error_text = token_response.text().await?;
+        return Err(
+            CommandError::new(ErrorCode::AuthFailed, "Token request failed")
+                .with_details(error_text),
+        );
     }
 
-    let tokens: TokenResponse = token_response.json().await.map_err(|e| e.to_string())?;
+    let tokens: TokenResponse = token_response.json().await?;
+
+    finish_login(&client, tokens, Some(&auth_state.nonce), credentials).await
+}
+
+// Validates the ID token and fetches the user profile for freshly issued
+// tokens, then stores them and exports the MCP token. Shared by every login flow.
+async fn finish_login(
+    client: &Client,
+    tokens: TokenResponse,
+    expected_nonce: Option<&str>,
+    credentials: &dyn CredentialStore,
+) -> CommandResult<AuthData> {
+    // Verify the ID token before trusting any of its claims
+    let id_claims = match &tokens.id_token {
+        Some(id_token) => Some(
+            validate_id_token(id_token, expected_nonce)
+                .await
+                .map_err(|e| CommandError::new(ErrorCode::AuthFailed, e))?,
+        ),
+        None => None,
+    };
 
     // Get user info
-    let user_info_response = client
-        .get(USER_INFO_URL)
+    let config = get_auth_config()
+        .await
+        .map_err(|e| CommandError::new(ErrorCode::AuthConfig, e))?;
+    let user_info: Option<UserInfo> = match client
+        .get(&config.userinfo_endpoint)
         .bearer_auth(&tokens.access_token)
         .send()
         .await
-        .map_err(|e| e.to_string())?;
-
-    let user_info: Option<UserInfo> = if user_info_response.status().is_success() {
-        Some(user_info_response.json().await.map_err(|e| e.to_string())?)
-    } else {
-        None
+    {
+        Ok(response) if response.status().is_success() => Some(response.json().await?),
+        Ok(response) => {
+            warn!("Userinfo request failed with status {}", response.status());
+            None
+        }
+        Err(e) => {
+            warn!("Userinfo request failed: {}", e);
+            None
+        }
     };
 
+    // OpenID Connect Core 1.0, section 5.3.2
+    if let (Some(user_info), Some(claims)) = (&user_info, &id_claims) {
+        if user_info.sub != claims.sub {
+            return Err(CommandError::new(
+                ErrorCode::AuthFailed,
+                "Userinfo subject does not match the ID token",
+            ));
+        }
+    }
+    let user_info = user_info.or_else(|| id_claims.as_ref().map(|claims| claims.user_info()));
+
     // Calculate token expiration
     let expires_at = tokens.expires_in.map(|expires_in| {
         std::time::SystemTime::now()
@@ -203,17 +449,13 @@ async fn handle_redirect_uri(
         user_info,
     };
 
-    // Save to persistent store
-    let app_data_dir = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|e| e.to_string())?;
-    let store_path = app_data_dir.join(STORE_PATH);
+    // Accounts are told apart by their subject; without one we can't store them
+    account_id(&auth_data).map_err(|e| CommandError::new(ErrorCode::AuthFailed, e))?;
 
-    // Create a simple JSON file to store the auth data
-    let json_data = serde_json::to_string_pretty(&auth_data).map_err(|e| e.to_string())?;
-    std::fs::create_dir_all(app_data_dir).map_err(|e| e.to_string())?;
-    std::fs::write(&store_path, json_data).map_err(|e| e.to_string())?;
+    // Save to persistent store as the active account
+    credentials
+        .add_account(&auth_data)
+        .map_err(|e| CommandError::new(ErrorCode::CredentialStorage, e))?;
 
     // Fetch and save MCP token
     save_way_key(tokens.access_token).await.ok(); // Ignore errors
@@ -222,122 +464,170 @@ async fn handle_redirect_uri(
 }
 
 #[tauri::command]
-async fn get_auth_data(app_handle: AppHandle) -> Result<Option<AuthData>, String> {
-    let app_data_dir = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|e| e.to_string())?;
-    let store_path = app_data_dir.join(STORE_PATH);
-
-    // Read the auth data from the file
-    if !store_path.exists() {
-        return Ok(None);
+async fn get_auth_data(
+    credentials: State<'_, CredentialStoreManager>,
+) -> CommandResult<Option<AuthData>> {
+    credentials
+        .0
+        .load()
+        .map_err(|e| CommandError::new(ErrorCode::CredentialStorage, e))
+}
+
+#[tauri::command]
+async fn logout(credentials: State<'_, CredentialStoreManager>) -> CommandResult<LogoutResult> {
+    // Keep the refresh scheduler from storing new tokens while we sign out
+    let _refresh_guard = token_refresh::REFRESH_LOCK.lock().await;
+
+    // Only the active account is signed out; other accounts stay available
+    let auth_data = credentials.0.load().unwrap_or_else(|e| {
+        warn!("Logout: {}", e);
+        None
+    });
+    if let Some(auth_data) = auth_data {
+        return Ok(sign_out(credentials.0.as_ref(), &auth_data).await);
     }
 
-    let json_data = std::fs::read_to_string(&store_path).map_err(|e| e.to_string())?;
-    let auth_data: AuthData = serde_json::from_str(&json_data).map_err(|e| e.to_string())?;
-    Ok(Some(auth_data))
+    // Nothing usable is stored; wipe whatever is there, including the MCP token file
+    let mut result = LogoutResult::default();
+    match credentials.0.clear() {
+        Ok(()) => result.credentials_deleted = true,
+        Err(e) => result.errors.push(e),
+    }
+    match delete_way_key() {
+        Ok(()) => result.way_key_deleted = true,
+        Err(e) => result.errors.push(e),
+    }
+    Ok(result)
 }
 
-#[tauri::command]
-async fn logout(app_handle: AppHandle) -> Result<(), String> {
-    let app_data_dir = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|e| e.to_string())?;
-    let store_path = app_data_dir.join(STORE_PATH);
-
-    // Delete the auth data file
-    if store_path.exists() {
-        std::fs::remove_file(&store_path).map_err(|e| e.to_string())?;
+// Revokes an account's tokens and forgets it. If it was the active account,
+// the MCP token file is handed over to the next account or deleted. Callers
+// hold `REFRESH_LOCK` from before they load `auth_data` until this returns.
+async fn sign_out(credentials: &dyn CredentialStore, auth_data: &AuthData) -> LogoutResult {
+    let mut result = LogoutResult::default();
+
+    // Revoke the tokens server-side first, while we still have them. The
+    // refresh token goes first since revoking it usually kills the access token too.
+    match get_auth_config().await {
+        Ok(config) => {
+            let client = http_client();
+            if let Some(refresh_token) = &auth_data.refresh_token {
+                match revoke_token(&client, &config, refresh_token, "refresh_token").await {
+                    Ok(()) => result.refresh_token_revoked = true,
+                    Err(e) => result
+                        .errors
+                        .push(format!("Failed to revoke refresh token: {}", e)),
+                }
+            }
+            match revoke_token(&client, &config, &auth_data.access_token, "access_token").await {
+                Ok(()) => result.access_token_revoked = true,
+                Err(e) => result
+                    .errors
+                    .push(format!("Failed to revoke access token: {}", e)),
+            }
+        }
+        Err(e) => result.errors.push(e),
     }
 
-    Ok(())
+    // Delete the stored auth data
+    let id = account_id(auth_data);
+    let was_active = matches!(
+        (&id, credentials.load()),
+        (Ok(id), Ok(Some(active))) if account_id(&active).as_ref() == Ok(id)
+    );
+    match id.and_then(|id| credentials.remove_account(&id)) {
+        Ok(next_account) => {
+            result.credentials_deleted = true;
+            result.next_account = next_account;
+        }
+        Err(e) => result.errors.push(e),
+    }
+
+    // Make sure the MCP server stops using this account's bearer token
+    let way_key = match (&result.next_account, was_active) {
+        (_, false) => Ok(()),
+        (Some(next_account), true) => save_way_key(next_account.access_token.clone())
+            .await
+            .map_err(|e| e.to_string()),
+        (None, true) => delete_way_key(),
+    };
+    match way_key {
+        Ok(()) => result.way_key_deleted = true,
+        Err(e) => result.errors.push(e),
+    }
+
+    for e in &result.errors {
+        warn!("Logout: {}", e);
+    }
+
+    result
 }
 
 #[tauri::command]
-async fn save_way_key(access_token: String) -> Result<(), String> {
-    let app_dir = app::get_app_directory()?;
+async fn save_way_key(access_token: String) -> CommandResult<()> {
+    let app_dir = app::get_app_directory().map_err(|e| CommandError::new(ErrorCode::Io, e))?;
 
     // Create directory if it doesn't exist
-    std::fs::create_dir_all(&app_dir).map_err(|e| e.to_string())?;
+    std::fs::create_dir_all(&app_dir)?;
 
     // Write token to file
     let token_path = app_dir.join("token");
-    std::fs::write(&token_path, format!("Bearer {}", &access_token))
-        .map_err(|e| e.to_string())?;
+    std::fs::write(&token_path, format!("Bearer {}", &access_token))?;
 
     Ok(())
 }
 
-#[tauri::command]
-async fn refresh_token(app_handle: AppHandle) -> Result<AuthData, String> {
-    // Get current auth data
-    let app_data_dir = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|e| e.to_string())?;
-    let store_path = app_data_dir.join(STORE_PATH);
-
-    if !store_path.exists() {
-        return Err("No auth data found".to_string());
-    }
-
-    let json_data = std::fs::read_to_string(&store_path).map_err(|e| e.to_string())?;
-    let auth_data: AuthData = serde_json::from_str(&json_data).map_err(|e| e.to_string())?;
-
-    let refresh_token = auth_data
-        .refresh_token
-        .clone()
-        .ok_or("No refresh token available")?;
-
-    // Exchange refresh token for new tokens
-    let client = Client::new();
-    let params = [
-        ("client_id", CLIENT_ID),
-        ("refresh_token", &refresh_token),
-        ("grant_type", "refresh_token"),
-    ];
-
-    let token_response = client
-        .post(TOKEN_URL)
-        .form(&params)
-        .send()
-        .await
-        .map_err(|e| e.to_string())?;
+// Whether the MCP token file is there to be kept up to date
+fn way_key_exists() -> bool {
+    app::get_app_directory().is_ok_and(|app_dir| app_dir.join("token").exists())
+}
 
-    if !token_response.status().is_success() {
-        let error_text = token_response.text().await.map_err(|e| e.to_string())?;
-        return Err(format!("Token refresh failed: {}", error_text));
+fn delete_way_key() -> Result<(), String> {
+    let token_path = app::get_app_directory()?.join("token");
+    if token_path.exists() {
+        std::fs::remove_file(&token_path)
+            .map_err(|e| format!("Failed to delete MCP token file: {}", e))?;
     }
+    Ok(())
+}
 
-    let tokens: TokenResponse = token_response.json().await.map_err(|e| e.to_string())?;
-
-    // Calculate token expiration
-    let expires_at = tokens.expires_in.map(|expires_in| {
-        std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs()
-            + expires_in
-    });
-
-    // Update auth data
-    let new_auth_data = AuthData {
-        access_token: tokens.access_token,
-        refresh_token: tokens.refresh_token.or(auth_data.refresh_token),
-        id_token: tokens.id_token.or(auth_data.id_token),
-        expires_at,
-        user_info: auth_data.user_info,
-    };
+#[tauri::command]
+async fn refresh_token(credentials: State<'_, CredentialStoreManager>) -> CommandResult<AuthData> {
+    Ok(token_refresh::refresh_auth_data(credentials.0.as_ref()).await?)
+}
 
-    // Save to persistent store
-    let json_data = serde_json::to_string_pretty(&new_auth_data).map_err(|e| e.to_string())?;
-    std::fs::write(&store_path, json_data).map_err(|e| e.to_string())?;
+// Registers the `waystation://` scheme and reports whether redirects to it
+// can be expected to reach the app
+#[cfg_attr(target_os = "macos", allow(unused_variables))]
+fn register_deep_links(app: &tauri::App) -> bool {
+    #[cfg(any(windows, target_os = "linux"))]
+    {
+        use tauri_plugin_deep_link::DeepLinkExt;
+        if let Err(e) = app.deep_link().register_all() {
+            warn!("Failed to register deep link scheme: {}", e);
+            return false;
+        }
+    }
 
-    save_way_key(new_auth_data.access_token.clone()).await.ok();
+    #[cfg(target_os = "linux")]
+    {
+        // Registration can succeed while the desktop still has no handler,
+        // e.g. when xdg-utils is missing or the .desktop file isn't picked up
+        let handler = std::process::Command::new("xdg-mime")
+            .args(["query", "default", "x-scheme-handler/waystation"])
+            .output();
+        match handler {
+            Ok(output)
+                if output.status.success()
+                    && !String::from_utf8_lossy(&output.stdout).trim().is_empty() => {}
+            _ => {
+                warn!("No xdg handler for the waystation scheme, using loopback login");
+                return false;
+            }
+        }
+    }
 
-    Ok(new_auth_data)
+    true
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -368,33 +658,74 @@ pub fn run() {
             .target(tauri_plugin_log::Target::new(tauri_plugin_log::TargetKind::Webview, ))
             .target(tauri_plugin_log::Target::new(tauri_plugin_log::TargetKind::LogDir { file_name: Some("logs".to_string()), }, ))
             .build())
-        .setup(|app| {
-            #[cfg(any(windows, target_os = "linux"))]
-            {
-                use tauri_plugin_deep_link::DeepLinkExt;
-                app.deep_link().register_all()?;
-            }
-            Ok(())
-        })
         .plugin(tauri_plugin_http::init())
         .plugin(tauri_plugin_store::Builder::new().build())
         .plugin(tauri_plugin_updater::Builder::new().build())
-        .manage(AuthStateManager(Mutex::new(None)))
+        .manage(AuthStateManager::default())
+        .manage(environment::EnvironmentStatusManager::default())
         .invoke_handler(tauri::generate_handler![
             login,
             handle_redirect_uri,
+            cancel_login,
             get_auth_data,
             logout,
             refresh_token,
+            device_flow::start_device_login,
+            device_flow::cancel_device_login,
+            accounts::list_accounts,
+            accounts::switch_account,
+            accounts::remove_account,
             app::install_waystation_mcp,
+            app::uninstall_waystation_mcp,
+            app::uninstall_waystation,
+            environment::ensure_environment,
+            environment::get_environment_status,
+            environment::cancel_environment_setup,
+            node_runtime::list_node_runtimes,
+            node_runtime::set_preferred_node_runtime,
+            mcp_clients::list_mcp_clients,
+            mcp_clients::restart_mcp_client,
+            mcp_servers::list_mcp_servers,
+            mcp_servers::add_mcp_server,
+            mcp_servers::update_mcp_server,
+            mcp_servers::rename_mcp_server,
+            mcp_servers::set_mcp_server_enabled,
+            mcp_servers::remove_mcp_server,
+            config_backup::list_config_backups,
+            config_backup::diff_config_backup,
+            config_backup::restore_config_backup,
             app::check_claude_installed,
+            app::get_claude_status,
             app::restart_claude_app,
+            claude_process::quit_claude_app,
             app::check_onboarding_completed
         ])
-        .setup(|_app| {
-            // No custom setup needed, deep link handling is done in the frontend
+        .setup(|app| {
+            // A failed registration is not fatal: login falls back to a
+            // loopback redirect instead of the `waystation://` scheme
+            let deep_links_registered = register_deep_links(app);
+            app.manage(DeepLinkStatus(AtomicBool::new(deep_links_registered)));
+
+            // Deep link handling is done in the frontend; here we only pick the
+            // credential backend and move any plaintext tokens into it.
+            let app_data_dir = app.path().app_data_dir()?;
+            let store = open_credential_store(&app_data_dir);
+            migrate_legacy_store(store.as_ref(), &app_data_dir);
+            app.manage(CredentialStoreManager(store));
+
+            // Keep the access token fresh without waiting on the frontend
+            token_refresh::start_scheduler(app.handle().clone());
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            // Don't leave a half-installed Node.js behind when the app exits
+            if let tauri::RunEvent::ExitRequested { api, .. } = event {
+                let app = app.clone();
+                if environment::cancel_setup_on_exit(move || app.exit(0)) {
+                    api.prevent_exit();
+                }
+            }
+        });
 }
@@ -0,0 +1,215 @@
+// RFC 8252 loopback redirect, used for login when the `waystation://` scheme
+// can't be registered with the OS.
+
+use log::{debug, warn};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use url::Url;
+
+const CALLBACK_PATH: &str = "/oauth/callback";
+// How long we wait for the browser to come back before giving up
+const CALLBACK_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+// A connection that doesn't send its request in time is dropped so it can't
+// hold up the real redirect
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_REQUEST_BYTES: usize = 16 * 1024;
+
+const PAGE_HEAD: &str = "<!DOCTYPE html><html><head><title>WayStation</title></head>\
+<body style=\"font-family: sans-serif; text-align: center; margin-top: 4em\">";
+
+pub struct LoopbackListener {
+    listener: TcpListener,
+    port: u16,
+}
+
+impl LoopbackListener {
+    /// Binds an ephemeral port on 127.0.0.1.
+    pub async fn bind() -> Result<Self, String> {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .map_err(|e| format!("Failed to bind loopback listener: {}", e))?;
+        let port = listener
+            .local_addr()
+            .map_err(|e| format!("Failed to read loopback address: {}", e))?
+            .port();
+        debug!("Loopback redirect listener bound on port {}", port);
+        Ok(Self { listener, port })
+    }
+
+    pub fn redirect_uri(&self) -> String {
+        format!("http://127.0.0.1:{}{}", self.port, CALLBACK_PATH)
+    }
+
+    /// Waits for the authorization server to redirect the browser back to us
+    /// with `state`. The browser is kept waiting until the callback is
+    /// answered. Callbacks for any other state, e.g. from a stale tab or
+    /// another local process, are turned away and listening goes on.
+    pub async fn wait_for_callback(self, state: &str) -> Result<LoopbackCallback, String> {
+        tokio::time::timeout(CALLBACK_TIMEOUT, self.accept_callback(state))
+            .await
+            .map_err(|_| "Timed out waiting for the login redirect".to_string())?
+    }
+
+    async fn accept_callback(&self, state: &str) -> Result<LoopbackCallback, String> {
+        loop {
+            let (mut stream, _) = self
+                .listener
+                .accept()
+                .await
+                .map_err(|e| format!("Failed to accept loopback connection: {}", e))?;
+
+            let target = tokio::time::timeout(REQUEST_TIMEOUT, read_request_target(&mut stream))
+                .await
+                .unwrap_or(None);
+            let Some(target) = target else {
+                warn!("Ignoring malformed loopback request");
+                continue;
+            };
+            let url = format!("http://127.0.0.1:{}{}", self.port, target);
+            let Some(parsed) = Url::parse(&url)
+                .ok()
+                .filter(|url| url.path() == CALLBACK_PATH)
+            else {
+                // Browsers also ask for things like /favicon.ico
+                debug!("Ignoring loopback request for {}", target);
+                let _ = write_response(&mut stream, "404 Not Found", "").await;
+                continue;
+            };
+
+            let received_state = parsed
+                .query_pairs()
+                .find_map(|(key, value)| (key == "state").then_some(value));
+            if received_state.as_deref() == Some(state) {
+                return Ok(LoopbackCallback { url, stream });
+            }
+            warn!("Ignoring loopback callback for another login attempt");
+            let body = format!(
+                "{}<h2>Sign-in link expired</h2>\
+                 <p>This link belongs to another sign-in attempt. \
+                 Finish signing in from the window the app opened.</p></body></html>",
+                PAGE_HEAD
+            );
+            let _ = write_response(&mut stream, "400 Bad Request", &body).await;
+        }
+    }
+}
+
+/// The browser's redirect back to us, still waiting for a response.
+pub struct LoopbackCallback {
+    /// The full callback URL, including its query string
+    pub url: String,
+    stream: TcpStream,
+}
+
+impl LoopbackCallback {
+    /// Tells the browser how the login went, once the code has been exchanged.
+    pub async fn respond(mut self, result: Result<(), String>) {
+        let (status, body) = match result {
+            Ok(()) => (
+                "200 OK",
+                format!(
+                    "{}<h2>Signed in to WayStation</h2>\
+                     <p>You can close this window and return to the app.</p></body></html>",
+                    PAGE_HEAD
+                ),
+            ),
+            Err(e) => (
+                "400 Bad Request",
+                format!(
+                    "{}<h2>Sign-in failed</h2><p>{}</p>\
+                     <p>Return to the app to try again.</p></body></html>",
+                    PAGE_HEAD,
+                    escape_html(&e)
+                ),
+            ),
+        };
+        if let Err(e) = write_response(&mut self.stream, status, &body).await {
+            debug!("Failed to answer the login redirect: {}", e);
+        }
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Reads the request head and returns the request target of a GET request
+async fn read_request_target(stream: &mut TcpStream) -> Option<String> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 1024];
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 || buf.len() + n > MAX_REQUEST_BYTES {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+
+    let head = String::from_utf8_lossy(&buf);
+    let mut parts = head.lines().next()?.split_whitespace();
+    match (parts.next(), parts.next()) {
+        (Some("GET"), Some(target)) => Some(target.to_string()),
+        _ => None,
+    }
+}
+
+async fn write_response(stream: &mut TcpStream, status: &str, body: &str) -> std::io::Result<()> {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Sends a GET for `target` and returns the response's status line
+    async fn get(port: u16, target: &str) -> String {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        let request = format!("GET {} HTTP/1.1\r\nHost: 127.0.0.1\r\n\r\n", target);
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response.lines().next().unwrap_or_default().to_string()
+    }
+
+    #[tokio::test]
+    async fn ignores_callbacks_for_another_state() {
+        let listener = LoopbackListener::bind().await.unwrap();
+        let port = listener.port;
+        let waiting = tokio::spawn(listener.wait_for_callback("expected"));
+
+        assert!(get(port, "/favicon.ico").await.contains("404"));
+        assert!(get(port, "/oauth/callback?code=x&state=other")
+            .await
+            .contains("400"));
+        assert!(get(port, "/oauth/callback?code=x").await.contains("400"));
+        assert!(get(port, "/oauth/callbackx?state=expected")
+            .await
+            .contains("404"));
+
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        stream
+            .write_all(b"GET /oauth/callback?code=x&state=expected HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        let callback = waiting.await.unwrap().unwrap();
+        assert!(callback
+            .url
+            .ends_with("/oauth/callback?code=x&state=expected"));
+
+        callback.respond(Ok(())).await;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+    }
+}
@@ -0,0 +1,572 @@
+// MCP clients the launcher can install servers into. Each client knows where
+// its config lives, where servers go inside it, how an entry is shaped and
+// whether it has to be restarted to pick up changes.
+//
+// Claude Desktop goes through `ConfigEdit` so it keeps the
+// backups and merge handling; the others are edited in place under the same
+// lock/atomic-write scheme, touching only the server entry so comments in
+// editor settings survive. Project-scoped configs (Claude Code's `.mcp.json`)
+// are only edited for a project directory the user picked.
+
+use crate::app::{
+    get_claude_path, get_config_path, read_existing_config, restart_claude_blocking, ConfigEdit,
+};
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::file_utils::{lock_file, strip_jsonc, write_atomic};
+use crate::jsonc;
+use crate::mcp_servers::McpServerSpec;
+use log::{debug, info};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub trait McpClient: Send + Sync {
+    /// Stable identifier used by the frontend, e.g. "cursor".
+    fn id(&self) -> &'static str;
+    fn name(&self) -> &'static str;
+    fn config_path(&self) -> Option<PathBuf>;
+    /// Whether the client appears to be installed for this user.
+    fn is_detected(&self) -> bool;
+    /// Keys leading from the config root to the servers object.
+    fn servers_key(&self) -> &'static [&'static str] {
+        &["mcpServers"]
+    }
+    /// Whether the client only sees config changes after a restart.
+    fn restart_required(&self) -> bool {
+        true
+    }
+
+    /// Converts a server spec into this client's entry format.
+    fn server_entry(&self, spec: &McpServerSpec) -> Value {
+        let mut entry = json!({
+            "command": spec.command,
+            "args": spec.args,
+        });
+        if !spec.env.is_empty() {
+            entry["env"] = json!(spec.env);
+        }
+        if let Some(cwd) = &spec.cwd {
+            entry["cwd"] = json!(cwd);
+        }
+        entry
+    }
+
+    /// A server's entry as it currently is in the config.
+    fn server_config(&self, name: &str) -> CommandResult<Option<Value>> {
+        let Some(path) = self.config_path().filter(|path| path.exists()) else {
+            return Ok(None);
+        };
+        let config = read_client_config(&path)?;
+        let servers = self
+            .servers_key()
+            .iter()
+            .try_fold(&config, |value, key| value.get(key));
+        Ok(servers.and_then(|servers| servers.get(name)).cloned())
+    }
+
+    fn has_server(&self, name: &str) -> CommandResult<bool> {
+        Ok(self.server_config(name)?.is_some())
+    }
+
+    /// Adds or replaces a server entry.
+    fn install_server(&self, name: &str, spec: &McpServerSpec) -> CommandResult<()> {
+        let entry = self.server_entry(spec);
+        edit_servers(self, |text| {
+            jsonc::set_member(text, self.servers_key(), name, &entry).map(Some)
+        })
+        .map(|_| ())
+    }
+
+    /// Removes a server entry; returns whether there was one.
+    fn remove_server(&self, name: &str) -> CommandResult<bool> {
+        if !self.config_path().is_some_and(|path| path.exists()) {
+            return Ok(false);
+        }
+        edit_servers(self, |text| {
+            jsonc::remove_member(text, self.servers_key(), name)
+        })
+    }
+
+    /// Restarts the client so it picks up MCP server changes. Clients that
+    /// pick them up on their own need nothing.
+    fn restart(&self) -> CommandResult<RestartOutcome> {
+        if !self.restart_required() {
+            return Ok(RestartOutcome::NotNeeded);
+        }
+        Err(CommandError::new(
+            ErrorCode::UnsupportedPlatform,
+            format!("Restart {} to pick up MCP server changes", self.name()),
+        ))
+    }
+}
+
+/// What `restart_mcp_client` did.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RestartOutcome {
+    Restarted,
+    /// The client picks up changes without a restart.
+    NotNeeded,
+}
+
+fn home_dir() -> Option<PathBuf> {
+    dirs::home_dir()
+}
+
+fn read_client_config_text(path: &Path) -> CommandResult<String> {
+    fs::read_to_string(path).map_err(|e| {
+        CommandError::new(ErrorCode::ConfigIo, "Failed to read config file").with_details(format!(
+            "{}: {}",
+            path.display(),
+            e
+        ))
+    })
+}
+
+// Editor settings files are often JSON with comments and trailing commas
+fn read_client_config(path: &Path) -> CommandResult<Value> {
+    let json_data = read_client_config_text(path)?;
+    if json_data.trim().is_empty() {
+        return Ok(json!({}));
+    }
+    serde_json::from_str(&strip_jsonc(&json_data)).map_err(|e| {
+        CommandError::new(ErrorCode::ConfigCorrupt, "Config file is not valid JSON")
+            .with_details(format!("{}: {}", path.display(), e))
+    })
+}
+
+// Applies `edit` to the text of the client's config, creating the file if
+// needed. `edit` returns the new text, or `None` when there is nothing to
+// change; edits go through `jsonc` so the user's comments and formatting are
+// kept.
+fn edit_servers<C: McpClient + ?Sized>(
+    client: &C,
+    edit: impl FnOnce(&str) -> Result<Option<String>, String>,
+) -> CommandResult<bool> {
+    let path = client.config_path().ok_or_else(|| {
+        CommandError::new(
+            ErrorCode::ConfigIo,
+            format!("Could not locate the {} config", client.name()),
+        )
+    })?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let _lock = lock_file(&path).map_err(|e| {
+        CommandError::new(
+            ErrorCode::ConfigLocked,
+            "The config file is in use by another process",
+        )
+        .with_details(e)
+    })?;
+    let json_data = if path.exists() {
+        read_client_config_text(&path)?
+    } else {
+        String::new()
+    };
+    let edited = edit(&json_data).map_err(|e| {
+        CommandError::new(
+            ErrorCode::ConfigCorrupt,
+            format!("Could not update the {} config", client.name()),
+        )
+        .with_details(format!("{}: {}", path.display(), e))
+    })?;
+    let Some(json_data) = edited.filter(|edited| *edited != json_data) else {
+        return Ok(false);
+    };
+
+    write_atomic(&path, json_data.as_bytes()).map_err(|e| {
+        CommandError::new(ErrorCode::ConfigIo, "Failed to write config file").with_details(e)
+    })?;
+    debug!("Updated {} config at {}", client.name(), path.display());
+    Ok(true)
+}
+
+// Visual Studio Code's per-user settings directory
+fn vscode_user_dir() -> Option<PathBuf> {
+    #[cfg(target_os = "macos")]
+    {
+        home_dir().map(|home| home.join("Library/Application Support/Code/User"))
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        dirs::config_dir().map(|dir| dir.join("Code").join("User"))
+    }
+}
+
+fn zed_config_dir() -> Option<PathBuf> {
+    #[cfg(target_os = "windows")]
+    {
+        dirs::data_dir().map(|dir| dir.join("Zed"))
+    }
+
+    // Zed uses ~/.config on macOS too
+    #[cfg(target_os = "macos")]
+    {
+        home_dir().map(|home| home.join(".config/zed"))
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    {
+        dirs::config_dir().map(|dir| dir.join("zed"))
+    }
+}
+
+pub struct ClaudeDesktop;
+
+impl McpClient for ClaudeDesktop {
+    fn id(&self) -> &'static str {
+        "claude_desktop"
+    }
+    fn name(&self) -> &'static str {
+        "Claude Desktop"
+    }
+    fn config_path(&self) -> Option<PathBuf> {
+        get_config_path().ok()
+    }
+    fn is_detected(&self) -> bool {
+        get_claude_path().is_some() || self.config_path().is_some_and(|path| path.exists())
+    }
+
+    // Only reads; a missing config is left for an install to create
+    fn server_config(&self, name: &str) -> CommandResult<Option<Value>> {
+        Ok(read_existing_config()?
+            .as_ref()
+            .and_then(|config| config.get("mcpServers"))
+            .and_then(|servers| servers.get(name))
+            .cloned())
+    }
+
+    fn install_server(&self, name: &str, spec: &McpServerSpec) -> CommandResult<()> {
+        let edit = ConfigEdit::begin()?;
+        let mut config = edit.value().clone();
+        let servers = config
+            .get_mut("mcpServers")
+            .and_then(Value::as_object_mut)
+            .ok_or_else(|| {
+                CommandError::new(
+                    ErrorCode::ConfigCorrupt,
+                    "Failed to find mcpServers in config",
+                )
+            })?;
+        servers.insert(name.to_string(), self.server_entry(spec));
+        edit.save(&config, &format!("Install {} MCP server", name))
+    }
+
+    fn remove_server(&self, name: &str) -> CommandResult<bool> {
+        let edit = ConfigEdit::begin()?;
+        let mut config = edit.value().clone();
+        let removed = config
+            .get_mut("mcpServers")
+            .and_then(Value::as_object_mut)
+            .is_some_and(|servers| servers.remove(name).is_some());
+        if removed {
+            edit.save(&config, &format!("Uninstall {} MCP server", name))?;
+        }
+        Ok(removed)
+    }
+
+    fn restart(&self) -> CommandResult<RestartOutcome> {
+        restart_claude_blocking(None).map(|_| RestartOutcome::Restarted)
+    }
+}
+
+pub struct ClaudeCode;
+
+impl McpClient for ClaudeCode {
+    fn id(&self) -> &'static str {
+        "claude_code"
+    }
+    fn name(&self) -> &'static str {
+        "Claude Code"
+    }
+    fn config_path(&self) -> Option<PathBuf> {
+        home_dir().map(|home| home.join(".claude.json"))
+    }
+    fn is_detected(&self) -> bool {
+        home_dir().is_some_and(|home| home.join(".claude").exists())
+            || self.config_path().is_some_and(|path| path.exists())
+    }
+    // New sessions read the config when they start
+    fn restart_required(&self) -> bool {
+        false
+    }
+    fn server_entry(&self, spec: &McpServerSpec) -> Value {
+        let mut entry = json!({
+            "type": "stdio",
+            "command": spec.command,
+            "args": spec.args,
+            "env": spec.env,
+        });
+        if let Some(cwd) = &spec.cwd {
+            entry["cwd"] = json!(cwd);
+        }
+        entry
+    }
+}
+
+/// Claude Code's project-scoped `.mcp.json`, shared with everyone working on
+/// the project.
+pub struct ClaudeCodeProject {
+    dir: PathBuf,
+}
+
+impl ClaudeCodeProject {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+}
+
+impl McpClient for ClaudeCodeProject {
+    fn id(&self) -> &'static str {
+        "claude_code_project"
+    }
+    fn name(&self) -> &'static str {
+        "Claude Code (project)"
+    }
+    fn config_path(&self) -> Option<PathBuf> {
+        Some(self.dir.join(".mcp.json"))
+    }
+    fn is_detected(&self) -> bool {
+        self.dir.is_dir()
+    }
+    fn restart_required(&self) -> bool {
+        false
+    }
+    fn server_entry(&self, spec: &McpServerSpec) -> Value {
+        ClaudeCode.server_entry(spec)
+    }
+}
+
+pub struct Cursor;
+
+impl McpClient for Cursor {
+    fn id(&self) -> &'static str {
+        "cursor"
+    }
+    fn name(&self) -> &'static str {
+        "Cursor"
+    }
+    fn config_path(&self) -> Option<PathBuf> {
+        home_dir().map(|home| home.join(".cursor").join("mcp.json"))
+    }
+    fn is_detected(&self) -> bool {
+        home_dir().is_some_and(|home| home.join(".cursor").exists())
+    }
+}
+
+pub struct VsCode;
+
+impl McpClient for VsCode {
+    fn id(&self) -> &'static str {
+        "vscode"
+    }
+    fn name(&self) -> &'static str {
+        "Visual Studio Code"
+    }
+    fn config_path(&self) -> Option<PathBuf> {
+        vscode_user_dir().map(|dir| dir.join("settings.json"))
+    }
+    fn is_detected(&self) -> bool {
+        vscode_user_dir().is_some_and(|dir| dir.exists())
+    }
+    fn servers_key(&self) -> &'static [&'static str] {
+        &["mcp", "servers"]
+    }
+    // Settings changes are applied live
+    fn restart_required(&self) -> bool {
+        false
+    }
+    fn server_entry(&self, spec: &McpServerSpec) -> Value {
+        let mut entry = json!({
+            "type": "stdio",
+            "command": spec.command,
+            "args": spec.args,
+        });
+        if !spec.env.is_empty() {
+            entry["env"] = json!(spec.env);
+        }
+        if let Some(cwd) = &spec.cwd {
+            entry["cwd"] = json!(cwd);
+        }
+        entry
+    }
+}
+
+pub struct Windsurf;
+
+impl McpClient for Windsurf {
+    fn id(&self) -> &'static str {
+        "windsurf"
+    }
+    fn name(&self) -> &'static str {
+        "Windsurf"
+    }
+    fn config_path(&self) -> Option<PathBuf> {
+        home_dir().map(|home| home.join(".codeium/windsurf/mcp_config.json"))
+    }
+    fn is_detected(&self) -> bool {
+        home_dir().is_some_and(|home| home.join(".codeium/windsurf").exists())
+    }
+}
+
+pub struct Zed;
+
+impl McpClient for Zed {
+    fn id(&self) -> &'static str {
+        "zed"
+    }
+    fn name(&self) -> &'static str {
+        "Zed"
+    }
+    fn config_path(&self) -> Option<PathBuf> {
+        zed_config_dir().map(|dir| dir.join("settings.json"))
+    }
+    fn is_detected(&self) -> bool {
+        zed_config_dir().is_some_and(|dir| dir.exists())
+    }
+    fn servers_key(&self) -> &'static [&'static str] {
+        &["context_servers"]
+    }
+    // Zed reloads its settings file on change
+    fn restart_required(&self) -> bool {
+        false
+    }
+    fn server_entry(&self, spec: &McpServerSpec) -> Value {
+        json!({
+            "source": "custom",
+            "command": spec.command,
+            "args": spec.args,
+            "env": spec.env,
+        })
+    }
+}
+
+pub fn all_clients() -> Vec<Box<dyn McpClient>> {
+    vec![
+        Box::new(ClaudeDesktop),
+        Box::new(ClaudeCode),
+        Box::new(Cursor),
+        Box::new(VsCode),
+        Box::new(Windsurf),
+        Box::new(Zed),
+    ]
+}
+
+/// Resolves client ids from the frontend. `None` means Claude Desktop only and
+/// `"all"` every detected client. `project_dir` is the project whose
+/// `.mcp.json` the `"claude_code_project"` client edits; `"all"` includes it
+/// when one is given.
+pub fn resolve_clients(
+    ids: Option<Vec<String>>,
+    project_dir: Option<PathBuf>,
+) -> CommandResult<Vec<Box<dyn McpClient>>> {
+    let Some(ids) = ids else {
+        return Ok(vec![Box::new(ClaudeDesktop)]);
+    };
+    let mut clients = all_clients();
+    match project_dir {
+        Some(dir) => {
+            let project = ClaudeCodeProject::new(dir);
+            if !project.is_detected() {
+                return Err(CommandError::new(
+                    ErrorCode::InvalidInput,
+                    "The project directory does not exist",
+                )
+                .with_details(project.dir.display().to_string()));
+            }
+            clients.push(Box::new(project));
+        }
+        None if ids.iter().any(|id| id == "claude_code_project") => {
+            return Err(CommandError::new(
+                ErrorCode::InvalidInput,
+                "Choose a project directory for Claude Code's .mcp.json",
+            ));
+        }
+        None => {}
+    }
+    if ids.iter().any(|id| id == "all") {
+        return Ok(clients
+            .into_iter()
+            .filter(|client| client.is_detected())
+            .collect());
+    }
+
+    if let Some(unknown) = ids
+        .iter()
+        .find(|id| !clients.iter().any(|client| client.id() == id.as_str()))
+    {
+        return Err(CommandError::new(
+            ErrorCode::InvalidInput,
+            format!("Unknown MCP client \"{}\"", unknown),
+        ));
+    }
+    clients.retain(|client| ids.iter().any(|id| id == client.id()));
+    Ok(clients)
+}
+
+#[derive(Debug, Serialize)]
+pub struct McpClientInfo {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub config_path: Option<PathBuf>,
+    pub detected: bool,
+    pub restart_required: bool,
+    /// Whether the WayStation server is configured in this client. None when
+    /// its config couldn't be read.
+    pub waystation_installed: Option<bool>,
+}
+
+/// Outcome of installing into or removing from one client.
+#[derive(Debug, Serialize)]
+pub struct ClientChange {
+    pub client: &'static str,
+    pub changed: bool,
+    pub restart_required: bool,
+    pub error: Option<CommandError>,
+}
+
+/// Lists the supported MCP clients and whether they were found on this machine.
+#[tauri::command]
+pub fn list_mcp_clients() -> CommandResult<Vec<McpClientInfo>> {
+    Ok(all_clients()
+        .iter()
+        .map(|client| McpClientInfo {
+            id: client.id(),
+            name: client.name(),
+            config_path: client.config_path(),
+            detected: client.is_detected(),
+            restart_required: client.restart_required(),
+            waystation_installed: client.has_server(crate::app::WAYSTATION_MCP_NAME).ok(),
+        })
+        .collect())
+}
+
+/// Restarts a client so it picks up MCP server changes, where supported,
+/// or reports that it doesn't need to be.
+#[tauri::command]
+pub async fn restart_mcp_client(client: String) -> CommandResult<RestartOutcome> {
+    // Restarting Claude waits for it to exit, so keep it off the main thread
+    tauri::async_runtime::spawn_blocking(move || {
+        let client = all_clients()
+            .into_iter()
+            .find(|known| known.id() == client)
+            .ok_or_else(|| {
+                CommandError::new(
+                    ErrorCode::InvalidInput,
+                    format!("Unknown MCP client \"{}\"", client),
+                )
+            })?;
+        let outcome = client.restart()?;
+        match outcome {
+            RestartOutcome::Restarted => info!("Restarted {}", client.name()),
+            RestartOutcome::NotNeeded => info!("{} needs no restart", client.name()),
+        }
+        Ok(outcome)
+    })
+    .await
+    .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?
+}
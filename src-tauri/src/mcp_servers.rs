@@ -0,0 +1,514 @@
+// Management of arbitrary `mcpServers` entries in the Claude config.
+//
+// Claude Desktop has no notion of a disabled server, so disabling one moves its
+// entry out of the config into `disabled_mcp_servers.json` in the app
+// directory; enabling it moves it back.
+
+use crate::app::{get_app_directory, get_config, ConfigEdit};
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::file_utils::write_atomic;
+use log::{debug, error, info};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const DISABLED_SERVERS_FILE: &str = "disabled_mcp_servers.json";
+const MAX_NAME_LEN: usize = 100;
+
+/// The launch settings of a stdio MCP server, as written to `mcpServers`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McpServerSpec {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    #[serde(default)]
+    pub cwd: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct McpServer {
+    pub name: String,
+    pub enabled: bool,
+    /// None for servers that aren't launched as a command, e.g. remote ones.
+    pub command: Option<String>,
+    pub args: Vec<String>,
+    pub env: BTreeMap<String, String>,
+    pub cwd: Option<String>,
+}
+
+impl McpServer {
+    fn from_entry(name: &str, entry: &Value, enabled: bool) -> Self {
+        let string = |key: &str| entry.get(key).and_then(Value::as_str).map(str::to_string);
+        McpServer {
+            name: name.to_string(),
+            enabled,
+            command: string("command"),
+            args: entry
+                .get("args")
+                .and_then(Value::as_array)
+                .map(|args| {
+                    args.iter()
+                        .filter_map(|arg| arg.as_str().map(str::to_string))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            env: entry
+                .get("env")
+                .and_then(Value::as_object)
+                .map(|env| {
+                    env.iter()
+                        .filter_map(|(key, value)| Some((key.clone(), value.as_str()?.to_string())))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            cwd: string("cwd"),
+        }
+    }
+}
+
+fn invalid(message: impl Into<String>) -> CommandError {
+    CommandError::new(ErrorCode::InvalidInput, message)
+}
+
+fn not_found(name: &str) -> CommandError {
+    CommandError::new(
+        ErrorCode::NotFound,
+        format!("No MCP server named \"{}\"", name),
+    )
+}
+
+fn validate_name(name: &str) -> CommandResult<()> {
+    if name.trim().is_empty() {
+        return Err(invalid("Server name must not be empty"));
+    }
+    if name.trim() != name {
+        return Err(invalid("Server name must not start or end with whitespace"));
+    }
+    if name.chars().count() > MAX_NAME_LEN {
+        return Err(invalid(format!(
+            "Server name must be at most {} characters",
+            MAX_NAME_LEN
+        )));
+    }
+    if name.chars().any(char::is_control) {
+        return Err(invalid("Server name must not contain control characters"));
+    }
+    Ok(())
+}
+
+fn validate_spec(spec: &McpServerSpec) -> CommandResult<()> {
+    if spec.command.trim().is_empty() {
+        return Err(invalid("Command must not be empty"));
+    }
+    if spec.command.chars().any(char::is_control) {
+        return Err(invalid("Command must not contain control characters"));
+    }
+    if spec.args.iter().any(|arg| arg.contains('\0')) {
+        return Err(invalid("Arguments must not contain NUL characters"));
+    }
+    for (key, value) in &spec.env {
+        let mut chars = key.chars();
+        let valid_key = chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_key {
+            return Err(invalid(format!(
+                "Invalid environment variable name \"{}\"",
+                key
+            )));
+        }
+        if value.contains('\0') {
+            return Err(invalid(format!(
+                "Environment variable {} must not contain NUL characters",
+                key
+            )));
+        }
+    }
+    if let Some(cwd) = &spec.cwd {
+        if !Path::new(cwd).is_absolute() {
+            return Err(invalid("Working directory must be an absolute path"));
+        }
+    }
+    Ok(())
+}
+
+// Writes the spec into an existing entry, keeping any keys we don't manage
+fn apply_spec(entry: &mut Map<String, Value>, spec: &McpServerSpec) {
+    entry.insert("command".to_string(), Value::from(spec.command.clone()));
+    entry.insert("args".to_string(), Value::from(spec.args.clone()));
+    if spec.env.is_empty() {
+        entry.remove("env");
+    } else {
+        let env = spec
+            .env
+            .iter()
+            .map(|(key, value)| (key.clone(), Value::from(value.clone())))
+            .collect();
+        entry.insert("env".to_string(), Value::Object(env));
+    }
+    match &spec.cwd {
+        Some(cwd) => entry.insert("cwd".to_string(), Value::from(cwd.clone())),
+        None => entry.remove("cwd"),
+    };
+}
+
+fn mcp_servers_mut(config: &mut Value) -> CommandResult<&mut Map<String, Value>> {
+    config
+        .get_mut("mcpServers")
+        .and_then(Value::as_object_mut)
+        .ok_or_else(|| {
+            CommandError::new(
+                ErrorCode::ConfigCorrupt,
+                "Failed to find mcpServers in config",
+            )
+        })
+}
+
+fn disabled_servers_path() -> CommandResult<PathBuf> {
+    Ok(get_app_directory()
+        .map_err(|e| CommandError::new(ErrorCode::Io, e))?
+        .join(DISABLED_SERVERS_FILE))
+}
+
+fn load_disabled_servers() -> CommandResult<Map<String, Value>> {
+    let path = disabled_servers_path()?;
+    if !path.exists() {
+        return Ok(Map::new());
+    }
+    let json_data = fs::read_to_string(&path)?;
+    serde_json::from_str(&json_data).map_err(|e| {
+        CommandError::new(ErrorCode::Internal, "Failed to parse disabled MCP servers")
+            .with_details(e.to_string())
+    })
+}
+
+fn save_disabled_servers(servers: &Map<String, Value>) -> CommandResult<()> {
+    let path = disabled_servers_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let json_data = serde_json::to_string_pretty(servers)
+        .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?;
+    write_atomic(&path, json_data.as_bytes()).map_err(|e| CommandError::new(ErrorCode::Io, e))
+}
+
+/// Lists every configured MCP server, enabled or not.
+#[tauri::command]
+pub fn list_mcp_servers() -> CommandResult<Vec<McpServer>> {
+    let mut config = get_config()?;
+    let enabled = mcp_servers_mut(&mut config)?;
+    let disabled = load_disabled_servers()?;
+
+    let mut servers: Vec<McpServer> = enabled
+        .iter()
+        .map(|(name, entry)| McpServer::from_entry(name, entry, true))
+        .chain(
+            disabled
+                .iter()
+                // An interrupted enable can leave a server in both places
+                .filter(|(name, _)| !enabled.contains_key(*name))
+                .map(|(name, entry)| McpServer::from_entry(name, entry, false)),
+        )
+        .collect();
+    servers.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(servers)
+}
+
+#[tauri::command]
+pub fn add_mcp_server(name: String, server: McpServerSpec) -> CommandResult<McpServer> {
+    validate_name(&name)?;
+    validate_spec(&server)?;
+
+    let edit = ConfigEdit::begin()?;
+    let mut config = edit.value().clone();
+    let servers = mcp_servers_mut(&mut config)?;
+    if servers.contains_key(&name) || load_disabled_servers()?.contains_key(&name) {
+        return Err(CommandError::new(
+            ErrorCode::AlreadyExists,
+            format!("An MCP server named \"{}\" already exists", name),
+        ));
+    }
+
+    let mut entry = Map::new();
+    apply_spec(&mut entry, &server);
+    let entry = Value::Object(entry);
+    let added = McpServer::from_entry(&name, &entry, true);
+    servers.insert(name.clone(), entry);
+
+    edit.save(&config, &format!("Add MCP server {}", name))?;
+    info!("Added MCP server {}", name);
+    Ok(added)
+}
+
+/// Replaces the launch settings of a server. Keys other than `command`,
+/// `args`, `env` and `cwd` are left untouched.
+#[tauri::command]
+pub fn update_mcp_server(name: String, server: McpServerSpec) -> CommandResult<McpServer> {
+    validate_spec(&server)?;
+
+    let edit = ConfigEdit::begin()?;
+    let mut config = edit.value().clone();
+    let servers = mcp_servers_mut(&mut config)?;
+    if let Some(entry) = servers.get_mut(&name) {
+        let entry_map = entry
+            .as_object_mut()
+            .ok_or_else(|| invalid(format!("MCP server \"{}\" is not an object", name)))?;
+        apply_spec(entry_map, &server);
+        let updated = McpServer::from_entry(&name, entry, true);
+        edit.save(&config, &format!("Update MCP server {}", name))?;
+        info!("Updated MCP server {}", name);
+        return Ok(updated);
+    }
+
+    let mut disabled = load_disabled_servers()?;
+    let entry = disabled.get_mut(&name).ok_or_else(|| not_found(&name))?;
+    let entry_map = entry
+        .as_object_mut()
+        .ok_or_else(|| invalid(format!("MCP server \"{}\" is not an object", name)))?;
+    apply_spec(entry_map, &server);
+    let updated = McpServer::from_entry(&name, entry, false);
+    save_disabled_servers(&disabled)?;
+    info!("Updated disabled MCP server {}", name);
+    Ok(updated)
+}
+
+#[tauri::command]
+pub fn rename_mcp_server(name: String, new_name: String) -> CommandResult<()> {
+    validate_name(&new_name)?;
+    if name == new_name {
+        return Ok(());
+    }
+
+    let edit = ConfigEdit::begin()?;
+    let mut config = edit.value().clone();
+    let servers = mcp_servers_mut(&mut config)?;
+    let mut disabled = load_disabled_servers()?;
+    if servers.contains_key(&new_name) || disabled.contains_key(&new_name) {
+        return Err(CommandError::new(
+            ErrorCode::AlreadyExists,
+            format!("An MCP server named \"{}\" already exists", new_name),
+        ));
+    }
+
+    if let Some(entry) = servers.remove(&name) {
+        servers.insert(new_name.clone(), entry);
+        edit.save(
+            &config,
+            &format!("Rename MCP server {} to {}", name, new_name),
+        )?;
+    } else {
+        let entry = disabled.remove(&name).ok_or_else(|| not_found(&name))?;
+        disabled.insert(new_name.clone(), entry);
+        save_disabled_servers(&disabled)?;
+    }
+
+    info!("Renamed MCP server {} to {}", name, new_name);
+    Ok(())
+}
+
+// Moves a server between the config's `servers` and the `disabled` stash.
+// Returns whether anything moved; a server already in place is left alone.
+fn move_server(
+    name: &str,
+    enabled: bool,
+    servers: &mut Map<String, Value>,
+    disabled: &mut Map<String, Value>,
+) -> CommandResult<bool> {
+    let (from, to) = if enabled {
+        (disabled, servers)
+    } else {
+        (servers, disabled)
+    };
+    let Some(entry) = from.remove(name) else {
+        return if to.contains_key(name) {
+            Ok(false)
+        } else {
+            Err(not_found(name))
+        };
+    };
+    // An interrupted enable can leave the server in both places; the copy
+    // already in place wins
+    to.entry(name.to_string()).or_insert(entry);
+    Ok(true)
+}
+
+/// Enables or disables a server. Disabled servers stay listed but are removed
+/// from the Claude config, so Claude Desktop won't start them.
+#[tauri::command]
+pub fn set_mcp_server_enabled(name: String, enabled: bool) -> CommandResult<()> {
+    // The config lock covers the stash too, since only config edits write it
+    let edit = ConfigEdit::begin()?;
+    let mut config = edit.value().clone();
+    let servers = mcp_servers_mut(&mut config)?;
+    let previous = load_disabled_servers()?;
+    let mut disabled = previous.clone();
+
+    if !move_server(&name, enabled, servers, &mut disabled)? {
+        debug!(
+            "MCP server {} is already {}",
+            name,
+            if enabled { "enabled" } else { "disabled" }
+        );
+        return Ok(());
+    }
+
+    // The stash goes first: if the config write then fails it is put back,
+    // and the server is never missing from both
+    save_disabled_servers(&disabled)?;
+    let reason = format!(
+        "{} MCP server {}",
+        if enabled { "Enable" } else { "Disable" },
+        name
+    );
+    if let Err(e) = edit.save(&config, &reason) {
+        if let Err(restore_error) = save_disabled_servers(&previous) {
+            error!("Failed to restore disabled MCP servers: {}", restore_error);
+        }
+        return Err(e);
+    }
+
+    info!(
+        "{} MCP server {}",
+        if enabled { "Enabled" } else { "Disabled" },
+        name
+    );
+    Ok(())
+}
+
+#[tauri::command]
+pub fn remove_mcp_server(name: String) -> CommandResult<()> {
+    let edit = ConfigEdit::begin()?;
+    let mut config = edit.value().clone();
+    let servers = mcp_servers_mut(&mut config)?;
+
+    if servers.remove(&name).is_some() {
+        edit.save(&config, &format!("Remove MCP server {}", name))?;
+    } else {
+        let mut disabled = load_disabled_servers()?;
+        disabled.remove(&name).ok_or_else(|| not_found(&name))?;
+        save_disabled_servers(&disabled)?;
+    }
+
+    info!("Removed MCP server {}", name);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn spec(command: &str) -> McpServerSpec {
+        McpServerSpec {
+            command: command.to_string(),
+            args: Vec::new(),
+            env: BTreeMap::new(),
+            cwd: None,
+        }
+    }
+
+    fn error_code<T>(result: CommandResult<T>) -> ErrorCode {
+        result.err().expect("expected an error").code
+    }
+
+    #[test]
+    fn validates_server_names() {
+        assert!(validate_name("filesystem").is_ok());
+        assert!(validate_name("My Server 2").is_ok());
+        for name in ["", "   ", " padded", "padded ", "tab\there"] {
+            assert_eq!(
+                error_code(validate_name(name)),
+                ErrorCode::InvalidInput,
+                "{:?}",
+                name
+            );
+        }
+        assert!(validate_name(&"x".repeat(MAX_NAME_LEN)).is_ok());
+        assert!(validate_name(&"x".repeat(MAX_NAME_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn validates_server_specs() {
+        assert!(validate_spec(&spec("npx")).is_ok());
+        assert!(validate_spec(&spec(" ")).is_err());
+        assert!(validate_spec(&spec("npx\n")).is_err());
+
+        let mut with_args = spec("npx");
+        with_args.args = vec!["a\0b".to_string()];
+        assert!(validate_spec(&with_args).is_err());
+
+        for (key, valid) in [
+            ("API_KEY", true),
+            ("_x1", true),
+            ("1X", false),
+            ("A-B", false),
+            ("", false),
+        ] {
+            let mut with_env = spec("npx");
+            with_env.env.insert(key.to_string(), "value".to_string());
+            assert_eq!(validate_spec(&with_env).is_ok(), valid, "{:?}", key);
+        }
+
+        let mut with_cwd = spec("npx");
+        with_cwd.cwd = Some("relative/dir".to_string());
+        assert!(validate_spec(&with_cwd).is_err());
+        with_cwd.cwd = Some(std::env::temp_dir().display().to_string());
+        assert!(validate_spec(&with_cwd).is_ok());
+    }
+
+    fn maps(servers: Value, disabled: Value) -> (Map<String, Value>, Map<String, Value>) {
+        let as_map = |value: Value| value.as_object().cloned().unwrap();
+        (as_map(servers), as_map(disabled))
+    }
+
+    #[test]
+    fn disabling_and_enabling_moves_the_entry() {
+        let entry = json!({ "command": "npx", "custom": true });
+        let (mut servers, mut disabled) = maps(json!({ "a": entry.clone() }), json!({}));
+
+        assert!(move_server("a", false, &mut servers, &mut disabled).unwrap());
+        assert!(servers.is_empty());
+        assert_eq!(disabled["a"], entry);
+
+        assert!(move_server("a", true, &mut servers, &mut disabled).unwrap());
+        assert!(disabled.is_empty());
+        assert_eq!(servers["a"], entry);
+    }
+
+    #[test]
+    fn enabling_an_enabled_server_changes_nothing() {
+        let (mut servers, mut disabled) = maps(json!({ "a": { "command": "npx" } }), json!({}));
+        assert!(!move_server("a", true, &mut servers, &mut disabled).unwrap());
+        assert_eq!(servers.len(), 1);
+        assert!(disabled.is_empty());
+    }
+
+    #[test]
+    fn moving_an_unknown_server_fails() {
+        let (mut servers, mut disabled) = maps(json!({}), json!({}));
+        assert_eq!(
+            error_code(move_server("a", true, &mut servers, &mut disabled)),
+            ErrorCode::NotFound
+        );
+        assert_eq!(
+            error_code(move_server("a", false, &mut servers, &mut disabled)),
+            ErrorCode::NotFound
+        );
+    }
+
+    #[test]
+    fn interrupted_enable_keeps_the_configured_copy() {
+        let (mut servers, mut disabled) = maps(
+            json!({ "a": { "command": "new" } }),
+            json!({ "a": { "command": "old" } }),
+        );
+        assert!(move_server("a", true, &mut servers, &mut disabled).unwrap());
+        assert!(disabled.is_empty());
+        assert_eq!(servers["a"]["command"], "new");
+    }
+}
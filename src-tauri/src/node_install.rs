@@ -0,0 +1,601 @@
+// Launcher-managed Node.js: official release archives are fetched from
+// nodejs.org (or a mirror, which may be a local directory), checked against
+// SHASUMS256.txt and its release signature, and extracted under the app
+// directory. Nothing downloaded is executed before it has been verified.
+//
+// The signature is checked in-process against the Node.js release keys
+// bundled in resources/node-release-keys.asc, which are only trusted when
+// their fingerprint is pinned in resources/node-release-keys.txt. Nothing is
+// fetched to decide what to trust.
+
+use crate::app::get_app_directory;
+use crate::node_runtime::{is_install_cancelled, InstallEvent, INSTALL_CANCELLED};
+use crate::openpgp::{self, Certificate};
+use log::{debug, info, warn};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use sysinfo::{Pid, System};
+
+pub const DEFAULT_MIRROR: &str = "https://nodejs.org/dist";
+const SHASUMS_FILE: &str = "SHASUMS256.txt";
+// Detached binary signature published next to SHASUMS256.txt
+const SIGNATURE_FILE: &str = "SHASUMS256.txt.sig";
+
+const RELEASE_KEYS: &str = include_str!("../resources/node-release-keys.txt");
+// Armored exports of the pinned keys, made by scripts/update_node_release_keys.sh
+const RELEASE_KEY_BUNDLE: &[u8] = include_bytes!("../resources/node-release-keys.asc");
+
+/// Where to get Node.js from and how to check it.
+#[derive(Debug, Clone)]
+pub struct InstallSource {
+    /// Base URL of a nodejs.org/dist style mirror, or a local directory with
+    /// the same layout (`<version>/SHASUMS256.txt`, ...).
+    pub mirror: String,
+    /// Exported OpenPGP keys (binary or armored) to trust instead of the
+    /// pinned Node.js release keys.
+    pub keyring: Option<PathBuf>,
+}
+
+/// Directory holding one subdirectory per managed Node.js version.
+pub fn runtimes_dir() -> Result<PathBuf, String> {
+    Ok(get_app_directory()?.join("runtimes").join("node"))
+}
+
+// Release archive name for this machine, e.g. node-v20.9.0-darwin-arm64.tar.gz
+fn archive_name(version: &str) -> Result<String, String> {
+    let os = match std::env::consts::OS {
+        "macos" => "darwin",
+        "linux" => "linux",
+        "windows" => "win",
+        other => return Err(format!("No official Node.js builds for {}", other)),
+    };
+    let arch = match std::env::consts::ARCH {
+        "x86_64" => "x64",
+        "aarch64" => "arm64",
+        "x86" => "x86",
+        "arm" => "armv7l",
+        other => return Err(format!("No official Node.js builds for {}", other)),
+    };
+    let extension = if cfg!(target_os = "windows") {
+        "zip"
+    } else {
+        "tar.gz"
+    };
+    Ok(format!("node-{}-{}-{}.{}", version, os, arch, extension))
+}
+
+enum Mirror {
+    Http(String),
+    Dir(PathBuf),
+}
+
+impl Mirror {
+    fn parse(mirror: &str) -> Mirror {
+        if mirror.starts_with("http://") || mirror.starts_with("https://") {
+            Mirror::Http(mirror.trim_end_matches('/').to_string())
+        } else {
+            let path = mirror.strip_prefix("file://").unwrap_or(mirror);
+            Mirror::Dir(PathBuf::from(path))
+        }
+    }
+
+    // Returns a local path for `<version>/<file>`, downloading it into
+    // `download_dir` if the mirror is remote. `on_percent` is called as the
+    // download progresses when the server sends its size.
+    fn fetch(
+        &self,
+        version: &str,
+        file: &str,
+        download_dir: &Path,
+        on_percent: &dyn Fn(u8),
+    ) -> Result<PathBuf, String> {
+        match self {
+            Mirror::Dir(dir) => {
+                // Accept both the versioned layout and a flat directory
+                let path = [dir.join(version).join(file), dir.join(file)]
+                    .into_iter()
+                    .find(|path| path.is_file())
+                    .ok_or_else(|| format!("{} not found in {}", file, dir.display()))?;
+                debug!("Using {} from local mirror", path.display());
+                Ok(path)
+            }
+            Mirror::Http(base) => {
+                let url = format!("{}/{}/{}", base, version, file);
+                info!("Downloading {}", url);
+                let mut response = reqwest::blocking::get(&url)
+                    .and_then(|response| response.error_for_status())
+                    .map_err(|e| format!("Failed to download {}: {}", url, e))?;
+                let path = download_dir.join(file);
+                let mut out = File::create(&path)
+                    .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+                let total = response.content_length().filter(|total| *total > 0);
+                let mut downloaded = 0u64;
+                let mut reported = None;
+                let mut buffer = [0u8; 64 * 1024];
+                loop {
+                    check_cancelled()?;
+                    let read = response
+                        .read(&mut buffer)
+                        .map_err(|e| format!("Failed to download {}: {}", url, e))?;
+                    if read == 0 {
+                        break;
+                    }
+                    out.write_all(&buffer[..read])
+                        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+                    downloaded += read as u64;
+                    if let Some(total) = total {
+                        let percent = (downloaded * 100 / total).min(100) as u8;
+                        if reported != Some(percent) {
+                            reported = Some(percent);
+                            on_percent(percent);
+                        }
+                    }
+                }
+                Ok(path)
+            }
+        }
+    }
+}
+
+fn check_cancelled() -> Result<(), String> {
+    if is_install_cancelled() {
+        return Err(INSTALL_CANCELLED.to_string());
+    }
+    Ok(())
+}
+
+fn sha256_file(path: &Path) -> Result<String, String> {
+    let mut file =
+        File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+// Looks up `file` in a SHASUMS256.txt ("<sha256>  <file>" per line)
+fn expected_checksum(shasums: &str, file: &str) -> Option<String> {
+    shasums.lines().find_map(|line| {
+        let (hash, name) = line.split_once(char::is_whitespace)?;
+        (name.trim() == file).then(|| hash.to_lowercase())
+    })
+}
+
+// Fingerprints listed in RELEASE_KEYS
+fn pinned_fingerprints() -> impl Iterator<Item = &'static str> {
+    RELEASE_KEYS
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .filter(|word| !word.starts_with('#'))
+}
+
+// The bundled release keys, leaving out any whose fingerprint isn't pinned
+fn release_keys() -> Result<Vec<Certificate>, String> {
+    let certificates: Vec<Certificate> = openpgp::parse_certificates(RELEASE_KEY_BUNDLE)?
+        .into_iter()
+        .filter(|certificate| {
+            let pinned = pinned_fingerprints().any(|pinned| pinned == certificate.fingerprint());
+            if !pinned {
+                warn!(
+                    "Ignoring bundled key {}, it is not pinned",
+                    certificate.fingerprint()
+                );
+            }
+            pinned
+        })
+        .collect();
+    if certificates.is_empty() {
+        return Err("No Node.js release keys are bundled with this build".to_string());
+    }
+    Ok(certificates)
+}
+
+// The keys to check the signature against: the configured keyring, or else
+// the bundled release keys
+fn trusted_certificates(keyring: Option<&Path>) -> Result<Vec<Certificate>, String> {
+    match keyring {
+        Some(keyring) => {
+            let data = fs::read(keyring)
+                .map_err(|e| format!("Failed to read keyring {}: {}", keyring.display(), e))?;
+            openpgp::parse_certificates(&data)
+        }
+        None => release_keys(),
+    }
+}
+
+fn verify_signature(
+    shasums: &Path,
+    signature: &Path,
+    keyring: Option<&Path>,
+) -> Result<(), String> {
+    let data = fs::read(shasums).map_err(|e| format!("Failed to read {}: {}", SHASUMS_FILE, e))?;
+    let signature = fs::read(signature)
+        .map_err(|e| format!("Failed to read {}: {}", SIGNATURE_FILE, e))
+        .and_then(|signature| openpgp::parse_signature(&signature))?;
+    let certificates = trusted_certificates(keyring)?;
+    let signer = openpgp::verify_detached(&data, &signature, &certificates)
+        .map_err(|e| format!("Bad signature on {}: {}", SHASUMS_FILE, e))?;
+    debug!("Verified signature on {} by {}", SHASUMS_FILE, signer);
+    Ok(())
+}
+
+#[cfg(not(target_os = "windows"))]
+fn extract(archive: &Path, into: &Path) -> Result<(), String> {
+    let file =
+        File::open(archive).map_err(|e| format!("Failed to open {}: {}", archive.display(), e))?;
+    tar::Archive::new(flate2::read::GzDecoder::new(file))
+        .unpack(into)
+        .map_err(|e| format!("Failed to extract {}: {}", archive.display(), e))
+}
+
+#[cfg(target_os = "windows")]
+fn extract(archive: &Path, into: &Path) -> Result<(), String> {
+    let file =
+        File::open(archive).map_err(|e| format!("Failed to open {}: {}", archive.display(), e))?;
+    zip::ZipArchive::new(file)
+        .and_then(|mut zip| zip.extract(into))
+        .map_err(|e| format!("Failed to extract {}: {}", archive.display(), e))
+}
+
+// Removes staging directories left behind by installs whose app didn't live to
+// finish them. The directories are named after the installing process, so
+// one that another running instance is still filling is left alone.
+fn remove_stale_staging(runtimes_dir: &Path) {
+    let Ok(entries) = fs::read_dir(runtimes_dir) else {
+        return;
+    };
+    let mut system = System::new();
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Some(pid) = name
+            .strip_prefix('.')
+            .and_then(|name| name.rsplit_once(".partial-"))
+            .and_then(|(_, pid)| pid.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        if system.refresh_process(Pid::from_u32(pid)) {
+            debug!("Leaving {}, process {} is still running", name, pid);
+            continue;
+        }
+        debug!("Removing stale {}", name);
+        let _ = fs::remove_dir_all(entry.path());
+    }
+}
+
+/// Downloads, verifies and extracts Node.js `version` (like "v20.9.0"),
+/// returning the directory it was installed to. An existing install of the
+/// same version is replaced only once the new one is complete.
+pub fn install_node(
+    version: &str,
+    source: &InstallSource,
+    progress: &dyn Fn(InstallEvent),
+) -> Result<PathBuf, String> {
+    install_into(&runtimes_dir()?, version, source, progress)
+}
+
+fn install_into(
+    runtimes_dir: &Path,
+    version: &str,
+    source: &InstallSource,
+    progress: &dyn Fn(InstallEvent),
+) -> Result<PathBuf, String> {
+    fs::create_dir_all(runtimes_dir)
+        .map_err(|e| format!("Failed to create {}: {}", runtimes_dir.display(), e))?;
+
+    remove_stale_staging(runtimes_dir);
+
+    // Everything is staged in a scratch directory next to the final one so the
+    // last step is a rename
+    let staging = runtimes_dir.join(format!(".{}.partial-{}", version, std::process::id()));
+    if staging.exists() {
+        let _ = fs::remove_dir_all(&staging);
+    }
+    fs::create_dir_all(&staging)
+        .map_err(|e| format!("Failed to create {}: {}", staging.display(), e))?;
+
+    let result = stage_node(version, source, &staging, progress);
+    let result = result.and_then(|extracted| {
+        let target = runtimes_dir.join(version);
+        if target.exists() {
+            fs::remove_dir_all(&target)
+                .map_err(|e| format!("Failed to replace {}: {}", target.display(), e))?;
+        }
+        fs::rename(&extracted, &target)
+            .map_err(|e| format!("Failed to move Node.js into place: {}", e))?;
+        Ok(target)
+    });
+
+    if let Err(e) = fs::remove_dir_all(&staging) {
+        warn!("Failed to clean up {}: {}", staging.display(), e);
+    }
+    let target = result?;
+    progress(InstallEvent::Percent(100));
+    info!("Installed Node.js {} to {}", version, target.display());
+    Ok(target)
+}
+
+// Fetches and verifies everything into `staging`, returning the extracted
+// Node.js directory. The archive download accounts for most of the progress.
+fn stage_node(
+    version: &str,
+    source: &InstallSource,
+    staging: &Path,
+    progress: &dyn Fn(InstallEvent),
+) -> Result<PathBuf, String> {
+    let mirror = Mirror::parse(&source.mirror);
+    let archive_name = archive_name(version)?;
+    let step = |step: &str, percent: u8| {
+        check_cancelled()?;
+        progress(InstallEvent::Step(step.to_string()));
+        progress(InstallEvent::Percent(percent));
+        Ok::<(), String>(())
+    };
+
+    step("Downloading checksums", 0)?;
+    let shasums_path = mirror.fetch(version, SHASUMS_FILE, staging, &|_| {})?;
+    step("Verifying release signature", 2)?;
+    let signature_path = mirror.fetch(version, SIGNATURE_FILE, staging, &|_| {})?;
+    verify_signature(&shasums_path, &signature_path, source.keyring.as_deref())?;
+
+    let shasums = fs::read_to_string(&shasums_path)
+        .map_err(|e| format!("Failed to read {}: {}", SHASUMS_FILE, e))?;
+    let expected = expected_checksum(&shasums, &archive_name)
+        .ok_or_else(|| format!("{} is not listed in {}", archive_name, SHASUMS_FILE))?;
+
+    step(&format!("Downloading {}", archive_name), 5)?;
+    let archive_path = mirror.fetch(version, &archive_name, staging, &|percent| {
+        progress(InstallEvent::Percent(
+            5 + (u16::from(percent) * 4 / 5) as u8,
+        ))
+    })?;
+    step("Verifying checksum", 87)?;
+    let actual = sha256_file(&archive_path)?;
+    if actual != expected {
+        return Err(format!(
+            "Checksum mismatch for {}: expected {}, got {}",
+            archive_name, expected, actual
+        ));
+    }
+    debug!("Verified checksum of {}", archive_name);
+
+    step("Extracting", 90)?;
+    let extract_dir = staging.join("extracted");
+    extract(&archive_path, &extract_dir)?;
+
+    // Archives contain a single node-<version>-<platform> directory
+    let root_name = archive_name
+        .trim_end_matches(".tar.gz")
+        .trim_end_matches(".zip");
+    let root = extract_dir.join(root_name);
+    if !root.is_dir() {
+        return Err(format!("Unexpected layout in {}", archive_name));
+    }
+    // Extraction can't be interrupted, so this is the last chance to back out
+    check_cancelled()?;
+    Ok(root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const VERSION: &str = "v20.9.0";
+
+    // A scratch directory removed again when the test ends
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> TempDir {
+            static COUNTER: AtomicUsize = AtomicUsize::new(0);
+            let path = std::env::temp_dir().join(format!(
+                "waystation-node-install-{}-{}",
+                std::process::id(),
+                COUNTER.fetch_add(1, Ordering::SeqCst)
+            ));
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    // A local mirror made with gpg: v20.9.0 holds a release archive for each
+    // of the common platforms, v20.9.1 lists the wrong checksums and v20.9.2
+    // lists no archive for them. Every SHASUMS256.txt is signed by
+    // release-key.gpg.
+    fn fixture_mirror() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/node-mirror")
+    }
+
+    fn signed_source(mirror: &Path) -> InstallSource {
+        InstallSource {
+            mirror: mirror.to_string_lossy().to_string(),
+            keyring: Some(fixture_mirror().join("release-key.gpg")),
+        }
+    }
+
+    fn install(version: &str, runtimes: &TempDir) -> Result<PathBuf, String> {
+        install_into(
+            &runtimes.0,
+            version,
+            &signed_source(&fixture_mirror()),
+            &|_| {},
+        )
+    }
+
+    #[test]
+    fn archive_name_matches_release_layout() {
+        let name = archive_name(VERSION).unwrap();
+        assert!(name.starts_with("node-v20.9.0-"), "{}", name);
+        assert!(
+            name.ends_with(".tar.gz") || name.ends_with(".zip"),
+            "{}",
+            name
+        );
+    }
+
+    #[test]
+    fn expected_checksum_finds_exact_file() {
+        let shasums = "\
+AAAA  node-v20.9.0-linux-x64.tar.gz
+bbbb  node-v20.9.0-linux-x64.tar.xz
+cccc  node-v20.9.0-linux-x64.tar.gz.extra
+";
+        assert_eq!(
+            expected_checksum(shasums, "node-v20.9.0-linux-x64.tar.gz").as_deref(),
+            Some("aaaa")
+        );
+        assert_eq!(
+            expected_checksum(shasums, "node-v20.9.0-linux-x64.tar.xz").as_deref(),
+            Some("bbbb")
+        );
+        assert_eq!(
+            expected_checksum(shasums, "node-v20.9.0-linux-arm64.tar.gz"),
+            None
+        );
+    }
+
+    #[test]
+    fn installs_verified_archive() {
+        let runtimes = TempDir::new();
+        let target = install(VERSION, &runtimes).unwrap();
+        assert_eq!(target, runtimes.0.join(VERSION));
+        let binary = if cfg!(target_os = "windows") {
+            "node.exe"
+        } else {
+            "bin/node"
+        };
+        assert!(target.join(binary).is_file());
+        // Nothing is left staged
+        assert_eq!(fs::read_dir(&runtimes.0).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn rejects_checksum_mismatch() {
+        let runtimes = TempDir::new();
+        let error = install("v20.9.1", &runtimes).unwrap_err();
+        assert!(error.contains("Checksum mismatch"), "{}", error);
+        assert!(!runtimes.0.join("v20.9.1").exists());
+    }
+
+    #[test]
+    fn rejects_archive_missing_from_shasums() {
+        let runtimes = TempDir::new();
+        let error = install("v20.9.2", &runtimes).unwrap_err();
+        assert!(error.contains("is not listed"), "{}", error);
+        assert!(!runtimes.0.join("v20.9.2").exists());
+    }
+
+    #[test]
+    fn removes_only_staging_of_dead_processes() {
+        let runtimes = TempDir::new();
+        let live = runtimes
+            .0
+            .join(format!(".v18.0.0.partial-{}", std::process::id()));
+        // Above any pid_max
+        let dead = runtimes.0.join(".v18.0.0.partial-999999999");
+        let installed = runtimes.0.join("v18.0.0");
+        for dir in [&live, &dead, &installed] {
+            fs::create_dir_all(dir).unwrap();
+        }
+        remove_stale_staging(&runtimes.0);
+        assert!(live.exists());
+        assert!(!dead.exists());
+        assert!(installed.exists());
+    }
+
+    #[test]
+    fn verifies_signature_with_configured_keyring() {
+        let dir = TempDir::new();
+        let fixture = |name: &str, contents: &[u8]| {
+            fs::write(dir.0.join(name), contents).unwrap();
+            dir.0.join(name)
+        };
+        let shasums = fixture(
+            SHASUMS_FILE,
+            include_bytes!("../testdata/openpgp/SHASUMS256.txt"),
+        );
+        let signature = fixture(
+            SIGNATURE_FILE,
+            include_bytes!("../testdata/openpgp/rsa.sig"),
+        );
+        let signer = fixture("rsa.asc", include_bytes!("../testdata/openpgp/rsa.asc"));
+        let other = fixture(
+            "ed25519.gpg",
+            include_bytes!("../testdata/openpgp/ed25519.gpg"),
+        );
+
+        assert!(verify_signature(&shasums, &signature, Some(&signer)).is_ok());
+        let error = verify_signature(&shasums, &signature, Some(&other)).unwrap_err();
+        assert!(error.starts_with("Bad signature"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_mirror_with_tampered_checksums() {
+        let mirror = TempDir::new();
+        let dir = mirror.0.join(VERSION);
+        fs::create_dir_all(&dir).unwrap();
+        for entry in fs::read_dir(fixture_mirror().join(VERSION)).unwrap() {
+            let entry = entry.unwrap();
+            fs::copy(entry.path(), dir.join(entry.file_name())).unwrap();
+        }
+        let mut shasums = fs::OpenOptions::new()
+            .append(true)
+            .open(dir.join(SHASUMS_FILE))
+            .unwrap();
+        writeln!(shasums, "{}  node-v20.9.0-extra.tar.gz", "0".repeat(64)).unwrap();
+
+        let runtimes = TempDir::new();
+        let error =
+            install_into(&runtimes.0, VERSION, &signed_source(&mirror.0), &|_| {}).unwrap_err();
+        assert!(error.starts_with("Bad signature"), "{}", error);
+        assert!(!runtimes.0.join(VERSION).exists());
+    }
+
+    #[test]
+    fn rejects_mirror_signed_by_another_key() {
+        let keyring = TempDir::new();
+        let other = keyring.0.join("ed25519.gpg");
+        fs::write(&other, include_bytes!("../testdata/openpgp/ed25519.gpg")).unwrap();
+        let source = InstallSource {
+            keyring: Some(other),
+            ..signed_source(&fixture_mirror())
+        };
+        let runtimes = TempDir::new();
+        let error = install_into(&runtimes.0, VERSION, &source, &|_| {}).unwrap_err();
+        assert!(error.starts_with("Bad signature"), "{}", error);
+        assert!(!runtimes.0.join(VERSION).exists());
+    }
+
+    #[test]
+    fn bundled_release_keys_are_pinned() {
+        let bundled = openpgp::parse_certificates(RELEASE_KEY_BUNDLE).unwrap();
+        for certificate in &bundled {
+            assert!(
+                pinned_fingerprints().any(|pinned| pinned == certificate.fingerprint()),
+                "{} is bundled but not pinned",
+                certificate.fingerprint()
+            );
+        }
+    }
+}
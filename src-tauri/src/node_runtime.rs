@@ -0,0 +1,1178 @@
+// Node.js version managers the launcher can take `node`/`npx` from and install
+// Node.js with. Providers are tried in a fixed order (nvm, fnm, volta, mise,
+// asdf, the launcher's own installs, then whatever node is on the system)
+// unless one is pinned in `runtime.json` in the app directory or through
+// WAYSTATION_NODE_RUNTIME.
+// Any installed version meeting the semver requirement is used; one is only
+// installed when none does.
+//
+// GUI apps don't inherit the user's shell PATH on macOS, so binaries are also
+// looked up in the places the managers install themselves to.
+
+use crate::app::get_app_directory;
+use crate::error::{CommandError, CommandResult, ErrorCode};
+use crate::file_utils::write_atomic;
+use crate::node_install::{self, InstallSource};
+use log::{debug, info, warn};
+use once_cell::sync::Lazy;
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex};
+use sysinfo::{Pid, System};
+use tauri::AppHandle;
+
+const SETTINGS_FILE: &str = "runtime.json";
+// Records the install in progress so an interrupted one can be rolled back
+const PENDING_INSTALL_FILE: &str = "pending-install.json";
+const PREFERRED_PROVIDER_ENV: &str = "WAYSTATION_NODE_RUNTIME";
+const NODE_REQUIREMENT_ENV: &str = "WAYSTATION_NODE_REQUIREMENT";
+const NODE_INSTALL_VERSION_ENV: &str = "WAYSTATION_NODE_INSTALL_VERSION";
+const NODE_MIRROR_ENV: &str = "WAYSTATION_NODE_MIRROR";
+const NODE_KEYRING_ENV: &str = "WAYSTATION_NODE_KEYRING";
+
+const DEFAULT_NODE_REQUIREMENT: &str = ">=18.17, <23";
+const DEFAULT_NODE_INSTALL_VERSION: &str = "v20.9.0";
+
+pub const INSTALL_CANCELLED: &str = "Node.js installation was cancelled";
+
+// Only one install runs at a time (environment setup holds a lock), so one
+// flag and one child process slot are enough
+static CANCEL_REQUESTED: AtomicBool = AtomicBool::new(false);
+static INSTALL_CHILD: Lazy<Mutex<Option<u32>>> = Lazy::new(|| Mutex::new(None));
+
+/// Absolute paths of the `node` and `npx` executables of one Node.js install.
+#[derive(Debug, Clone, Serialize)]
+pub struct NodePaths {
+    pub node: PathBuf,
+    pub npx: PathBuf,
+}
+
+/// Which stream a line of installer output was printed to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// Progress reported by a provider while it installs Node.js.
+#[derive(Debug, Clone)]
+pub enum InstallEvent {
+    /// What the install is doing now, e.g. "Verifying checksum".
+    Step(String),
+    /// How far along the install is, 0-100, for providers that can tell.
+    Percent(u8),
+    /// A line printed by the version manager.
+    Output(OutputStream, String),
+}
+
+pub trait RuntimeProvider: Send + Sync {
+    /// Stable identifier used in settings, e.g. "fnm".
+    fn id(&self) -> &'static str;
+    fn name(&self) -> &'static str;
+    /// Whether the version manager itself is installed.
+    fn is_available(&self) -> bool;
+    /// Installed Node.js versions, formatted like "v20.9.0".
+    fn installed_versions(&self) -> Result<Vec<String>, String>;
+    /// Paths of an installed version.
+    fn node_paths(&self, version: &str) -> Result<NodePaths, String>;
+
+    fn can_install(&self) -> bool {
+        true
+    }
+
+    fn install(&self, version: &str, _progress: &dyn Fn(InstallEvent)) -> Result<(), String> {
+        Err(format!("{} can't install Node.js {}", self.name(), version))
+    }
+
+    /// Directory a version gets installed to, removed again when an install
+    /// fails part way. None for providers that install atomically.
+    fn version_dir(&self, _version: &str) -> Option<PathBuf> {
+        None
+    }
+
+    fn has_version(&self, version: &str) -> bool {
+        self.installed_versions()
+            .is_ok_and(|versions| versions.iter().any(|installed| installed == version))
+    }
+}
+
+fn normalize_version(version: &str) -> String {
+    format!("v{}", version.trim().trim_start_matches('v'))
+}
+
+fn home_dir() -> PathBuf {
+    dirs::home_dir().unwrap_or_default()
+}
+
+// Runs a program and returns its trimmed stdout, or stderr as the error
+fn run(program: &Path, args: &[&str]) -> Result<String, String> {
+    debug!("Running {} {}", program.display(), args.join(" "));
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", program.display(), e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} {} failed: {}",
+            program.display(),
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+// Sends each line read from `reader` down `sender` from a background thread
+fn forward_lines<R: Read + Send + 'static>(
+    reader: Option<R>,
+    stream: OutputStream,
+    sender: mpsc::Sender<(OutputStream, String)>,
+) {
+    if let Some(reader) = reader {
+        std::thread::spawn(move || {
+            for line in BufReader::new(reader).lines().map_while(Result::ok) {
+                if sender.send((stream, line.trim_end().to_string())).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+// Like `run`, but passes the program's output on line by line as it's printed
+fn run_streaming(
+    program: &Path,
+    args: &[&str],
+    progress: &dyn Fn(InstallEvent),
+) -> Result<(), String> {
+    if is_install_cancelled() {
+        return Err(INSTALL_CANCELLED.to_string());
+    }
+    debug!("Running {} {}", program.display(), args.join(" "));
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run {}: {}", program.display(), e))?;
+    {
+        // A cancel that came in while spawning didn't see the child yet
+        let mut slot = INSTALL_CHILD.lock().unwrap();
+        *slot = Some(child.id());
+        if is_install_cancelled() {
+            kill_process_tree(child.id());
+        }
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    forward_lines(child.stdout.take(), OutputStream::Stdout, sender.clone());
+    forward_lines(child.stderr.take(), OutputStream::Stderr, sender);
+
+    // The loop ends once both pipes have been closed
+    let mut last_error = None;
+    for (stream, line) in receiver {
+        if stream == OutputStream::Stderr && !line.is_empty() {
+            last_error = Some(line.clone());
+        }
+        progress(InstallEvent::Output(stream, line));
+    }
+
+    let status = child.wait();
+    *INSTALL_CHILD.lock().unwrap() = None;
+    let status = status.map_err(|e| format!("Failed to run {}: {}", program.display(), e))?;
+    if is_install_cancelled() {
+        return Err(INSTALL_CANCELLED.to_string());
+    }
+    if !status.success() {
+        return Err(format!(
+            "{} {} failed: {}",
+            program.display(),
+            args.join(" "),
+            last_error.unwrap_or_else(|| status.to_string())
+        ));
+    }
+    Ok(())
+}
+
+// Kills a process along with everything it started, e.g. the curl and tar
+// run by `nvm install`
+fn kill_process_tree(pid: u32) {
+    #[cfg(target_os = "windows")]
+    {
+        let _ = Command::new("taskkill")
+            .args(["/F", "/T", "/PID", &pid.to_string()])
+            .output();
+    }
+
+    let mut system = System::new();
+    system.refresh_processes();
+    // Collect the whole tree first, since killing a parent re-parents its
+    // children
+    let mut tree = vec![Pid::from_u32(pid)];
+    let mut next = 0;
+    while next < tree.len() {
+        let parent = tree[next];
+        tree.extend(
+            system
+                .processes()
+                .iter()
+                .filter(|(_, process)| process.parent() == Some(parent))
+                .map(|(pid, _)| *pid),
+        );
+        next += 1;
+    }
+    debug!("Killing install process tree {:?}", tree);
+    for pid in tree.iter().rev() {
+        if let Some(process) = system.process(*pid) {
+            process.kill();
+        }
+    }
+}
+
+/// Whether the running install has been asked to stop.
+pub fn is_install_cancelled() -> bool {
+    CANCEL_REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Clears a previous cancellation before a new setup starts.
+pub fn reset_install_cancellation() {
+    CANCEL_REQUESTED.store(false, Ordering::SeqCst);
+}
+
+/// Stops the running install, killing the version manager and whatever it
+/// started. The install then fails with `INSTALL_CANCELLED`.
+pub fn cancel_install() {
+    CANCEL_REQUESTED.store(true, Ordering::SeqCst);
+    if let Some(pid) = *INSTALL_CHILD.lock().unwrap() {
+        info!("Cancelling Node.js install (pid {})", pid);
+        kill_process_tree(pid);
+    }
+}
+
+// Looks for an executable on PATH and then in `extra_dirs`
+fn find_executable(name: &str, extra_dirs: &[PathBuf]) -> Option<PathBuf> {
+    let names: Vec<String> = if cfg!(target_os = "windows") {
+        vec![format!("{}.exe", name), format!("{}.cmd", name)]
+    } else {
+        vec![name.to_string()]
+    };
+    let path_dirs = env::var_os("PATH")
+        .map(|paths| env::split_paths(&paths).collect::<Vec<_>>())
+        .unwrap_or_default();
+    path_dirs
+        .iter()
+        .chain(extra_dirs)
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|path| path.is_file())
+}
+
+// Where package managers put binaries on macOS and Linux
+fn common_bin_dirs() -> Vec<PathBuf> {
+    vec![
+        home_dir().join(".local/bin"),
+        home_dir().join(".cargo/bin"),
+        PathBuf::from("/opt/homebrew/bin"),
+        PathBuf::from("/usr/local/bin"),
+        PathBuf::from("/usr/bin"),
+    ]
+}
+
+// The layout of an extracted Node.js distribution
+fn node_in_dir(dir: &Path) -> Result<NodePaths, String> {
+    let paths = if cfg!(target_os = "windows") {
+        NodePaths {
+            node: dir.join("node.exe"),
+            npx: dir.join("npx.cmd"),
+        }
+    } else {
+        NodePaths {
+            node: dir.join("bin").join("node"),
+            npx: dir.join("bin").join("npx"),
+        }
+    };
+    if !paths.node.is_file() {
+        return Err(format!("node not found in {}", dir.display()));
+    }
+    Ok(paths)
+}
+
+// Subdirectories named after Node.js versions, with or without the "v",
+// oldest first
+fn versions_in_dir(dir: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut versions: Vec<(Version, String)> = entries
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter_map(|name| Some((parse_version(&name)?, normalize_version(&name))))
+        .collect();
+    versions.sort();
+    versions.into_iter().map(|(_, version)| version).collect()
+}
+
+/// nvm (https://github.com/nvm-sh/nvm), which only exists as a shell function.
+#[cfg(not(target_os = "windows"))]
+pub struct Nvm;
+
+#[cfg(not(target_os = "windows"))]
+impl Nvm {
+    fn dir() -> PathBuf {
+        env::var_os("NVM_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| home_dir().join(".nvm"))
+    }
+
+    // NVM_DIR and the arguments reach the script as positional parameters,
+    // so nothing is interpolated into it
+    fn run_nvm(args: &[&str], progress: &dyn Fn(InstallEvent)) -> Result<(), String> {
+        const SCRIPT: &str = r#"
+            export NVM_DIR="$1"
+            shift
+            [ -s "$NVM_DIR/nvm.sh" ] && \. "$NVM_DIR/nvm.sh"
+            nvm "$@"
+        "#;
+        let nvm_dir = Self::dir().to_string_lossy().into_owned();
+        let mut bash_args = vec!["-c", SCRIPT, "bash", &nvm_dir];
+        bash_args.extend_from_slice(args);
+        run_streaming(Path::new("bash"), &bash_args, progress)
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+impl RuntimeProvider for Nvm {
+    fn id(&self) -> &'static str {
+        "nvm"
+    }
+    fn name(&self) -> &'static str {
+        "nvm"
+    }
+    fn is_available(&self) -> bool {
+        Self::dir().join("nvm.sh").is_file()
+    }
+    fn installed_versions(&self) -> Result<Vec<String>, String> {
+        Ok(versions_in_dir(&Self::dir().join("versions/node")))
+    }
+    fn node_paths(&self, version: &str) -> Result<NodePaths, String> {
+        node_in_dir(&Self::dir().join("versions/node").join(version))
+    }
+    fn version_dir(&self, version: &str) -> Option<PathBuf> {
+        Some(Self::dir().join("versions/node").join(version))
+    }
+    fn install(&self, version: &str, progress: &dyn Fn(InstallEvent)) -> Result<(), String> {
+        // Only ever hand nvm an exact version, never an alias or an option
+        parse_version(version).ok_or_else(|| format!("Invalid Node.js version \"{}\"", version))?;
+        Self::run_nvm(&["install", version, "--no-progress"], progress)
+    }
+}
+
+/// nvm for Windows (https://github.com/coreybutler/nvm-windows).
+#[cfg(target_os = "windows")]
+pub struct NvmWindows;
+
+#[cfg(target_os = "windows")]
+impl NvmWindows {
+    fn home() -> PathBuf {
+        env::var_os("NVM_HOME")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| dirs::config_dir().unwrap_or_default().join("nvm"))
+    }
+}
+
+#[cfg(target_os = "windows")]
+impl RuntimeProvider for NvmWindows {
+    fn id(&self) -> &'static str {
+        "nvm-windows"
+    }
+    fn name(&self) -> &'static str {
+        "NVM for Windows"
+    }
+    fn is_available(&self) -> bool {
+        Self::home().join("nvm.exe").is_file()
+    }
+    fn installed_versions(&self) -> Result<Vec<String>, String> {
+        Ok(versions_in_dir(&Self::home()))
+    }
+    fn node_paths(&self, version: &str) -> Result<NodePaths, String> {
+        node_in_dir(&Self::home().join(version))
+    }
+    fn version_dir(&self, version: &str) -> Option<PathBuf> {
+        Some(Self::home().join(version))
+    }
+    fn install(&self, version: &str, progress: &dyn Fn(InstallEvent)) -> Result<(), String> {
+        let nvm_exe = Self::home().join("nvm.exe");
+        run_streaming(
+            &nvm_exe,
+            &["install", version.trim_start_matches('v')],
+            progress,
+        )
+    }
+}
+
+/// fnm (https://github.com/Schniz/fnm).
+pub struct Fnm;
+
+impl Fnm {
+    fn binary() -> Option<PathBuf> {
+        let mut extra_dirs = common_bin_dirs();
+        extra_dirs.push(home_dir().join(".fnm"));
+        if let Some(data_dir) = dirs::data_dir() {
+            extra_dirs.push(data_dir.join("fnm"));
+        }
+        find_executable("fnm", &extra_dirs)
+    }
+
+    fn dir() -> PathBuf {
+        if let Some(dir) = env::var_os("FNM_DIR") {
+            return PathBuf::from(dir);
+        }
+        // Older releases used ~/.fnm
+        let legacy = home_dir().join(".fnm");
+        if legacy.join("node-versions").is_dir() {
+            return legacy;
+        }
+        dirs::data_dir().unwrap_or_default().join("fnm")
+    }
+}
+
+impl RuntimeProvider for Fnm {
+    fn id(&self) -> &'static str {
+        "fnm"
+    }
+    fn name(&self) -> &'static str {
+        "fnm"
+    }
+    fn is_available(&self) -> bool {
+        Self::binary().is_some()
+    }
+    fn installed_versions(&self) -> Result<Vec<String>, String> {
+        Ok(versions_in_dir(&Self::dir().join("node-versions")))
+    }
+    fn node_paths(&self, version: &str) -> Result<NodePaths, String> {
+        node_in_dir(
+            &Self::dir()
+                .join("node-versions")
+                .join(version)
+                .join("installation"),
+        )
+    }
+    fn version_dir(&self, version: &str) -> Option<PathBuf> {
+        Some(Self::dir().join("node-versions").join(version))
+    }
+    fn install(&self, version: &str, progress: &dyn Fn(InstallEvent)) -> Result<(), String> {
+        let fnm = Self::binary().ok_or("fnm not found")?;
+        run_streaming(&fnm, &["install", version], progress)
+    }
+}
+
+/// Volta (https://volta.sh).
+pub struct Volta;
+
+impl Volta {
+    fn home() -> PathBuf {
+        if let Some(home) = env::var_os("VOLTA_HOME") {
+            return PathBuf::from(home);
+        }
+        if cfg!(target_os = "windows") {
+            dirs::data_local_dir().unwrap_or_default().join("Volta")
+        } else {
+            home_dir().join(".volta")
+        }
+    }
+
+    fn binary() -> Option<PathBuf> {
+        let mut extra_dirs = common_bin_dirs();
+        extra_dirs.push(Self::home().join("bin"));
+        find_executable("volta", &extra_dirs)
+    }
+
+    fn image_dir() -> PathBuf {
+        Self::home().join("tools").join("image").join("node")
+    }
+}
+
+impl RuntimeProvider for Volta {
+    fn id(&self) -> &'static str {
+        "volta"
+    }
+    fn name(&self) -> &'static str {
+        "Volta"
+    }
+    fn is_available(&self) -> bool {
+        Self::binary().is_some()
+    }
+    fn installed_versions(&self) -> Result<Vec<String>, String> {
+        Ok(versions_in_dir(&Self::image_dir()))
+    }
+    fn node_paths(&self, version: &str) -> Result<NodePaths, String> {
+        // Volta names its image directories without the "v"
+        node_in_dir(&Self::image_dir().join(version.trim_start_matches('v')))
+    }
+    fn version_dir(&self, version: &str) -> Option<PathBuf> {
+        Some(Self::image_dir().join(version.trim_start_matches('v')))
+    }
+    fn install(&self, version: &str, progress: &dyn Fn(InstallEvent)) -> Result<(), String> {
+        // `fetch` downloads without changing the user's default
+        let volta = Self::binary().ok_or("volta not found")?;
+        let spec = format!("node@{}", version.trim_start_matches('v'));
+        run_streaming(&volta, &["fetch", &spec], progress)
+    }
+}
+
+/// mise and asdf, which share the same installs layout.
+pub struct ToolVersionManager {
+    id: &'static str,
+    name: &'static str,
+    binary: &'static str,
+    plugin: &'static str,
+    data_dir_env: &'static str,
+    default_data_dir: fn() -> PathBuf,
+}
+
+pub const MISE: ToolVersionManager = ToolVersionManager {
+    id: "mise",
+    name: "mise",
+    binary: "mise",
+    plugin: "node",
+    data_dir_env: "MISE_DATA_DIR",
+    default_data_dir: || {
+        if cfg!(target_os = "windows") {
+            dirs::data_local_dir().unwrap_or_default().join("mise")
+        } else {
+            home_dir().join(".local/share/mise")
+        }
+    },
+};
+
+pub const ASDF: ToolVersionManager = ToolVersionManager {
+    id: "asdf",
+    name: "asdf",
+    binary: "asdf",
+    plugin: "nodejs",
+    data_dir_env: "ASDF_DATA_DIR",
+    default_data_dir: || home_dir().join(".asdf"),
+};
+
+impl ToolVersionManager {
+    fn binary(&self) -> Option<PathBuf> {
+        let mut extra_dirs = common_bin_dirs();
+        extra_dirs.push(self.data_dir().join("bin"));
+        find_executable(self.binary, &extra_dirs)
+    }
+
+    fn data_dir(&self) -> PathBuf {
+        env::var_os(self.data_dir_env)
+            .map(PathBuf::from)
+            .unwrap_or_else(self.default_data_dir)
+    }
+
+    fn installs_dir(&self) -> PathBuf {
+        self.data_dir().join("installs").join(self.plugin)
+    }
+}
+
+impl RuntimeProvider for ToolVersionManager {
+    fn id(&self) -> &'static str {
+        self.id
+    }
+    fn name(&self) -> &'static str {
+        self.name
+    }
+    fn is_available(&self) -> bool {
+        self.binary().is_some()
+    }
+    fn installed_versions(&self) -> Result<Vec<String>, String> {
+        Ok(versions_in_dir(&self.installs_dir()))
+    }
+    fn node_paths(&self, version: &str) -> Result<NodePaths, String> {
+        node_in_dir(&self.installs_dir().join(version.trim_start_matches('v')))
+    }
+    fn version_dir(&self, version: &str) -> Option<PathBuf> {
+        Some(self.installs_dir().join(version.trim_start_matches('v')))
+    }
+    fn install(&self, version: &str, progress: &dyn Fn(InstallEvent)) -> Result<(), String> {
+        let binary = self
+            .binary()
+            .ok_or_else(|| format!("{} not found", self.name))?;
+        let version = version.trim_start_matches('v');
+        if self.id == "asdf" {
+            run_streaming(&binary, &["install", self.plugin, version], progress)
+        } else {
+            let spec = format!("{}@{}", self.plugin, version);
+            run_streaming(&binary, &["install", &spec], progress)
+        }
+    }
+}
+
+/// Whatever `node` is on PATH or in the usual install locations. It can't
+/// install other versions.
+pub struct SystemNode;
+
+impl SystemNode {
+    fn binary() -> Option<PathBuf> {
+        let mut extra_dirs = common_bin_dirs();
+        if cfg!(target_os = "windows") {
+            for var in ["ProgramFiles", "APPDATA"] {
+                if let Some(dir) = env::var_os(var) {
+                    extra_dirs.push(PathBuf::from(dir).join("nodejs"));
+                }
+            }
+        }
+        find_executable("node", &extra_dirs)
+    }
+}
+
+impl RuntimeProvider for SystemNode {
+    fn id(&self) -> &'static str {
+        "system"
+    }
+    fn name(&self) -> &'static str {
+        "System Node.js"
+    }
+    fn is_available(&self) -> bool {
+        Self::binary().is_some()
+    }
+    fn installed_versions(&self) -> Result<Vec<String>, String> {
+        let node = Self::binary().ok_or("Node.js not found")?;
+        let version = run(&node, &["--version"])?;
+        Ok(vec![normalize_version(&version)])
+    }
+    fn node_paths(&self, version: &str) -> Result<NodePaths, String> {
+        if !self.has_version(version) {
+            return Err(format!("System Node.js is not {}", version));
+        }
+        let node = Self::binary().ok_or("Node.js not found")?;
+        // npx ships next to node
+        let npx_name = if cfg!(target_os = "windows") {
+            "npx.cmd"
+        } else {
+            "npx"
+        };
+        let npx = node
+            .parent()
+            .map(|dir| dir.join(npx_name))
+            .filter(|npx| npx.is_file())
+            .or_else(|| find_executable("npx", &common_bin_dirs()))
+            .ok_or("npx not found")?;
+        Ok(NodePaths { node, npx })
+    }
+    fn can_install(&self) -> bool {
+        false
+    }
+}
+
+/// Node.js installed by the launcher itself from verified release archives
+/// (see `node_install`). Always available, so it's the installer of last
+/// resort when the user has no version manager.
+pub struct ManagedNode;
+
+impl RuntimeProvider for ManagedNode {
+    fn id(&self) -> &'static str {
+        "managed"
+    }
+    fn name(&self) -> &'static str {
+        "WayStation"
+    }
+    fn is_available(&self) -> bool {
+        true
+    }
+    fn installed_versions(&self) -> Result<Vec<String>, String> {
+        Ok(versions_in_dir(&node_install::runtimes_dir()?))
+    }
+    fn node_paths(&self, version: &str) -> Result<NodePaths, String> {
+        node_in_dir(&node_install::runtimes_dir()?.join(version))
+    }
+    fn install(&self, version: &str, progress: &dyn Fn(InstallEvent)) -> Result<(), String> {
+        node_install::install_node(version, &install_source(), progress).map(|_| ())
+    }
+}
+
+// Detection order when no provider is pinned
+fn all_providers() -> Vec<Box<dyn RuntimeProvider>> {
+    vec![
+        #[cfg(not(target_os = "windows"))]
+        Box::new(Nvm),
+        #[cfg(target_os = "windows")]
+        Box::new(NvmWindows),
+        Box::new(Fnm),
+        Box::new(Volta),
+        Box::new(MISE),
+        Box::new(ASDF),
+        Box::new(ManagedNode),
+        Box::new(SystemNode),
+    ]
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct RuntimeSettings {
+    preferred_provider: Option<String>,
+    /// Semver requirement the Node.js version has to meet, e.g. ">=18.17, <23".
+    node_requirement: Option<String>,
+    /// Version installed when nothing installed meets the requirement.
+    node_install_version: Option<String>,
+    /// Where managed installs come from: a nodejs.org/dist style URL or a
+    /// local directory.
+    node_mirror: Option<String>,
+    /// Exported OpenPGP keys to verify releases with instead of the pinned
+    /// Node.js release keys, e.g. for a mirror that re-signs them.
+    node_keyring: Option<String>,
+}
+
+fn settings_path() -> Result<PathBuf, String> {
+    Ok(get_app_directory()?.join(SETTINGS_FILE))
+}
+
+fn load_settings() -> Result<RuntimeSettings, String> {
+    let path = settings_path()?;
+    if !path.exists() {
+        return Ok(RuntimeSettings::default());
+    }
+    let json_data =
+        fs::read_to_string(&path).map_err(|e| format!("Failed to read runtime settings: {}", e))?;
+    serde_json::from_str(&json_data).map_err(|e| format!("Failed to parse runtime settings: {}", e))
+}
+
+fn save_settings(settings: &RuntimeSettings) -> Result<(), String> {
+    let path = settings_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create app directory: {}", e))?;
+    }
+    let json_data = serde_json::to_string_pretty(settings).map_err(|e| e.to_string())?;
+    write_atomic(&path, json_data.as_bytes())
+}
+
+// A setting from the environment, which wins, or the settings file
+fn setting(
+    env_var: &str,
+    from_file: impl FnOnce(RuntimeSettings) -> Option<String>,
+) -> Option<String> {
+    if let Some(value) = env::var(env_var).ok().filter(|v| !v.is_empty()) {
+        return Some(value);
+    }
+    match load_settings() {
+        Ok(settings) => from_file(settings),
+        Err(e) => {
+            warn!("{}", e);
+            None
+        }
+    }
+}
+
+/// The pinned provider id, if any.
+pub fn preferred_provider() -> Option<String> {
+    setting(PREFERRED_PROVIDER_ENV, |settings| {
+        settings.preferred_provider
+    })
+}
+
+/// Parses a requirement like ">=18.17 <23", also accepting the
+/// space-separated form npm uses.
+pub fn parse_requirement(requirement: &str) -> Result<VersionReq, String> {
+    let normalized = if requirement.contains(',') {
+        requirement.to_string()
+    } else {
+        requirement
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    VersionReq::parse(&normalized).map_err(|e| {
+        format!(
+            "Invalid Node.js version requirement \"{}\": {}",
+            requirement, e
+        )
+    })
+}
+
+/// The Node.js versions WayStation can run on.
+pub fn node_requirement() -> Result<VersionReq, String> {
+    let requirement = setting(NODE_REQUIREMENT_ENV, |settings| settings.node_requirement)
+        .unwrap_or_else(|| DEFAULT_NODE_REQUIREMENT.to_string());
+    parse_requirement(&requirement)
+}
+
+/// The version to install when nothing installed meets the requirement.
+pub fn node_install_version() -> String {
+    setting(NODE_INSTALL_VERSION_ENV, |settings| {
+        settings.node_install_version
+    })
+    .map(|version| normalize_version(&version))
+    .unwrap_or_else(|| DEFAULT_NODE_INSTALL_VERSION.to_string())
+}
+
+// Mirror and keyring for managed installs
+fn install_source() -> InstallSource {
+    InstallSource {
+        mirror: setting(NODE_MIRROR_ENV, |settings| settings.node_mirror)
+            .unwrap_or_else(|| node_install::DEFAULT_MIRROR.to_string()),
+        keyring: setting(NODE_KEYRING_ENV, |settings| settings.node_keyring).map(PathBuf::from),
+    }
+}
+
+fn parse_version(version: &str) -> Option<Version> {
+    Version::parse(version.trim().trim_start_matches('v')).ok()
+}
+
+pub fn provider_by_id(id: &str) -> Option<Box<dyn RuntimeProvider>> {
+    all_providers()
+        .into_iter()
+        .find(|provider| provider.id() == id)
+}
+
+// The providers to consider: only the pinned one if there is one, otherwise
+// every installed one in detection order
+fn candidate_providers() -> Result<Vec<Box<dyn RuntimeProvider>>, String> {
+    if let Some(id) = preferred_provider() {
+        let provider = provider_by_id(&id)
+            .ok_or_else(|| format!("Unknown Node.js runtime provider \"{}\"", id))?;
+        if !provider.is_available() {
+            return Err(format!(
+                "The preferred Node.js runtime provider {} is not installed",
+                provider.name()
+            ));
+        }
+        debug!("Using preferred Node.js runtime provider {}", provider.id());
+        return Ok(vec![provider]);
+    }
+
+    let available: Vec<Box<dyn RuntimeProvider>> = all_providers()
+        .into_iter()
+        .filter(|provider| provider.is_available())
+        .collect();
+    debug!(
+        "Available Node.js runtime providers: {:?}",
+        available.iter().map(|p| p.id()).collect::<Vec<_>>()
+    );
+    Ok(available)
+}
+
+/// A Node.js version installed through a provider.
+pub struct InstalledNode {
+    pub provider: Box<dyn RuntimeProvider>,
+    /// Formatted like "v20.9.0".
+    pub version: String,
+}
+
+impl InstalledNode {
+    pub fn paths(&self) -> Result<NodePaths, String> {
+        self.provider.node_paths(&self.version)
+    }
+}
+
+/// Finds the highest installed Node.js version meeting `requirement` across
+/// the candidate providers. On a tie the provider detected first wins.
+pub fn find_installed_node(requirement: &VersionReq) -> Result<Option<InstalledNode>, String> {
+    Ok(newest_installed(candidate_providers()?, requirement))
+}
+
+fn newest_installed(
+    providers: Vec<Box<dyn RuntimeProvider>>,
+    requirement: &VersionReq,
+) -> Option<InstalledNode> {
+    let mut best: Option<(Version, InstalledNode)> = None;
+    for provider in providers {
+        let versions = match provider.installed_versions() {
+            Ok(versions) => versions,
+            Err(e) => {
+                debug!("{}: {}", provider.name(), e);
+                continue;
+            }
+        };
+        let newest = versions
+            .into_iter()
+            .filter_map(|version| Some((parse_version(&version)?, version)))
+            .filter(|(parsed, _)| requirement.matches(parsed))
+            .max_by(|(a, _), (b, _)| a.cmp(b));
+        let Some((parsed, version)) = newest else {
+            continue;
+        };
+        if let Some((current, _)) = &best {
+            if parsed <= *current {
+                continue;
+            }
+        }
+        best = Some((parsed, InstalledNode { provider, version }));
+    }
+    best.map(|(_, node)| node)
+}
+
+/// Picks the provider to install Node.js with: the pinned one, else the first
+/// installed version manager, falling back to a launcher-managed install.
+pub fn installer_provider() -> Result<Box<dyn RuntimeProvider>, String> {
+    // Only a pinned provider that can't install (i.e. system) gets here empty
+    // handed, since managed installs are always available otherwise
+    candidate_providers()?
+        .into_iter()
+        .find(|provider| provider.can_install())
+        .ok_or_else(|| {
+            format!(
+                "The preferred Node.js runtime provider can't install Node.js {}",
+                node_install_version()
+            )
+        })
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PendingInstall {
+    provider: String,
+    version: String,
+}
+
+fn pending_install_path() -> Result<PathBuf, String> {
+    Ok(get_app_directory()?.join(PENDING_INSTALL_FILE))
+}
+
+// Removes what a failed install of `version` left behind
+fn roll_back_install(provider: &dyn RuntimeProvider, version: &str) {
+    let Some(dir) = provider.version_dir(version) else {
+        return;
+    };
+    if !dir.exists() {
+        return;
+    }
+    match fs::remove_dir_all(&dir) {
+        Ok(()) => info!(
+            "Rolled back partial Node.js {} install at {}",
+            version,
+            dir.display()
+        ),
+        Err(e) => warn!("Failed to roll back {}: {}", dir.display(), e),
+    }
+}
+
+/// Installs `version` with `provider`. If the install fails, is cancelled, or
+/// the app exits before it finishes, the partly installed version is removed
+/// (at the latest by the next `recover_interrupted_install`).
+pub fn install_with_rollback(
+    provider: &dyn RuntimeProvider,
+    version: &str,
+    progress: &dyn Fn(InstallEvent),
+) -> Result<(), String> {
+    // Never touch a version that was there before. Go by its directory: a
+    // half-installed leftover isn't listed as installed but must not be removed
+    // by rolling back this attempt either.
+    let track = provider
+        .version_dir(version)
+        .is_some_and(|dir| !dir.exists());
+    let pending_path = pending_install_path()?;
+    if track {
+        let pending = PendingInstall {
+            provider: provider.id().to_string(),
+            version: version.to_string(),
+        };
+        if let Some(parent) = pending_path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create app directory: {}", e))?;
+        }
+        let json_data = serde_json::to_string_pretty(&pending).map_err(|e| e.to_string())?;
+        write_atomic(&pending_path, json_data.as_bytes())?;
+    }
+
+    let result = provider.install(version, progress);
+    if result.is_err() && track {
+        roll_back_install(provider, version);
+    }
+    if track {
+        if let Err(e) = fs::remove_file(&pending_path) {
+            warn!("Failed to remove {}: {}", pending_path.display(), e);
+        }
+    }
+    result
+}
+
+/// Rolls back an install that was interrupted by the app exiting, so the
+/// next attempt starts clean.
+pub fn recover_interrupted_install() -> Result<(), String> {
+    let pending_path = pending_install_path()?;
+    if !pending_path.exists() {
+        return Ok(());
+    }
+    let pending: Option<PendingInstall> = fs::read_to_string(&pending_path)
+        .ok()
+        .and_then(|json_data| serde_json::from_str(&json_data).ok());
+    match pending {
+        Some(pending) => match provider_by_id(&pending.provider) {
+            Some(provider) => {
+                warn!(
+                    "Node.js {} install with {} was interrupted, rolling it back",
+                    pending.version,
+                    provider.name()
+                );
+                roll_back_install(provider.as_ref(), &pending.version);
+            }
+            None => warn!(
+                "Unknown provider \"{}\" in {}",
+                pending.provider, PENDING_INSTALL_FILE
+            ),
+        },
+        None => warn!("Ignoring unreadable {}", PENDING_INSTALL_FILE),
+    }
+    fs::remove_file(&pending_path)
+        .map_err(|e| format!("Failed to remove {}: {}", pending_path.display(), e))
+}
+
+#[derive(Debug, Serialize)]
+pub struct RuntimeProviderInfo {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub available: bool,
+    pub can_install: bool,
+    pub versions: Vec<String>,
+    pub preferred: bool,
+}
+
+/// Lists the known runtime providers in detection order.
+#[tauri::command]
+pub fn list_node_runtimes() -> CommandResult<Vec<RuntimeProviderInfo>> {
+    let preferred = preferred_provider();
+    Ok(all_providers()
+        .iter()
+        .map(|provider| {
+            let available = provider.is_available();
+            RuntimeProviderInfo {
+                id: provider.id(),
+                name: provider.name(),
+                available,
+                can_install: provider.can_install(),
+                versions: if available {
+                    provider.installed_versions().unwrap_or_default()
+                } else {
+                    Vec::new()
+                },
+                preferred: preferred.as_deref() == Some(provider.id()),
+            }
+        })
+        .collect())
+}
+
+/// Pins the provider Node.js is taken from; None restores detection. The
+/// environment is then set up again in the background, which also re-points
+/// the WayStation MCP entries at the new runtime.
+#[tauri::command]
+pub fn set_preferred_node_runtime(
+    app_handle: AppHandle,
+    provider: Option<String>,
+) -> CommandResult<()> {
+    if let Some(id) = &provider {
+        if provider_by_id(id).is_none() {
+            return Err(CommandError::new(
+                ErrorCode::InvalidInput,
+                format!("Unknown Node.js runtime provider \"{}\"", id),
+            ));
+        }
+    }
+    let mut settings = load_settings().map_err(|e| CommandError::new(ErrorCode::Io, e))?;
+    settings.preferred_provider = provider.clone();
+    save_settings(&settings).map_err(|e| CommandError::new(ErrorCode::Io, e))?;
+    crate::environment::reset_environment();
+    info!(
+        "Preferred Node.js runtime provider set to {}",
+        provider.as_deref().unwrap_or("automatic")
+    );
+    crate::environment::ensure_environment(app_handle)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FakeProvider {
+        id: &'static str,
+        versions: &'static [&'static str],
+    }
+
+    impl RuntimeProvider for FakeProvider {
+        fn id(&self) -> &'static str {
+            self.id
+        }
+        fn name(&self) -> &'static str {
+            self.id
+        }
+        fn is_available(&self) -> bool {
+            true
+        }
+        fn installed_versions(&self) -> Result<Vec<String>, String> {
+            Ok(self.versions.iter().map(|v| v.to_string()).collect())
+        }
+        fn node_paths(&self, version: &str) -> Result<NodePaths, String> {
+            Err(format!("no paths for {}", version))
+        }
+    }
+
+    fn providers(
+        list: &[(&'static str, &'static [&'static str])],
+    ) -> Vec<Box<dyn RuntimeProvider>> {
+        list.iter()
+            .map(|&(id, versions)| {
+                Box::new(FakeProvider { id, versions }) as Box<dyn RuntimeProvider>
+            })
+            .collect()
+    }
+
+    fn pick(
+        requirement: &str,
+        list: &[(&'static str, &'static [&'static str])],
+    ) -> Option<(&'static str, String)> {
+        newest_installed(providers(list), &parse_requirement(requirement).unwrap())
+            .map(|node| (node.provider.id(), node.version))
+    }
+
+    #[test]
+    fn requirement_accepts_space_and_comma_separated_ranges() {
+        let spaced = parse_requirement(">=18.17 <23").unwrap();
+        let comma = parse_requirement(">=18.17, <23").unwrap();
+        for (version, expected) in [
+            ("18.16.0", false),
+            ("18.17.0", true),
+            ("22.11.0", true),
+            ("23.0.0", false),
+        ] {
+            let version = Version::parse(version).unwrap();
+            assert_eq!(spaced.matches(&version), expected);
+            assert_eq!(comma.matches(&version), expected);
+        }
+        assert!(parse_requirement(DEFAULT_NODE_REQUIREMENT).is_ok());
+        assert!(parse_requirement("latest").is_err());
+    }
+
+    #[test]
+    fn picks_the_newest_matching_version_across_providers() {
+        let list = [
+            ("nvm", &["v18.20.4", "v20.9.0", "v23.1.0"][..]),
+            ("fnm", &["v20.10.0", "v16.0.0"][..]),
+        ];
+        assert_eq!(
+            pick(">=18.17, <23", &list),
+            Some(("fnm", "v20.10.0".to_string()))
+        );
+    }
+
+    #[test]
+    fn compares_versions_numerically_and_skips_unparseable_ones() {
+        let list = [("nvm", &["v20.9.0", "v20.10.0", "lts"][..])];
+        assert_eq!(pick(">=20", &list), Some(("nvm", "v20.10.0".to_string())));
+    }
+
+    #[test]
+    fn first_provider_wins_a_tie() {
+        let list = [("nvm", &["v20.9.0"][..]), ("fnm", &["v20.9.0"][..])];
+        assert_eq!(pick(">=20", &list), Some(("nvm", "v20.9.0".to_string())));
+    }
+
+    #[test]
+    fn nothing_matches() {
+        let list = [("nvm", &["v16.20.0"][..])];
+        assert_eq!(pick(">=18.17, <23", &list), None);
+    }
+}
@@ -0,0 +1,1007 @@
+// Just enough OpenPGP (RFC 4880) to check the detached signature Node.js
+// publishes on SHASUMS256.txt, without relying on gpg being installed: v4
+// public keys (RSA and Ed25519) with their signing subkeys, and v4 signatures
+// over binary documents. Keys are trusted by fingerprint, so the web of trust
+// is out of scope, but a key only signs if its own self-signatures say it
+// may: revoked keys, keys without the sign flag and signatures made after a
+// key expired are all rejected.
+
+use base64::Engine;
+use ring::digest;
+use ring::signature::{self as ring_signature, RsaPublicKeyComponents, UnparsedPublicKey};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const TAG_SIGNATURE: u8 = 2;
+const TAG_PUBLIC_KEY: u8 = 6;
+const TAG_USER_ID: u8 = 13;
+const TAG_PUBLIC_SUBKEY: u8 = 14;
+const TAG_USER_ATTRIBUTE: u8 = 17;
+
+const SIG_BINARY_DOCUMENT: u8 = 0x00;
+const SIG_GENERIC_CERTIFICATION: u8 = 0x10;
+const SIG_POSITIVE_CERTIFICATION: u8 = 0x13;
+const SIG_SUBKEY_BINDING: u8 = 0x18;
+const SIG_PRIMARY_KEY_BINDING: u8 = 0x19;
+const SIG_DIRECT_KEY: u8 = 0x1F;
+const SIG_KEY_REVOCATION: u8 = 0x20;
+const SIG_SUBKEY_REVOCATION: u8 = 0x28;
+
+const SUBPACKET_CREATION_TIME: u8 = 2;
+const SUBPACKET_SIGNATURE_EXPIRATION: u8 = 3;
+const SUBPACKET_KEY_EXPIRATION: u8 = 9;
+const SUBPACKET_ISSUER: u8 = 16;
+const SUBPACKET_KEY_FLAGS: u8 = 27;
+const SUBPACKET_EMBEDDED_SIGNATURE: u8 = 32;
+const SUBPACKET_ISSUER_FINGERPRINT: u8 = 33;
+const KEY_FLAG_SIGN: u8 = 0x02;
+
+// Object identifier of Ed25519 in legacy EdDSA keys
+const ED25519_OID: &[u8] = &[0x2B, 0x06, 0x01, 0x04, 0x01, 0xDA, 0x47, 0x0F, 0x01];
+
+#[derive(Debug, Clone, PartialEq)]
+enum KeyMaterial {
+    Rsa {
+        n: Vec<u8>,
+        e: Vec<u8>,
+    },
+    Ed25519([u8; 32]),
+    /// An algorithm we can't verify with; kept so fingerprints still work.
+    Unsupported(u8),
+}
+
+#[derive(Debug, Clone)]
+struct Key {
+    /// Hex, upper case, as gpg prints it.
+    fingerprint: String,
+    /// Seconds since the epoch.
+    created: u32,
+    // The packet body, which fingerprints and self-signatures cover
+    body: Vec<u8>,
+    material: KeyMaterial,
+}
+
+impl Key {
+    fn key_id(&self) -> &str {
+        &self.fingerprint[self.fingerprint.len() - 16..]
+    }
+}
+
+/// What the newest valid self-signature on a key says about it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Binding {
+    signed_at: u32,
+    can_sign: bool,
+    /// Seconds since the epoch; `None` if the key doesn't expire.
+    expires_at: Option<u64>,
+}
+
+impl Binding {
+    fn new(signature: &Signature, key: &Key) -> Binding {
+        Binding {
+            signed_at: signature.created.unwrap_or(0),
+            // No key flags means no stated use, so not signing either
+            can_sign: signature
+                .key_flags
+                .is_some_and(|flags| flags & KEY_FLAG_SIGN != 0),
+            expires_at: signature
+                .key_expiration
+                .filter(|seconds| *seconds != 0)
+                .map(|seconds| u64::from(key.created) + u64::from(seconds)),
+        }
+    }
+}
+
+// A primary key or subkey with what its self-signatures established
+#[derive(Debug, Clone)]
+struct Component {
+    key: Key,
+    binding: Option<Binding>,
+    revoked: bool,
+}
+
+impl Component {
+    fn new(key: Key) -> Component {
+        Component {
+            key,
+            binding: None,
+            revoked: false,
+        }
+    }
+
+    fn bind(&mut self, binding: Binding) {
+        if self
+            .binding
+            .is_none_or(|current| binding.signed_at >= current.signed_at)
+        {
+            self.binding = Some(binding);
+        }
+    }
+
+    fn usable(&self) -> Option<Binding> {
+        self.binding.filter(|_| !self.revoked)
+    }
+}
+
+/// A primary key and its subkeys, with what their self-signatures allow.
+#[derive(Debug, Clone)]
+pub struct Certificate {
+    primary: Component,
+    subkeys: Vec<Component>,
+}
+
+impl Certificate {
+    /// Fingerprint of the primary key, hex and upper case.
+    pub fn fingerprint(&self) -> &str {
+        &self.primary.key.fingerprint
+    }
+
+    fn keys(&self) -> impl Iterator<Item = &Key> {
+        std::iter::once(&self.primary)
+            .chain(&self.subkeys)
+            .map(|component| &component.key)
+    }
+
+    // Keys that may sign, with when they stop being allowed to. Nothing signs
+    // for a revoked primary key, and subkeys expire with it.
+    fn signing_keys(&self) -> Vec<(&Key, Option<u64>)> {
+        let Some(primary) = self.primary.usable() else {
+            return Vec::new();
+        };
+        std::iter::once(&self.primary)
+            .chain(&self.subkeys)
+            .filter_map(|component| {
+                let binding = component.usable().filter(|binding| binding.can_sign)?;
+                let expires_at = match (primary.expires_at, binding.expires_at) {
+                    (Some(a), Some(b)) => Some(a.min(b)),
+                    (a, b) => a.or(b),
+                };
+                Some((&component.key, expires_at))
+            })
+            .collect()
+    }
+
+    // Applies a signature found after `position` in the certificate's packets.
+    // Only signatures the primary key made over its own parts count.
+    fn apply(&mut self, position: &Position, signature: &Signature) {
+        let primary = &self.primary.key;
+        if signature
+            .issuer()
+            .is_some_and(|issuer| !issuer.matches(primary))
+        {
+            return;
+        }
+        match (position, signature.sig_type) {
+            (_, SIG_KEY_REVOCATION)
+                if check(signature, primary, key_hash_prefix(&primary.body)).is_ok() =>
+            {
+                self.primary.revoked = true;
+            }
+            (Position::Primary, SIG_DIRECT_KEY)
+                if check(signature, primary, key_hash_prefix(&primary.body)).is_ok() =>
+            {
+                let binding = Binding::new(signature, primary);
+                self.primary.bind(binding);
+            }
+            (Position::UserId(user_id), SIG_GENERIC_CERTIFICATION..=SIG_POSITIVE_CERTIFICATION) => {
+                let mut signed = key_hash_prefix(&primary.body);
+                signed.push(0xB4);
+                signed.extend_from_slice(&(user_id.len() as u32).to_be_bytes());
+                signed.extend_from_slice(user_id);
+                if check(signature, primary, signed).is_ok() {
+                    let binding = Binding::new(signature, primary);
+                    self.primary.bind(binding);
+                }
+            }
+            (Position::Subkey, SIG_SUBKEY_BINDING) => {
+                let Some(subkey) = self.subkeys.last_mut() else {
+                    return;
+                };
+                if let Ok(binding) = subkey_binding(primary, &subkey.key, signature) {
+                    subkey.bind(binding);
+                }
+            }
+            (Position::Subkey, SIG_SUBKEY_REVOCATION) => {
+                let Some(subkey) = self.subkeys.last_mut() else {
+                    return;
+                };
+                let mut signed = key_hash_prefix(&primary.body);
+                signed.extend_from_slice(&key_hash_prefix(&subkey.key.body));
+                if check(signature, primary, signed).is_ok() {
+                    subkey.revoked = true;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Who made a signature, as recorded in it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Issuer {
+    Fingerprint(String),
+    KeyId(String),
+}
+
+impl Issuer {
+    fn matches(&self, key: &Key) -> bool {
+        match self {
+            Issuer::Fingerprint(fingerprint) => key.fingerprint == *fingerprint,
+            Issuer::KeyId(key_id) => key.key_id() == key_id,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Signature {
+    sig_type: u8,
+    public_algorithm: u8,
+    hash_algorithm: u8,
+    // From the version byte to the end of the hashed subpackets
+    hashed: Vec<u8>,
+    hash_prefix: [u8; 2],
+    issuer: Option<Issuer>,
+    created: Option<u32>,
+    // Seconds after `created`, like `key_expiration` is after key creation
+    expiration: Option<u32>,
+    key_expiration: Option<u32>,
+    key_flags: Option<u8>,
+    // The primary key binding signature a signing subkey's binding carries
+    embedded: Option<Box<Signature>>,
+    values: Vec<Vec<u8>>,
+}
+
+impl Signature {
+    pub fn issuer(&self) -> Option<&Issuer> {
+        self.issuer.as_ref()
+    }
+}
+
+struct Packet<'a> {
+    tag: u8,
+    body: &'a [u8],
+}
+
+// Which part of a certificate the packets being read belong to
+enum Position {
+    // After a key we can't use, until the next primary key
+    Skipped,
+    Primary,
+    UserId(Vec<u8>),
+    Subkey,
+    // After a user attribute or an unusable subkey
+    Other,
+}
+
+// Reads packets one after another, in both the old and new header formats
+fn packets(mut data: &[u8]) -> Result<Vec<Packet<'_>>, String> {
+    let mut packets = Vec::new();
+    while !data.is_empty() {
+        let header = data[0];
+        if header & 0x80 == 0 {
+            return Err("Not an OpenPGP packet".to_string());
+        }
+        let (tag, length, header_len) = if header & 0x40 != 0 {
+            let (length, length_len) = match data.get(1).copied() {
+                Some(octet @ 0..=191) => (usize::from(octet), 1),
+                Some(octet @ 192..=223) => {
+                    let second = *data.get(2).ok_or("Truncated OpenPGP packet")?;
+                    (
+                        ((usize::from(octet) - 192) << 8) + usize::from(second) + 192,
+                        2,
+                    )
+                }
+                Some(255) => (read_u32(data.get(2..6))? as usize, 5),
+                Some(_) => {
+                    return Err("Partial-length OpenPGP packets are not supported".to_string())
+                }
+                None => return Err("Truncated OpenPGP packet".to_string()),
+            };
+            (header & 0x3F, length, 1 + length_len)
+        } else {
+            let tag = (header >> 2) & 0x0F;
+            match header & 0x03 {
+                0 => (
+                    tag,
+                    usize::from(*data.get(1).ok_or("Truncated OpenPGP packet")?),
+                    2,
+                ),
+                1 => (tag, usize::from(read_u16(data.get(1..3))?), 3),
+                2 => (tag, read_u32(data.get(1..5))? as usize, 5),
+                // Indeterminate: runs to the end of the data
+                _ => (tag, data.len() - 1, 1),
+            }
+        };
+        let body = data
+            .get(header_len..header_len + length)
+            .ok_or("Truncated OpenPGP packet")?;
+        packets.push(Packet { tag, body });
+        data = &data[header_len + length..];
+    }
+    Ok(packets)
+}
+
+fn read_u16(bytes: Option<&[u8]>) -> Result<u16, String> {
+    let bytes = bytes.ok_or("Truncated OpenPGP data")?;
+    Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(bytes: Option<&[u8]>) -> Result<u32, String> {
+    let bytes = bytes.ok_or("Truncated OpenPGP data")?;
+    Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+// A multiprecision integer: a bit count followed by the big-endian bytes.
+// Returns the bytes and what follows.
+fn read_mpi(data: &[u8]) -> Result<(&[u8], &[u8]), String> {
+    let bits = read_u16(data.get(0..2))?;
+    let length = usize::from(bits).div_ceil(8);
+    let value = data.get(2..2 + length).ok_or("Truncated OpenPGP integer")?;
+    Ok((value, &data[2 + length..]))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .unwrap_or(0)
+}
+
+// The bodies of the ASCII-armored blocks in `data` one after another, or the
+// data itself if it is binary. Text around the blocks is ignored.
+fn dearmor(data: &[u8]) -> Result<Vec<u8>, String> {
+    // Binary data starts with a packet header, which has the top bit set
+    if data.first().is_some_and(|octet| octet & 0x80 != 0) {
+        return Ok(data.to_vec());
+    }
+    let text = std::str::from_utf8(data).map_err(|_| "Not OpenPGP data".to_string())?;
+    let mut decoded = Vec::new();
+    // The base64 of the block being read, if any
+    let mut block: Option<String> = None;
+    let mut in_headers = false;
+    for line in text.lines().map(str::trim) {
+        let Some(body) = block.as_mut() else {
+            if line.starts_with("-----BEGIN PGP") {
+                block = Some(String::new());
+                in_headers = true;
+            }
+            continue;
+        };
+        if line.starts_with("-----END PGP") {
+            let bytes = base64::engine::general_purpose::STANDARD
+                .decode(body.as_bytes())
+                .map_err(|e| format!("Invalid ASCII armor: {}", e))?;
+            decoded.extend(bytes);
+            block = None;
+            continue;
+        }
+        // Armor headers ("Comment: ...") end at the first blank line
+        if in_headers {
+            in_headers = line.contains(": ");
+            if in_headers || line.is_empty() {
+                continue;
+            }
+        }
+        // The "=XXXX" line is a CRC24 checksum of the body
+        if line.starts_with('=') {
+            continue;
+        }
+        body.push_str(line);
+    }
+    if block.is_some() {
+        return Err("Truncated ASCII armor".to_string());
+    }
+    Ok(decoded)
+}
+
+fn parse_key(body: &[u8]) -> Result<Option<Key>, String> {
+    // v3 keys are long obsolete and v5/v6 keys are not used for releases
+    if body.first() != Some(&4) {
+        return Ok(None);
+    }
+    let algorithm = *body.get(5).ok_or("Truncated OpenPGP key")?;
+    let created = read_u32(body.get(1..5))?;
+    let fields = &body[6..];
+    let material = match algorithm {
+        1 | 3 => {
+            let (n, rest) = read_mpi(fields)?;
+            let (e, _) = read_mpi(rest)?;
+            KeyMaterial::Rsa {
+                n: n.to_vec(),
+                e: e.to_vec(),
+            }
+        }
+        22 => {
+            let oid_len = usize::from(*fields.first().ok_or("Truncated OpenPGP key")?);
+            let oid = fields.get(1..1 + oid_len).ok_or("Truncated OpenPGP key")?;
+            let (point, _) = read_mpi(&fields[1 + oid_len..])?;
+            // Native points carry a 0x40 prefix
+            match (oid == ED25519_OID, point) {
+                (true, [0x40, rest @ ..]) if rest.len() == 32 => {
+                    KeyMaterial::Ed25519(rest.try_into().unwrap())
+                }
+                _ => KeyMaterial::Unsupported(algorithm),
+            }
+        }
+        27 => KeyMaterial::Ed25519(
+            fields
+                .get(..32)
+                .ok_or("Truncated OpenPGP key")?
+                .try_into()
+                .unwrap(),
+        ),
+        other => KeyMaterial::Unsupported(other),
+    };
+    Ok(Some(Key {
+        fingerprint: hex(
+            digest::digest(&digest::SHA1_FOR_LEGACY_USE_ONLY, &key_hash_prefix(body)).as_ref(),
+        ),
+        created,
+        body: body.to_vec(),
+        material,
+    }))
+}
+
+// How a key is fed into fingerprints and self-signatures
+fn key_hash_prefix(body: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(body.len() + 3);
+    data.push(0x99);
+    data.extend_from_slice(&(body.len() as u16).to_be_bytes());
+    data.extend_from_slice(body);
+    data
+}
+
+// Reads a subpacket area into `signature`. Embedded signatures are only read
+// from a signature that isn't itself embedded, so nesting stays shallow.
+fn parse_subpackets(
+    mut data: &[u8],
+    signature: &mut Signature,
+    embedded: bool,
+) -> Result<(), String> {
+    while !data.is_empty() {
+        let (length, length_len) = match data[0] {
+            octet @ 0..=191 => (usize::from(octet), 1),
+            octet @ 192..=254 => {
+                let second = *data.get(1).ok_or("Truncated signature subpacket")?;
+                (
+                    ((usize::from(octet) - 192) << 8) + usize::from(second) + 192,
+                    2,
+                )
+            }
+            255 => (read_u32(data.get(1..5))? as usize, 5),
+        };
+        let subpacket = data
+            .get(length_len..length_len + length)
+            .filter(|subpacket| !subpacket.is_empty())
+            .ok_or("Truncated signature subpacket")?;
+        let content = &subpacket[1..];
+        let time = || read_u32(Some(content).filter(|content| content.len() == 4));
+        // The top bit only marks the subpacket as critical
+        match subpacket[0] & 0x7F {
+            SUBPACKET_ISSUER_FINGERPRINT if content.len() == 21 && content[0] == 4 => {
+                signature.issuer = Some(Issuer::Fingerprint(hex(&content[1..])));
+            }
+            SUBPACKET_ISSUER if content.len() == 8 && signature.issuer.is_none() => {
+                signature.issuer = Some(Issuer::KeyId(hex(content)));
+            }
+            SUBPACKET_CREATION_TIME => signature.created = Some(time()?),
+            SUBPACKET_SIGNATURE_EXPIRATION => signature.expiration = Some(time()?),
+            SUBPACKET_KEY_EXPIRATION => signature.key_expiration = Some(time()?),
+            SUBPACKET_KEY_FLAGS => signature.key_flags = content.first().copied(),
+            SUBPACKET_EMBEDDED_SIGNATURE if !embedded => {
+                signature.embedded = parse_signature_packet(content, true)?
+                    .filter(|inner| inner.sig_type == SIG_PRIMARY_KEY_BINDING)
+                    .map(Box::new);
+            }
+            _ => {}
+        }
+        data = &data[length_len + length..];
+    }
+    Ok(())
+}
+
+fn parse_signature_packet(body: &[u8], embedded: bool) -> Result<Option<Signature>, String> {
+    if body.first() != Some(&4) {
+        return Ok(None);
+    }
+    let truncated = || "Truncated OpenPGP signature".to_string();
+    let hashed_len = usize::from(read_u16(body.get(4..6))?);
+    let hashed_end = 6 + hashed_len;
+    let unhashed_len = usize::from(read_u16(body.get(hashed_end..hashed_end + 2))?);
+    let unhashed_start = hashed_end + 2;
+    let unhashed_end = unhashed_start + unhashed_len;
+    let hash_prefix = body
+        .get(unhashed_end..unhashed_end + 2)
+        .ok_or_else(truncated)?;
+
+    let mut signature = Signature {
+        sig_type: body[1],
+        public_algorithm: body[2],
+        hash_algorithm: body[3],
+        hashed: body[..hashed_end].to_vec(),
+        hash_prefix: [hash_prefix[0], hash_prefix[1]],
+        issuer: None,
+        created: None,
+        expiration: None,
+        key_expiration: None,
+        key_flags: None,
+        embedded: None,
+        values: Vec::new(),
+    };
+    parse_subpackets(&body[6..hashed_end], &mut signature, embedded)?;
+    // Only the issuer and an embedded signature are taken from the unhashed
+    // area. The issuer is a hint, and a wrong one just fails verification;
+    // an embedded signature is checked on its own.
+    let mut unhashed = signature.clone();
+    parse_subpackets(&body[unhashed_start..unhashed_end], &mut unhashed, embedded)?;
+    if signature.issuer.is_none() {
+        signature.issuer = unhashed.issuer;
+    }
+    signature.embedded = unhashed.embedded;
+
+    let mut rest = &body[unhashed_end + 2..];
+    if signature.public_algorithm == 27 {
+        signature
+            .values
+            .push(rest.get(..64).ok_or_else(truncated)?.to_vec());
+    } else {
+        while !rest.is_empty() {
+            let (value, remaining) = read_mpi(rest)?;
+            signature.values.push(value.to_vec());
+            rest = remaining;
+        }
+    }
+    Ok(Some(signature))
+}
+
+// Checks the binding of `subkey` to `primary`. A signing subkey must also
+// sign the binding back, or anyone could claim someone else's subkey as
+// their own.
+fn subkey_binding(primary: &Key, subkey: &Key, signature: &Signature) -> Result<Binding, String> {
+    let mut signed = key_hash_prefix(&primary.body);
+    signed.extend_from_slice(&key_hash_prefix(&subkey.body));
+    check(signature, primary, signed.clone())?;
+    let binding = Binding::new(signature, subkey);
+    if binding.can_sign {
+        let back = signature.embedded.as_deref().ok_or_else(|| {
+            format!(
+                "Signing subkey {} has no primary key binding signature",
+                subkey.fingerprint
+            )
+        })?;
+        check(back, subkey, signed)?;
+    }
+    Ok(binding)
+}
+
+/// Reads the keys in a keyring or exported key file, binary or armored.
+/// Armored input may hold several blocks.
+pub fn parse_certificates(data: &[u8]) -> Result<Vec<Certificate>, String> {
+    let data = dearmor(data)?;
+    let mut certificates: Vec<Certificate> = Vec::new();
+    let mut position = Position::Skipped;
+    for packet in packets(&data)? {
+        match (packet.tag, &position) {
+            (TAG_PUBLIC_KEY, _) => match parse_key(packet.body)? {
+                Some(primary) => {
+                    certificates.push(Certificate {
+                        primary: Component::new(primary),
+                        subkeys: Vec::new(),
+                    });
+                    position = Position::Primary;
+                }
+                None => position = Position::Skipped,
+            },
+            (_, Position::Skipped) => {}
+            (TAG_PUBLIC_SUBKEY, _) => match parse_key(packet.body)? {
+                Some(subkey) => {
+                    if let Some(certificate) = certificates.last_mut() {
+                        certificate.subkeys.push(Component::new(subkey));
+                    }
+                    position = Position::Subkey;
+                }
+                None => position = Position::Other,
+            },
+            (TAG_USER_ID, _) => position = Position::UserId(packet.body.to_vec()),
+            (TAG_USER_ATTRIBUTE, _) => position = Position::Other,
+            (TAG_SIGNATURE, _) => {
+                let (Some(certificate), Some(signature)) = (
+                    certificates.last_mut(),
+                    parse_signature_packet(packet.body, false)?,
+                ) else {
+                    continue;
+                };
+                certificate.apply(&position, &signature);
+            }
+            // Trust packets and the like
+            _ => {}
+        }
+    }
+    Ok(certificates)
+}
+
+/// Reads a detached signature, binary or armored.
+pub fn parse_signature(data: &[u8]) -> Result<Signature, String> {
+    let data = dearmor(data)?;
+    packets(&data)?
+        .into_iter()
+        .filter(|packet| packet.tag == TAG_SIGNATURE)
+        .find_map(|packet| parse_signature_packet(packet.body, false).transpose())
+        .unwrap_or_else(|| Err("No OpenPGP v4 signature found".to_string()))
+}
+
+// Left-pads a big-endian integer to `length` bytes
+fn left_pad(value: &[u8], length: usize) -> Option<Vec<u8>> {
+    let value = &value[value.iter().take_while(|b| **b == 0).count()..];
+    let padding = length.checked_sub(value.len())?;
+    let mut padded = vec![0u8; padding];
+    padded.extend_from_slice(value);
+    Some(padded)
+}
+
+// Checks `signature` by `key` over `signed`, the data before the signature's
+// own hashed part
+fn check(signature: &Signature, key: &Key, mut signed: Vec<u8>) -> Result<(), String> {
+    signed.extend_from_slice(&signature.hashed);
+    signed.extend_from_slice(&[0x04, 0xFF]);
+    signed.extend_from_slice(&(signature.hashed.len() as u32).to_be_bytes());
+
+    let (digest_algorithm, rsa_parameters): (_, &ring_signature::RsaParameters) =
+        match signature.hash_algorithm {
+            // SHA-1 only for the self-signatures of older keys
+            2 if signature.sig_type != SIG_BINARY_DOCUMENT => (
+                &digest::SHA1_FOR_LEGACY_USE_ONLY,
+                &ring_signature::RSA_PKCS1_2048_8192_SHA1_FOR_LEGACY_USE_ONLY,
+            ),
+            8 => (&digest::SHA256, &ring_signature::RSA_PKCS1_2048_8192_SHA256),
+            9 => (&digest::SHA384, &ring_signature::RSA_PKCS1_2048_8192_SHA384),
+            10 => (&digest::SHA512, &ring_signature::RSA_PKCS1_2048_8192_SHA512),
+            other => return Err(format!("Unsupported signature hash algorithm {}", other)),
+        };
+    let hash = digest::digest(digest_algorithm, &signed);
+    if hash.as_ref()[..2] != signature.hash_prefix {
+        return Err("Signature does not match the signed data".to_string());
+    }
+
+    let bad = || "Signature does not match the signed data".to_string();
+    match (&key.material, signature.public_algorithm) {
+        (KeyMaterial::Rsa { n, e }, 1 | 3) => {
+            let value = signature.values.first().ok_or_else(bad)?;
+            let value = left_pad(value, n.len()).ok_or_else(bad)?;
+            RsaPublicKeyComponents { n, e }
+                .verify(rsa_parameters, &signed, &value)
+                .map_err(|_| bad())
+        }
+        (KeyMaterial::Ed25519(point), 22 | 27) => {
+            // Legacy EdDSA stores R and S as separate integers
+            let value = match signature.values.as_slice() {
+                [r, s] => {
+                    let mut value = left_pad(r, 32).ok_or_else(bad)?;
+                    value.extend(left_pad(s, 32).ok_or_else(bad)?);
+                    value
+                }
+                [value] => value.clone(),
+                _ => return Err(bad()),
+            };
+            // EdDSA signs the digest rather than the data
+            UnparsedPublicKey::new(&ring_signature::ED25519, point)
+                .verify(hash.as_ref(), &value)
+                .map_err(|_| bad())
+        }
+        (KeyMaterial::Unsupported(algorithm), _) => Err(format!(
+            "Key {} uses unsupported algorithm {}",
+            key.fingerprint, algorithm
+        )),
+        _ => Err("Signature algorithm does not match the key".to_string()),
+    }
+}
+
+/// Checks a detached signature over `data` against `certificates`, returning
+/// the fingerprint of the certificate whose key made it. The key must have
+/// been allowed to sign when the signature was made, and still not revoked.
+pub fn verify_detached(
+    data: &[u8],
+    signature: &Signature,
+    certificates: &[Certificate],
+) -> Result<String, String> {
+    if signature.sig_type != SIG_BINARY_DOCUMENT {
+        return Err(format!(
+            "Unexpected signature type 0x{:02x}",
+            signature.sig_type
+        ));
+    }
+    let issuer = signature
+        .issuer()
+        .ok_or("Signature does not say which key made it")?;
+    let certificate = certificates
+        .iter()
+        .find(|certificate| certificate.keys().any(|key| issuer.matches(key)))
+        .ok_or_else(|| format!("Signed by an unknown key ({:?})", issuer))?;
+    let (key, expires_at) = certificate
+        .signing_keys()
+        .into_iter()
+        .find(|(key, _)| issuer.matches(key))
+        .ok_or_else(|| {
+            format!(
+                "Key {:?} of {} is revoked or not allowed to sign",
+                issuer,
+                certificate.fingerprint()
+            )
+        })?;
+    check(signature, key, data.to_vec())?;
+
+    let created = signature
+        .created
+        .ok_or("Signature does not say when it was made")?;
+    if created < key.created {
+        return Err(format!("Signature predates key {}", key.fingerprint));
+    }
+    if expires_at.is_some_and(|expires_at| u64::from(created) >= expires_at) {
+        return Err(format!(
+            "Signature was made after key {} expired",
+            key.fingerprint
+        ));
+    }
+    if let Some(seconds) = signature.expiration.filter(|seconds| *seconds != 0) {
+        if u64::from(created) + u64::from(seconds) <= now() {
+            return Err("Signature has expired".to_string());
+        }
+    }
+    Ok(certificate.fingerprint().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Made with gpg: an RSA key that signs with its primary key, and an
+    // Ed25519 key that signs with a subkey
+    const DATA: &[u8] = include_bytes!("../testdata/openpgp/SHASUMS256.txt");
+    const RSA_KEY: &[u8] = include_bytes!("../testdata/openpgp/rsa.asc");
+    const RSA_SIGNATURE: &[u8] = include_bytes!("../testdata/openpgp/rsa.sig");
+    const RSA_FINGERPRINT: &str = "4610E475C91E3AE476FB8085AEAA652B341F9D84";
+    const ED25519_KEY: &[u8] = include_bytes!("../testdata/openpgp/ed25519.gpg");
+    const ED25519_SIGNATURE: &[u8] = include_bytes!("../testdata/openpgp/ed25519.sig");
+    const ED25519_FINGERPRINT: &str = "93CA24F7B909AE27F0D42A17AC3B6395DBA3610A";
+    // An Ed25519 key revoked after it made REVOKED_SIGNATURE
+    const REVOKED_KEY: &[u8] = include_bytes!("../testdata/openpgp/revoked.gpg");
+    const REVOKED_SIGNATURE: &[u8] = include_bytes!("../testdata/openpgp/revoked.sig");
+    // An Ed25519 key that expired on 2024-01-02, with a signature from the
+    // day before and one from the day after
+    const EXPIRING_KEY: &[u8] = include_bytes!("../testdata/openpgp/expiring.gpg");
+    const EARLY_SIGNATURE: &[u8] = include_bytes!("../testdata/openpgp/expiring-early.sig");
+    const LATE_SIGNATURE: &[u8] = include_bytes!("../testdata/openpgp/expiring-late.sig");
+
+    fn verify(key: &[u8], signature: &[u8], data: &[u8]) -> Result<String, String> {
+        let certificates = parse_certificates(key)?;
+        verify_detached(data, &parse_signature(signature)?, &certificates)
+    }
+
+    #[test]
+    fn computes_fingerprints_like_gpg() {
+        let certificates = parse_certificates(RSA_KEY).unwrap();
+        assert_eq!(certificates.len(), 1);
+        assert_eq!(certificates[0].fingerprint(), RSA_FINGERPRINT);
+        let certificates = parse_certificates(ED25519_KEY).unwrap();
+        assert_eq!(certificates[0].fingerprint(), ED25519_FINGERPRINT);
+        assert_eq!(certificates[0].subkeys.len(), 1);
+    }
+
+    #[test]
+    fn verifies_rsa_signature() {
+        assert_eq!(
+            verify(RSA_KEY, RSA_SIGNATURE, DATA).unwrap(),
+            RSA_FINGERPRINT
+        );
+    }
+
+    #[test]
+    fn verifies_ed25519_subkey_signature() {
+        assert_eq!(
+            verify(ED25519_KEY, ED25519_SIGNATURE, DATA).unwrap(),
+            ED25519_FINGERPRINT
+        );
+    }
+
+    #[test]
+    fn rejects_modified_data() {
+        let mut data = DATA.to_vec();
+        data[0] ^= 1;
+        assert!(verify(RSA_KEY, RSA_SIGNATURE, &data).is_err());
+        assert!(verify(ED25519_KEY, ED25519_SIGNATURE, &data).is_err());
+    }
+
+    #[test]
+    fn rejects_signature_by_unknown_key() {
+        let error = verify(ED25519_KEY, RSA_SIGNATURE, DATA).unwrap_err();
+        assert!(error.contains("unknown key"), "{}", error);
+    }
+
+    #[test]
+    fn ignores_subkeys_without_binding_signature() {
+        // Drop the packets after the subkey, which include its binding
+        let data = dearmor(ED25519_KEY).unwrap();
+        let mut end = 0;
+        for packet in packets(&data).unwrap() {
+            let offset = packet.body.as_ptr() as usize - data.as_ptr() as usize;
+            if packet.tag == TAG_PUBLIC_SUBKEY {
+                end = offset + packet.body.len();
+            }
+        }
+        let error = verify(&data[..end], ED25519_SIGNATURE, DATA).unwrap_err();
+        assert!(error.contains("not allowed to sign"), "{}", error);
+    }
+
+    // A new-format packet header for `body`
+    fn packet(tag: u8, body: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0xC0 | tag, 0xFF];
+        packet.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        packet.extend_from_slice(body);
+        packet
+    }
+
+    #[test]
+    fn rejects_signature_by_revoked_key() {
+        let error = verify(REVOKED_KEY, REVOKED_SIGNATURE, DATA).unwrap_err();
+        assert!(error.contains("revoked"), "{}", error);
+    }
+
+    #[test]
+    fn checks_signatures_against_key_expiry() {
+        assert!(verify(EXPIRING_KEY, EARLY_SIGNATURE, DATA).is_ok());
+        let error = verify(EXPIRING_KEY, LATE_SIGNATURE, DATA).unwrap_err();
+        assert!(error.contains("expired"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_signing_subkey_without_back_signature() {
+        // Empty the unhashed area of the subkey binding, which holds the
+        // subkey's signature over the binding; the binding itself stays valid
+        let data = dearmor(ED25519_KEY).unwrap();
+        let mut rebuilt = Vec::new();
+        for packet in packets(&data).unwrap() {
+            let signature = parse_signature_packet(packet.body, false).ok().flatten();
+            if packet.tag != TAG_SIGNATURE
+                || signature.map(|signature| signature.sig_type) != Some(SIG_SUBKEY_BINDING)
+            {
+                rebuilt.extend(self::packet(packet.tag, packet.body));
+                continue;
+            }
+            let hashed_end = 6 + usize::from(read_u16(packet.body.get(4..6)).unwrap());
+            let unhashed_len =
+                usize::from(read_u16(packet.body.get(hashed_end..hashed_end + 2)).unwrap());
+            let mut body = packet.body[..hashed_end].to_vec();
+            body.extend_from_slice(&[0, 0]);
+            body.extend_from_slice(&packet.body[hashed_end + 2 + unhashed_len..]);
+            rebuilt.extend(self::packet(packet.tag, &body));
+        }
+
+        let certificates = parse_certificates(&rebuilt).unwrap();
+        assert_eq!(certificates[0].fingerprint(), ED25519_FINGERPRINT);
+        assert!(certificates[0].signing_keys().is_empty());
+        let error = verify(&rebuilt, ED25519_SIGNATURE, DATA).unwrap_err();
+        assert!(error.contains("not allowed to sign"), "{}", error);
+    }
+
+    #[test]
+    fn subkey_without_key_flags_cannot_sign() {
+        let data = dearmor(ED25519_KEY).unwrap();
+        let packets = packets(&data).unwrap();
+        let key = |tag| {
+            packets
+                .iter()
+                .find(|packet| packet.tag == tag)
+                .and_then(|packet| parse_key(packet.body).unwrap())
+                .unwrap()
+        };
+        let (primary, subkey) = (key(TAG_PUBLIC_KEY), key(TAG_PUBLIC_SUBKEY));
+        let mut binding = packets
+            .iter()
+            .filter(|packet| packet.tag == TAG_SIGNATURE)
+            .filter_map(|packet| parse_signature_packet(packet.body, false).unwrap())
+            .find(|signature| signature.sig_type == SIG_SUBKEY_BINDING)
+            .unwrap();
+
+        assert!(
+            subkey_binding(&primary, &subkey, &binding)
+                .unwrap()
+                .can_sign
+        );
+        // The flags are hashed, so this leaves the binding signature valid
+        binding.key_flags = None;
+        assert!(
+            !subkey_binding(&primary, &subkey, &binding)
+                .unwrap()
+                .can_sign
+        );
+    }
+
+    #[test]
+    fn primary_key_without_sign_flag_cannot_sign() {
+        // The Ed25519 primary key is only flagged for certifying
+        let certificates = parse_certificates(ED25519_KEY).unwrap();
+        let signers: Vec<_> = certificates[0]
+            .signing_keys()
+            .into_iter()
+            .map(|(key, _)| key.fingerprint.clone())
+            .collect();
+        assert_eq!(signers.len(), 1);
+        assert_ne!(signers[0], ED25519_FINGERPRINT);
+    }
+
+    #[test]
+    fn reads_several_armored_blocks() {
+        let rsa = std::str::from_utf8(RSA_KEY).unwrap();
+        let bundle = format!("Release keys\n\n{}\nmore text\n{}", rsa, rsa);
+        assert_eq!(parse_certificates(bundle.as_bytes()).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        let armored = std::str::from_utf8(RSA_KEY).unwrap();
+        let unterminated = &armored[..armored.find("-----END").unwrap()];
+        let cases: &[&[u8]] = &[
+            // A second packet without a header
+            &[0x88, 0x00, 0x00],
+            b"\xFFnot a packet",
+            // Partial body length
+            &[0xC2, 0xE0, 0x00],
+            // Old format, body longer than the data
+            &[0x88, 0x10, 0x04],
+            unterminated.as_bytes(),
+            b"-----BEGIN PGP SIGNATURE-----\n\n!!!!\n-----END PGP SIGNATURE-----\n",
+        ];
+        for case in cases {
+            assert!(parse_certificates(case).is_err(), "{:?}", case);
+            assert!(parse_signature(case).is_err(), "{:?}", case);
+        }
+        assert!(parse_signature(b"").is_err());
+        assert!(parse_signature(b"no armored blocks").is_err());
+        assert!(parse_certificates(b"no armored blocks").unwrap().is_empty());
+        // A signature whose hashed area runs past the packet
+        assert!(parse_signature(&[0xC2, 0x06, 0x04, 0x00, 0x16, 0x08, 0xFF, 0xFF]).is_err());
+    }
+
+    #[test]
+    fn survives_truncated_and_corrupted_input() {
+        let inputs = [
+            dearmor(RSA_KEY).unwrap(),
+            ED25519_KEY.to_vec(),
+            REVOKED_KEY.to_vec(),
+            RSA_SIGNATURE.to_vec(),
+            ED25519_SIGNATURE.to_vec(),
+        ];
+        // Only checks that nothing panics; a flipped bit in an unhashed area
+        // can legitimately leave the result unchanged
+        let attempt = |data: &[u8]| {
+            let certificates = parse_certificates(data).unwrap_or_default();
+            if let Ok(signature) = parse_signature(data) {
+                let _ = verify_detached(DATA, &signature, &certificates);
+            }
+        };
+        // xorshift, so failures reproduce
+        let mut state = 0x2545_F491_4F6C_DD1Du64;
+        let mut random = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for input in &inputs {
+            for end in 0..input.len() {
+                attempt(&input[..end]);
+            }
+            for _ in 0..500 {
+                let mut data = input.clone();
+                for _ in 0..1 + random() % 4 {
+                    let index = random() as usize % data.len();
+                    data[index] ^= 1 << (random() % 8);
+                }
+                attempt(&data);
+            }
+        }
+    }
+}
@@ -0,0 +1,39 @@
+// OAuth 2.0 token revocation (RFC 7009)
+
+use crate::auth_config::AuthConfig;
+use reqwest::Client;
+
+/// Asks the authorization server to invalidate `token`. `token_type_hint` is
+/// either "access_token" or "refresh_token".
+pub async fn revoke_token(
+    client: &Client,
+    config: &AuthConfig,
+    token: &str,
+    token_type_hint: &str,
+) -> Result<(), String> {
+    let revocation_endpoint = config
+        .revocation_endpoint
+        .as_ref()
+        .ok_or("The identity provider does not support token revocation")?;
+
+    let params = [
+        ("client_id", config.client_id.as_str()),
+        ("token", token),
+        ("token_type_hint", token_type_hint),
+    ];
+
+    let response = client
+        .post(revocation_endpoint)
+        .form(&params)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // The server answers 200 for unknown or already revoked tokens as well
+    if !response.status().is_success() {
+        let error_text = response.text().await.map_err(|e| e.to_string())?;
+        return Err(format!("Token revocation failed: {}", error_text));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,305 @@
+use crate::auth_config::{get_auth_config, http_client};
+use crate::credentials::{account_id, CredentialStore, CredentialStoreManager};
+use crate::error::{CommandError, ErrorCode};
+use crate::id_token::validate_id_token;
+use crate::{delete_way_key, save_way_key, way_key_exists, AuthData, TokenResponse};
+use log::{debug, error, info, warn};
+use once_cell::sync::Lazy;
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager};
+
+// Refresh this long before the access token expires
+const REFRESH_LEAD_SECS: u64 = 5 * 60;
+// How often the scheduler re-reads the stored tokens
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+// Retry delays for transient failures
+const INITIAL_BACKOFF: Duration = Duration::from_secs(5);
+const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+
+// Serializes refreshes so the scheduler and the `refresh_token` command
+// never redeem the same (possibly single-use) refresh token twice. Signing
+// out holds it too, so a refresh can't store an account that is being removed.
+pub(crate) static REFRESH_LOCK: Lazy<tokio::sync::Mutex<()>> =
+    Lazy::new(|| tokio::sync::Mutex::new(()));
+
+#[derive(Debug)]
+pub enum RefreshError {
+    /// The token endpoint could not be reached or failed for a reason that
+    /// may pass; worth retrying.
+    Transient(String),
+    /// The refresh token or client was rejected as invalid, or there is no
+    /// refresh token; the user has to sign in again.
+    Rejected(String),
+    /// Reading or writing the credential store failed.
+    Storage(String),
+}
+
+impl From<RefreshError> for CommandError {
+    fn from(e: RefreshError) -> Self {
+        let code = match e {
+            RefreshError::Transient(_) => ErrorCode::Network,
+            RefreshError::Rejected(_) => ErrorCode::NotAuthenticated,
+            RefreshError::Storage(_) => ErrorCode::CredentialStorage,
+        };
+        CommandError::new(code, e.to_string())
+    }
+}
+
+impl fmt::Display for RefreshError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RefreshError::Transient(msg) => write!(f, "Token refresh failed: {}", msg),
+            RefreshError::Rejected(msg) => write!(f, "Token refresh failed: {}", msg),
+            RefreshError::Storage(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// Exchanges the stored refresh token for new tokens, saves them and updates
+/// the MCP token file.
+pub async fn refresh_auth_data(
+    credentials: &dyn CredentialStore,
+) -> Result<AuthData, RefreshError> {
+    let _guard = REFRESH_LOCK.lock().await;
+    refresh_locked(credentials).await
+}
+
+async fn refresh_locked(credentials: &dyn CredentialStore) -> Result<AuthData, RefreshError> {
+    // Get current auth data
+    let auth_data = credentials
+        .load()
+        .map_err(RefreshError::Storage)?
+        .ok_or_else(|| RefreshError::Rejected("No auth data found".to_string()))?;
+
+    let refresh_token = auth_data
+        .refresh_token
+        .clone()
+        .ok_or_else(|| RefreshError::Rejected("No refresh token available".to_string()))?;
+
+    // Exchange refresh token for new tokens
+    let config = get_auth_config().await.map_err(RefreshError::Transient)?;
+    let client = http_client();
+    let params = [
+        ("client_id", config.client_id.as_str()),
+        ("refresh_token", &refresh_token),
+        ("grant_type", "refresh_token"),
+    ];
+
+    let token_response = client
+        .post(&config.token_endpoint)
+        .form(&params)
+        .send()
+        .await
+        .map_err(|e| RefreshError::Transient(e.to_string()))?;
+
+    let status = token_response.status();
+    if !status.is_success() {
+        let error_text = token_response
+            .text()
+            .await
+            .map_err(|e| RefreshError::Transient(e.to_string()))?;
+        return Err(classify_failure(status.as_u16(), error_text));
+    }
+
+    let tokens: TokenResponse = token_response
+        .json()
+        .await
+        .map_err(|e| RefreshError::Transient(e.to_string()))?;
+
+    // Calculate token expiration
+    let expires_at = tokens.expires_in.map(|expires_in| now_secs() + expires_in);
+
+    // A refreshed ID token that fails validation is dropped rather than
+    // failing the refresh; the previous one stays in place
+    let id_token = match tokens.id_token {
+        Some(id_token) => match validate_id_token(&id_token, None).await {
+            Ok(_) => Some(id_token),
+            Err(e) => {
+                warn!("Ignoring refreshed ID token: {}", e);
+                None
+            }
+        },
+        None => None,
+    };
+
+    // Update auth data
+    let new_auth_data = AuthData {
+        access_token: tokens.access_token,
+        refresh_token: tokens.refresh_token.or(auth_data.refresh_token),
+        id_token: id_token.or(auth_data.id_token),
+        expires_at,
+        user_info: auth_data.user_info,
+    };
+
+    // Save to persistent store
+    credentials
+        .save(&new_auth_data)
+        .map_err(RefreshError::Storage)?;
+
+    // Uninstalling deletes the token file; don't bring it back
+    if way_key_exists() {
+        if let Err(e) = save_way_key(new_auth_data.access_token.clone()).await {
+            warn!("Failed to update MCP token file: {}", e);
+        }
+    }
+
+    Ok(new_auth_data)
+}
+
+// Only an OAuth error saying the grant or client is no longer valid means
+// signing in again (RFC 6749 section 5.2). Anything else, such as rate
+// limiting, timeouts or a proxy's error page, may pass and is retried.
+fn classify_failure(status: u16, body: String) -> RefreshError {
+    let oauth_error = serde_json::from_str::<serde_json::Value>(&body)
+        .ok()
+        .and_then(|body| body.get("error")?.as_str().map(str::to_string));
+    match (status, oauth_error.as_deref()) {
+        (400 | 401, Some("invalid_grant" | "invalid_client")) => RefreshError::Rejected(body),
+        _ => RefreshError::Transient(format!("HTTP {}: {}", status, body)),
+    }
+}
+
+// Returns true when the stored tokens should be refreshed now
+pub(crate) fn needs_refresh(auth_data: &AuthData) -> bool {
+    match (auth_data.expires_at, &auth_data.refresh_token) {
+        (Some(expires_at), Some(_)) => now_secs() + REFRESH_LEAD_SECS >= expires_at,
+        _ => false,
+    }
+}
+
+/// Starts the background task that refreshes tokens ahead of expiry and
+/// emits `auth-success` / `auth-error` to the frontend.
+pub fn start_scheduler(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        run_scheduler(app_handle).await;
+    });
+}
+
+async fn run_scheduler(app_handle: AppHandle) {
+    let credentials = app_handle.state::<CredentialStoreManager>();
+    let mut backoff = INITIAL_BACKOFF;
+
+    loop {
+        let due = match credentials.0.load() {
+            Ok(Some(auth_data)) => needs_refresh(&auth_data),
+            Ok(None) => false,
+            Err(e) => {
+                error!("Failed to read stored credentials: {}", e);
+                false
+            }
+        };
+
+        if !due {
+            backoff = INITIAL_BACKOFF;
+            tokio::time::sleep(CHECK_INTERVAL).await;
+            continue;
+        }
+
+        // Re-check under the lock in case a manual refresh just completed
+        let result = {
+            let _guard = REFRESH_LOCK.lock().await;
+            match credentials.0.load() {
+                Ok(Some(auth_data)) if needs_refresh(&auth_data) => {
+                    let result = refresh_locked(credentials.0.as_ref()).await;
+                    if let Err(RefreshError::Rejected(_)) = result {
+                        // Still under the lock, so `auth_data` is the account
+                        // whose refresh token was rejected, even if another
+                        // sign-in is waiting for the lock
+                        forget_rejected(credentials.0.as_ref(), &auth_data);
+                    }
+                    Some(result)
+                }
+                _ => None,
+            }
+        };
+
+        match result {
+            None => debug!("Tokens already refreshed, skipping scheduled refresh"),
+            Some(Ok(auth_data)) => {
+                info!("Access token refreshed in the background");
+                backoff = INITIAL_BACKOFF;
+                let _ = app_handle.emit("auth-success", &auth_data);
+            }
+            Some(Err(e @ (RefreshError::Transient(_) | RefreshError::Storage(_)))) => {
+                warn!(
+                    "Background token refresh failed, retrying in {}s: {}",
+                    backoff.as_secs(),
+                    e
+                );
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+            Some(Err(e)) => {
+                error!("Background token refresh failed: {}", e);
+                let _ = app_handle.emit("auth-error", CommandError::from(e));
+            }
+        }
+    }
+}
+
+// The refresh token of `auth_data` is no longer usable; drop the account so we
+// stop retrying. Other signed-in accounts are left inactive: the user chooses
+// which one to continue with, since switching would hand that account's token
+// to the MCP server. Callers hold REFRESH_LOCK.
+fn forget_rejected(credentials: &dyn CredentialStore, auth_data: &AuthData) {
+    let forgotten = account_id(auth_data).and_then(|id| credentials.forget_account(&id));
+    if let Err(e) = forgotten {
+        error!("Failed to clear stored credentials: {}", e);
+    }
+    if let Err(e) = delete_way_key() {
+        warn!("{}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_rejected(status: u16, body: &str) -> bool {
+        matches!(
+            classify_failure(status, body.to_string()),
+            RefreshError::Rejected(_)
+        )
+    }
+
+    #[test]
+    fn invalid_grant_and_client_are_rejections() {
+        assert!(is_rejected(400, r#"{"error":"invalid_grant"}"#));
+        assert!(is_rejected(
+            400,
+            r#"{"error":"invalid_grant","error_description":"Refresh token expired"}"#
+        ));
+        assert!(is_rejected(401, r#"{"error":"invalid_client"}"#));
+    }
+
+    #[test]
+    fn other_oauth_errors_are_transient() {
+        assert!(!is_rejected(400, r#"{"error":"invalid_request"}"#));
+        assert!(!is_rejected(400, r#"{"error":"temporarily_unavailable"}"#));
+    }
+
+    #[test]
+    fn rate_limits_timeouts_and_server_errors_are_transient() {
+        assert!(!is_rejected(429, r#"{"error":"invalid_grant"}"#));
+        assert!(!is_rejected(408, ""));
+        assert!(!is_rejected(500, r#"{"error":"invalid_grant"}"#));
+        assert!(!is_rejected(503, "Service Unavailable"));
+    }
+
+    #[test]
+    fn non_oauth_client_errors_are_transient() {
+        // Proxies and captive portals answer with their own pages
+        assert!(!is_rejected(400, "<html>Bad Request</html>"));
+        assert!(!is_rejected(403, "Forbidden"));
+        assert!(!is_rejected(407, "Proxy Authentication Required"));
+        assert!(!is_rejected(401, ""));
+    }
+}
@@ -2,6 +2,7 @@
 
 import { useEffect, useState } from 'react';
 import { authService, AuthData } from '@/app/lib/auth-service';
+import { errorMessage } from '@/app/lib/utils/command-error';
 
 interface AuthButtonProps {
   className?: string;
@@ -31,7 +32,7 @@ export default function AuthButton({ className = '' }: AuthButtonProps) {
       await authService.login();
       // The auth state will be updated via the onAuthChange listener
     } catch (err) {
-      setError(err instanceof Error ? err.message : 'Failed to login');
+      setError(errorMessage(err, 'Failed to login'));
       console.error('Login error:', err);
     } finally {
       setLoading(false);
@@ -45,7 +46,7 @@ export default function AuthButton({ className = '' }: AuthButtonProps) {
       await authService.logout();
       // The auth state will be updated via the onAuthChange listener
     } catch (err) {
-      setError(err instanceof Error ? err.message : 'Failed to logout');
+      setError(errorMessage(err, 'Failed to logout'));
       console.error('Logout error:', err);
     } finally {
       setLoading(false);
@@ -3,6 +3,7 @@
 import { useState } from 'react';
 // For Tauri v2
 import { invoke } from '@tauri-apps/api/core';
+import { errorMessage } from '@/app/lib/utils/command-error';
 
 interface ClaudeButtonProps {
   className?: string;
@@ -11,13 +12,13 @@ interface ClaudeButtonProps {
 export default function ClaudeButton({ className }: ClaudeButtonProps) {
   const [isRestarting, setIsRestarting] = useState(false);
   const [restartStatus, setRestartStatus] = useState<'idle' | 'success' | 'error'>('idle');
-  const [errorMessage, setErrorMessage] = useState('');
+  const [message, setMessage] = useState('');
 
   const handleOpenClaude = async () => {
     try {
       setIsRestarting(true);
       setRestartStatus('idle');
-      setErrorMessage('');
+      setMessage('');
       
       const result = await invoke('restart_claude_app');
       
@@ -25,7 +26,7 @@ export default function ClaudeButton({ className }: ClaudeButtonProps) {
       console.log('Claude app restarted successfully:', result);
     } catch (error) {
       setRestartStatus('error');
-      setErrorMessage(error instanceof Error ? error.message : String(error));
+      setMessage(errorMessage(error, 'Failed to open Claude'));
       console.error('Failed to restart Claude app:', error);
     } finally {
       setIsRestarting(false);
@@ -41,7 +42,7 @@ export default function ClaudeButton({ className }: ClaudeButtonProps) {
       {isRestarting ? 'Opening...' : 'Open Claude'}
       {restartStatus === 'error' && (
         <div className="text-red-500 text-xs mt-1">
-          {errorMessage || 'Failed to open Claude'}
+          {message}
         </div>
       )}
     </button>
@@ -3,6 +3,7 @@
 import { useState } from 'react';
 // For Tauri v2
 import { invoke } from '@tauri-apps/api/core';
+import { errorMessage } from '@/app/lib/utils/command-error';
 
 interface InstallButtonProps {
   className?: string;
@@ -11,13 +12,13 @@ interface InstallButtonProps {
 export default function InstallButton({ className }: InstallButtonProps) {
   const [isInstalling, setIsInstalling] = useState(false);
   const [installStatus, setInstallStatus] = useState<'idle' | 'success' | 'error'>('idle');
-  const [errorMessage, setErrorMessage] = useState('');
+  const [message, setMessage] = useState('');
 
   const handleInstall = async () => {
     try {
       setIsInstalling(true);
       setInstallStatus('idle');
-      setErrorMessage('');
+      setMessage('');
       
       const result = await invoke('install_waystation_mcp');
       
@@ -25,7 +26,7 @@ export default function InstallButton({ className }: InstallButtonProps) {
       console.log('Installation successful:', result);
     } catch (error) {
       setInstallStatus('error');
-      setErrorMessage(error instanceof Error ? error.message : String(error));
+      setMessage(errorMessage(error, 'Installation failed'));
       console.error('Installation failed:', error);
     } finally {
       setIsInstalling(false);
@@ -42,7 +43,7 @@ export default function InstallButton({ className }: InstallButtonProps) {
       {installStatus === 'success' && <span className="ml-2">✓</span>}
       {installStatus === 'error' && (
         <div className="text-red-500 text-xs mt-1">
-          {errorMessage || 'Installation failed'}
+          {message}
         </div>
       )}
     </button>
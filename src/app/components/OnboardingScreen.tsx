@@ -14,6 +14,13 @@ import { TextAnimate } from './magicui/text-animate';
 import { BlurFade } from './magicui/blur-fade';
 import AuthButton from './AuthButton';
 import { markOnboardingCompleted } from '@/app/lib/utils/onboarding';
+import { errorMessage } from '@/app/lib/utils/command-error';
+import {
+  cancelEnvironmentSetup,
+  getEnvironmentStatus,
+  onEnvironmentProgress,
+  type EnvironmentProgress,
+} from '@/app/lib/environment';
 
 import { openUrl } from '@tauri-apps/plugin-opener';
 
@@ -50,6 +57,10 @@ export function OnboardingScreen({
   const [currentStep, setCurrentStep] = useState(0);
   const [isClaudeInstalled, setIsClaudeInstalled] = useState(false);
   const claudeOpened = useRef(false);
+  // Node.js setup, which installing the MCP server runs first
+  const [setup, setSetup] = useState<Omit<EnvironmentProgress, 'output'> | null>(null);
+  const [installError, setInstallError] = useState('');
+  const settingUp = setup?.step === 'detecting' || setup?.step === 'installing_node';
 
   const steps = [
     {
@@ -68,6 +79,27 @@ export function OnboardingScreen({
     },
   ];
 
+  useEffect(() => {
+    let active = true;
+    let unlisten: (() => void) | undefined;
+    onEnvironmentProgress(setSetup).then((fn) => {
+      if (active) unlisten = fn;
+      else fn();
+    });
+    // Picks up a setup that started before we were listening
+    getEnvironmentStatus()
+      .then((status) => {
+        if (active) setSetup((current) => current ?? status);
+      })
+      .catch((error) => {
+        console.error("Failed to get environment status:", error);
+      });
+    return () => {
+      active = false;
+      unlisten?.();
+    };
+  }, []);
+
   useEffect(() => {
     // Check if Claude is installed when component mounts or when step 1 is reached
     if (currentStep === 1) {
@@ -128,18 +160,29 @@ export function OnboardingScreen({
     };
   }, [currentStep]);
 
-  const onDropSuccess = () => {
-    setCurrentStep(currentStep + 1);
-
+  const installMcp = () => {
+    setInstallError('');
     invoke("install_waystation_mcp")
       .then(() => {
         console.log("Successfully installed waystation-mcp");
       })
       .catch((error) => {
         console.error("Failed to install waystation-mcp:", error);
+        setInstallError(errorMessage(error, "Failed to install waystation-mcp"));
       });
   };
 
+  const onDropSuccess = () => {
+    setCurrentStep(currentStep + 1);
+    installMcp();
+  };
+
+  const handleCancelSetup = () => {
+    cancelEnvironmentSetup().catch((error) => {
+      console.error("Failed to cancel environment setup:", error);
+    });
+  };
+
   const handleOpenClaude = () => {
     claudeOpened.current = true; // Set flag indicating Claude was opened
 
@@ -231,11 +274,29 @@ export function OnboardingScreen({
                           ? handleOpenClaude
                           : handleDownloadClaude
                       }
+                      // Claude only sees WayStation once setup has finished
+                      disabled={isClaudeInstalled && settingUp}
                       variant="secondary"
                       className="w-full aurora-btn">
                       {isClaudeInstalled ? "Open Claude" : "Download Claude"}
                     </Button>
                   </div>
+                  {settingUp && setup && (
+                    <div className="text-xs text-center text-[#535d75] mt-3">
+                      {setup.message} ({setup.percent}%)
+                      <button className="ml-2 underline" onClick={handleCancelSetup}>
+                        Cancel
+                      </button>
+                    </div>
+                  )}
+                  {!settingUp && installError && (
+                    <div className="text-xs text-center text-red-500 mt-3">
+                      {installError}
+                      <button className="ml-2 underline" onClick={installMcp}>
+                        Try again
+                      </button>
+                    </div>
+                  )}
                 </BlurFade>
               )}
               {currentStep === 2 && (
@@ -1,3 +1,4 @@
+import { CommandError } from '@/app/lib/utils/command-error';
 import { markOnboardingCompleted, resetOnboardingStatus } from '@/app/lib/utils/onboarding';
 import { invoke } from '@tauri-apps/api/core';
 import { listen } from '@tauri-apps/api/event';
@@ -13,6 +14,23 @@ export interface UserInfo {
   picture?: string;
 }
 
+export interface LogoutResult {
+  refresh_token_revoked: boolean;
+  access_token_revoked: boolean;
+  credentials_deleted: boolean;
+  way_key_deleted: boolean;
+  next_account?: AuthData;
+  errors: string[];
+}
+
+export interface AccountSummary {
+  sub: string;
+  name?: string;
+  email?: string;
+  picture?: string;
+  active: boolean;
+}
+
 export interface AuthData {
   access_token: string;
   refresh_token?: string;
@@ -24,7 +42,6 @@ export interface AuthData {
 class AuthService {
   private authListeners: ((authData: AuthData | null) => void)[] = [];
   private authData: AuthData | null = null;
-  private refreshTimer: number | null = null;
 
   constructor() {
     this.initialize();
@@ -34,9 +51,11 @@ class AuthService {
     // Listen for auth events from Tauri
     await listen<AuthData>('auth-success', (event) => {
       this.setAuthData(event.payload);
+      // Loopback logins complete in Rust and only report back through this event
+      markOnboardingCompleted();
     });
 
-    await listen<string>('auth-error', (event) => {
+    await listen<CommandError>('auth-error', (event) => {
       console.error('Authentication error:', event.payload);
       this.setAuthData(null);
     });
@@ -102,10 +121,19 @@ class AuthService {
     }
   }
 
+  // Abandons any browser login that hasn't come back yet
+  public async cancelLogin(): Promise<void> {
+    await invoke<number>('cancel_login');
+  }
+
   public async logout(): Promise<void> {
     try {
-      await invoke<void>('logout');
-      this.setAuthData(null);
+      const result = await invoke<LogoutResult>('logout');
+      if (result.errors.length > 0) {
+        console.warn('Logout completed with errors:', result.errors);
+      }
+      // Another signed-in account becomes active, if there is one
+      this.setAuthData(result.next_account ?? null);
     } catch (error) {
       console.error('Logout failed:', error);
       throw error;
@@ -123,6 +151,37 @@ class AuthService {
     }
   }
 
+  public async listAccounts(): Promise<AccountSummary[]> {
+    return invoke<AccountSummary[]>('list_accounts');
+  }
+
+  public async switchAccount(sub: string): Promise<AuthData> {
+    try {
+      const authData = await invoke<AuthData>('switch_account', { sub });
+      this.setAuthData(authData);
+      return authData;
+    } catch (error) {
+      console.error('Account switch failed:', error);
+      throw error;
+    }
+  }
+
+  public async removeAccount(sub: string): Promise<void> {
+    try {
+      const wasActive = this.authData?.user_info?.sub === sub;
+      const result = await invoke<LogoutResult>('remove_account', { sub });
+      if (result.errors.length > 0) {
+        console.warn('Account removal completed with errors:', result.errors);
+      }
+      if (wasActive) {
+        this.setAuthData(result.next_account ?? null);
+      }
+    } catch (error) {
+      console.error('Account removal failed:', error);
+      throw error;
+    }
+  }
+
   public getAuthData(): AuthData | null {
     return this.authData;
   }
@@ -152,33 +211,10 @@ class AuthService {
   }
 
   private setAuthData(authData: AuthData | null): void {
+    // Token refresh is scheduled by the Rust core, which emits `auth-success`
+    // with the new tokens or `auth-error` when the session can't be renewed.
     this.authData = authData;
     
-    // Clear any existing refresh timer
-    if (this.refreshTimer !== null) {
-      clearTimeout(this.refreshTimer);
-      this.refreshTimer = null;
-    }
-    
-    // Set up token refresh if we have an expiration time
-    if (authData?.expires_at && authData.refresh_token) {
-      const expiresAt = authData.expires_at * 1000; // Convert to milliseconds
-      const now = Date.now();
-      const timeUntilExpiry = expiresAt - now;
-      
-      // Refresh 5 minutes before expiry
-      const refreshTime = Math.max(0, timeUntilExpiry - 5 * 60 * 1000);
-      
-      if (refreshTime > 0) {
-        this.refreshTimer = window.setTimeout(() => {
-          this.refreshToken().catch(console.error);
-        }, refreshTime);
-      } else {
-        // Token is already expired or about to expire, refresh now
-        this.refreshToken().catch(console.error);
-      }
-    }
-    
     // Notify all listeners
     this.authListeners.forEach(listener => listener(authData));
   }
@@ -0,0 +1,37 @@
+import { invoke } from '@tauri-apps/api/core';
+import type { CommandError } from './utils/command-error';
+
+// What happened while shutting Claude Desktop down
+export interface ShutdownReport {
+  // Claude processes found before asking it to quit
+  pids: number[];
+  graceful: boolean;
+  force_killed: boolean;
+  elapsed_ms: number;
+}
+
+// timeoutMs is how long to wait for Claude to quit before force-killing it
+export function restartClaude(timeoutMs?: number): Promise<ShutdownReport> {
+  return invoke<ShutdownReport>('restart_claude_app', { timeoutMs });
+}
+
+export function quitClaude(timeoutMs?: number): Promise<ShutdownReport> {
+  return invoke<ShutdownReport>('quit_claude_app', { timeoutMs });
+}
+
+export interface ClaudeStatus {
+  installed: boolean;
+  path: string | null;
+  version: string | null;
+  running: boolean;
+  pids: number[];
+  config_path: string | null;
+  config_exists: boolean;
+  // false when the config doesn't exist or isn't valid JSON
+  config_valid: boolean;
+  config_error: CommandError | null;
+}
+
+export function getClaudeStatus(): Promise<ClaudeStatus> {
+  return invoke<ClaudeStatus>('get_claude_status');
+}
@@ -0,0 +1,31 @@
+import { invoke } from '@tauri-apps/api/core';
+
+// Snapshots of claude_desktop_config.json taken before every write
+export interface ConfigBackup {
+  id: string;
+  created_at: string;
+  reason: string;
+  size: number;
+}
+
+export interface ConfigBackupDiff {
+  id: string;
+  unified: string;
+  added_servers: string[];
+  removed_servers: string[];
+  changed_servers: string[];
+}
+
+// Newest first
+export function listConfigBackups(): Promise<ConfigBackup[]> {
+  return invoke<ConfigBackup[]>('list_config_backups');
+}
+
+export function diffConfigBackup(id: string): Promise<ConfigBackupDiff> {
+  return invoke<ConfigBackupDiff>('diff_config_backup', { id });
+}
+
+// The config being replaced is backed up too, so a restore can be undone
+export function restoreConfigBackup(id: string): Promise<string> {
+  return invoke<string>('restore_config_backup', { id });
+}
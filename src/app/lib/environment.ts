@@ -0,0 +1,58 @@
+import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import type { CommandError } from './utils/command-error';
+
+export type SetupStep =
+  | 'idle'
+  | 'detecting'
+  | 'installing_node'
+  | 'ready'
+  | 'failed'
+  | 'cancelled';
+
+export interface OutputLine {
+  stream: 'stdout' | 'stderr';
+  line: string;
+}
+
+export interface EnvironmentStatus {
+  step: SetupStep;
+  // Overall progress, 0-100
+  percent: number;
+  message: string;
+  // The Node.js in use, once one was found
+  node_version: string | null;
+  node_provider: string | null;
+  // Installer output of the latest run, up to the last 500 lines
+  output: OutputLine[];
+  error: CommandError | null;
+}
+
+// Sent as setup progresses; only carries the newest output line
+export interface EnvironmentProgress {
+  step: SetupStep;
+  percent: number;
+  message: string;
+  output: OutputLine | null;
+  error: CommandError | null;
+}
+
+// Returns immediately; follow along with onEnvironmentProgress
+export function ensureEnvironment(): Promise<string> {
+  return invoke<string>('ensure_environment');
+}
+
+export function getEnvironmentStatus(): Promise<EnvironmentStatus> {
+  return invoke<EnvironmentStatus>('get_environment_status');
+}
+
+// Resolves to false when no setup was running
+export function cancelEnvironmentSetup(): Promise<boolean> {
+  return invoke<boolean>('cancel_environment_setup');
+}
+
+export function onEnvironmentProgress(
+  handler: (progress: EnvironmentProgress) => void,
+): Promise<UnlistenFn> {
+  return listen<EnvironmentProgress>('environment-progress', (event) => handler(event.payload));
+}
@@ -0,0 +1,57 @@
+import { invoke } from '@tauri-apps/api/core';
+import type { CommandError } from './utils/command-error';
+
+export type McpClientId =
+  | 'claude_desktop'
+  | 'claude_code'
+  // The .mcp.json of the project passed as projectDir
+  | 'claude_code_project'
+  | 'cursor'
+  | 'vscode'
+  | 'windsurf'
+  | 'zed';
+
+export interface McpClient {
+  id: McpClientId;
+  name: string;
+  config_path: string | null;
+  detected: boolean;
+  restart_required: boolean;
+  // null when the client's config couldn't be read
+  waystation_installed: boolean | null;
+}
+
+export interface ClientChange {
+  client: McpClientId;
+  changed: boolean;
+  restart_required: boolean;
+  error: CommandError | null;
+}
+
+// 'all' targets every detected client, plus the project when projectDir is
+// given; omitting clients targets Claude Desktop
+export type ClientSelection = (McpClientId | 'all')[];
+
+export type RestartOutcome = 'restarted' | 'not_needed';
+
+export function listMcpClients(): Promise<McpClient[]> {
+  return invoke<McpClient[]>('list_mcp_clients');
+}
+
+export function installWayStation(
+  clients?: ClientSelection,
+  projectDir?: string,
+): Promise<ClientChange[]> {
+  return invoke<ClientChange[]>('install_waystation_mcp', { clients, projectDir });
+}
+
+export function uninstallWayStationFrom(
+  clients?: ClientSelection,
+  projectDir?: string,
+): Promise<ClientChange[]> {
+  return invoke<ClientChange[]>('uninstall_waystation_mcp', { clients, projectDir });
+}
+
+export function restartMcpClient(client: McpClientId): Promise<RestartOutcome> {
+  return invoke<RestartOutcome>('restart_mcp_client', { client });
+}
@@ -0,0 +1,43 @@
+import { invoke } from '@tauri-apps/api/core';
+
+// Launch settings of a stdio MCP server
+export interface McpServerSpec {
+  command: string;
+  args?: string[];
+  env?: Record<string, string>;
+  cwd?: string | null;
+}
+
+export interface McpServer {
+  name: string;
+  enabled: boolean;
+  // null for servers that aren't launched as a command, e.g. remote ones
+  command: string | null;
+  args: string[];
+  env: Record<string, string>;
+  cwd: string | null;
+}
+
+export function listMcpServers(): Promise<McpServer[]> {
+  return invoke<McpServer[]>('list_mcp_servers');
+}
+
+export function addMcpServer(name: string, server: McpServerSpec): Promise<McpServer> {
+  return invoke<McpServer>('add_mcp_server', { name, server });
+}
+
+export function updateMcpServer(name: string, server: McpServerSpec): Promise<McpServer> {
+  return invoke<McpServer>('update_mcp_server', { name, server });
+}
+
+export function renameMcpServer(name: string, newName: string): Promise<void> {
+  return invoke<void>('rename_mcp_server', { name, newName });
+}
+
+export function setMcpServerEnabled(name: string, enabled: boolean): Promise<void> {
+  return invoke<void>('set_mcp_server_enabled', { name, enabled });
+}
+
+export function removeMcpServer(name: string): Promise<void> {
+  return invoke<void>('remove_mcp_server', { name });
+}
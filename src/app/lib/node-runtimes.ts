@@ -0,0 +1,33 @@
+import { invoke } from '@tauri-apps/api/core';
+
+export type NodeRuntimeId =
+  | 'nvm'
+  | 'nvm-windows'
+  | 'fnm'
+  | 'volta'
+  | 'mise'
+  | 'asdf'
+  | 'managed'
+  | 'system';
+
+export interface NodeRuntime {
+  id: NodeRuntimeId;
+  name: string;
+  // Whether the version manager is installed
+  available: boolean;
+  can_install: boolean;
+  // Installed Node.js versions, e.g. "v20.9.0"
+  versions: string[];
+  preferred: boolean;
+}
+
+// Listed in detection order
+export function listNodeRuntimes(): Promise<NodeRuntime[]> {
+  return invoke<NodeRuntime[]>('list_node_runtimes');
+}
+
+// null goes back to automatic detection. Environment setup then reruns in the
+// background (see onEnvironmentProgress) and re-points the WayStation entries.
+export function setPreferredNodeRuntime(provider: NodeRuntimeId | null): Promise<void> {
+  return invoke<void>('set_preferred_node_runtime', { provider });
+}
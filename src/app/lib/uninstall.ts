@@ -0,0 +1,30 @@
+import { invoke } from '@tauri-apps/api/core';
+import { clearOnboardingCompleted } from '@/app/lib/utils/onboarding';
+
+export interface UninstallReport {
+  config_entry_removed: boolean;
+  token_deleted: boolean;
+  onboarding_reset: boolean;
+  npx_cache_cleared: boolean;
+  claude_restarted: boolean;
+  errors: string[];
+}
+
+export interface UninstallOptions {
+  // Delete the cached @waystation/mcp package so a reinstall fetches it again
+  clearNpxCache?: boolean;
+  // Defaults to true; Claude only drops the server after a restart
+  restartClaude?: boolean;
+}
+
+export async function uninstallWayStation(options: UninstallOptions = {}): Promise<UninstallReport> {
+  const report = await invoke<UninstallReport>('uninstall_waystation', {
+    clearNpxCache: options.clearNpxCache ?? false,
+    restartClaude: options.restartClaude ?? true,
+  });
+  clearOnboardingCompleted();
+  if (report.errors.length > 0) {
+    console.warn('Uninstall completed with errors:', report.errors);
+  }
+  return report;
+}
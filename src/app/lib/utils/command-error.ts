@@ -0,0 +1,43 @@
+// Mirrors `CommandError` in src-tauri/src/error.rs
+export type ErrorCode =
+  | 'claude_not_installed'
+  | 'claude_restart_failed'
+  | 'unsupported_platform'
+  | 'config_io'
+  | 'config_corrupt'
+  | 'config_locked'
+  | 'config_conflict'
+  | 'not_authenticated'
+  | 'auth_failed'
+  | 'auth_config'
+  | 'network'
+  | 'credential_storage'
+  | 'environment_setup_failed'
+  | 'environment_setup_cancelled'
+  | 'io'
+  | 'not_found'
+  | 'already_exists'
+  | 'invalid_input'
+  | 'internal';
+
+export interface CommandError {
+  code: ErrorCode;
+  message: string;
+  details?: string;
+}
+
+export function isCommandError(error: unknown): error is CommandError {
+  return (
+    typeof error === 'object' &&
+    error !== null &&
+    'code' in error &&
+    'message' in error
+  );
+}
+
+export function errorMessage(error: unknown, fallback = 'Something went wrong'): string {
+  if (isCommandError(error)) return error.message;
+  if (error instanceof Error) return error.message;
+  if (typeof error === 'string') return error;
+  return fallback;
+}
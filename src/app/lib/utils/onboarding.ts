@@ -29,6 +29,17 @@ export function markOnboardingCompleted(): void {
   }
 }
 
+/**
+ * Forget that onboarding was completed, without signing out
+ */
+export function clearOnboardingCompleted(): void {
+  try {
+    localStorage.removeItem(ONBOARDING_COMPLETED_KEY);
+  } catch (error) {
+    console.error('Error clearing onboarding status:', error);
+  }
+}
+
 /**
  * Reset the onboarding status (for testing purposes)
  */